@@ -0,0 +1,42 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use graphix_to_hugr::{convert_graphix_pattern_to_hugr, Command, Pattern, Plane};
+
+/// Linear cluster of `n` nodes: every node but the last is measured and
+/// its outcome corrects the next node
+fn linear_cluster(n: usize) -> Pattern {
+    let mut pattern = Pattern::new(vec![0], vec![n - 1]);
+    for node in 1..n {
+        pattern.add_command(Command::N { node });
+    }
+    for node in 1..n {
+        pattern.add_command(Command::E {
+            nodes: (node - 1, node),
+        });
+    }
+    for node in 0..n - 1 {
+        pattern.add_command(Command::M {
+            node,
+            plane: Plane::XY,
+            angle: 0.1,
+        });
+        pattern.add_command(Command::X {
+            node: node + 1,
+            domain: [node].into_iter().collect(),
+        });
+    }
+    pattern
+}
+
+fn bench_linear_cluster(c: &mut Criterion) {
+    let mut group = c.benchmark_group("convert_linear_cluster");
+    for n in [100, 1_000, 10_000] {
+        let pattern = linear_cluster(n);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &pattern, |b, pattern| {
+            b.iter(|| convert_graphix_pattern_to_hugr(black_box(pattern)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_linear_cluster);
+criterion_main!(benches);
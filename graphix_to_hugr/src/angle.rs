@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+
+/// Exact angle expressed as a rational multiple of π
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PiFraction {
+    pub numerator: i64,
+    pub denominator: i64,
+}
+
+impl PiFraction {
+    /// Create `numerator/denominator * π` in lowest terms.
+    ///
+    /// Panics if `denominator` is zero.
+    pub fn new(numerator: i64, denominator: i64) -> Self {
+        assert!(denominator != 0, "PiFraction denominator must be non-zero");
+        
+        let g = gcd(numerator, denominator).max(1);
+        let sign = if denominator < 0 { -1 } else { 1 };
+        Self {
+            numerator: sign * numerator / g,
+            denominator: sign * denominator / g,
+        }
+    }
+    
+    pub fn zero() -> Self {
+        Self::new(0, 1)
+    }
+    
+    pub fn to_radians(&self) -> f64 {
+        self.numerator as f64 * PI / self.denominator as f64
+    }
+    
+    /// Recover an exact fraction from a radian value, if one exists with a
+    /// denominator of at most `max_denominator`
+    pub fn from_radians(angle: f64, max_denominator: i64) -> Option<Self> {
+        let turns = angle / PI;
+        (1..=max_denominator).find_map(|denominator| {
+            let numerator = (turns * denominator as f64).round();
+            if (numerator / denominator as f64 - turns).abs() < 1e-10 {
+                Some(Self::new(numerator as i64, denominator))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+impl Add for PiFraction {
+    type Output = PiFraction;
+    
+    fn add(self, rhs: PiFraction) -> PiFraction {
+        PiFraction::new(
+            self.numerator * rhs.denominator + rhs.numerator * self.denominator,
+            self.denominator * rhs.denominator,
+        )
+    }
+}
+
+impl Sub for PiFraction {
+    type Output = PiFraction;
+    
+    fn sub(self, rhs: PiFraction) -> PiFraction {
+        self + (-rhs)
+    }
+}
+
+impl Neg for PiFraction {
+    type Output = PiFraction;
+    
+    fn neg(self) -> PiFraction {
+        PiFraction::new(-self.numerator, self.denominator)
+    }
+}
+
+/// Formats as `pi`, `-pi/2`, `3*pi/4`, etc. as used in QASM
+impl fmt::Display for PiFraction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.numerator, self.denominator) {
+            (0, _) => write!(f, "0"),
+            (1, 1) => write!(f, "pi"),
+            (-1, 1) => write!(f, "-pi"),
+            (n, 1) => write!(f, "{}*pi", n),
+            (1, d) => write!(f, "pi/{}", d),
+            (-1, d) => write!(f, "-pi/{}", d),
+            (n, d) => write!(f, "{}*pi/{}", n, d),
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use crate::converter::{check_angle, ClassicalOp, ConversionError, GraphixToHugrConverter};
+use crate::hugr::{ConstValue, Hugr, HugrType, Operation, Wire};
+use crate::types::{Angle, CliffordGate, Command, CommandKind, InitState, Pattern};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use thiserror::Error;
+
+/// A single instruction of a flat gate-model circuit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GateInstr {
+    /// Gate name, as emitted in the HUGR (`H`, `CZ`, `Rz`, `Measure`, ...)
+    pub name: String,
+    /// Pattern nodes the gate acts on
+    pub qubits: Vec<usize>,
+    /// Gate parameters (e.g. rotation angles)
+    pub params: Vec<f64>,
+    /// If set, the gate is applied only when these nodes' measurement
+    /// outcomes, folded with `combiner`, give 1
+    pub condition: Option<BTreeSet<usize>>,
+    /// How the `condition` outcomes are combined (parity by default)
+    #[serde(default)]
+    pub combiner: ClassicalOp,
+}
+
+impl GateInstr {
+    fn from_op(op: Operation, qubits: Vec<usize>) -> Self {
+        let (name, params) = match op {
+            Operation::Custom { name, args, .. } => (name, args),
+            other => (format!("{:?}", other), vec![]),
+        };
+        Self {
+            name,
+            qubits,
+            params,
+            condition: None,
+            combiner: ClassicalOp::Xor,
+        }
+    }
+    
+    pub fn is_conditional(&self) -> bool {
+        self.condition.is_some()
+    }
+}
+
+impl fmt::Display for GateInstr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(domain) = &self.condition {
+            match self.combiner {
+                ClassicalOp::Xor => write!(f, "if {:?} ", domain)?,
+                other => write!(f, "if {} {:?} ", other.op_name(), domain)?,
+            }
+        }
+        write!(f, "{}", self.name)?;
+        if !self.params.is_empty() {
+            write!(f, "({:?})", self.params)?;
+        }
+        for q in &self.qubits {
+            write!(f, " q{}", q)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lower a pattern to a linear list of gate instructions, using the same
+/// gate decompositions as the HUGR converter
+pub fn to_gate_list(pattern: &Pattern) -> Result<Vec<GateInstr>, ConversionError> {
+    let lowering = GraphixToHugrConverter::new();
+    let mut live: HashSet<usize> = pattern.input_nodes.iter().cloned().collect();
+    let mut instrs = Vec::new();
+    
+    let check_live = |live: &HashSet<usize>, node: usize| {
+        if live.contains(&node) {
+            Ok(())
+        } else {
+            Err(ConversionError::NodeNotFound(node))
+        }
+    };
+    
+    // Inputs initialised inside the pattern are prepared first, as in the
+    // converter: |+⟩ directly and |0⟩ as |+⟩ followed by H
+    let mut internal: Vec<_> = pattern.input_states.iter().collect();
+    internal.sort_by_key(|(node, _)| **node);
+    for (&node, &state) in internal {
+        if live.contains(&node) {
+            instrs.push(GateInstr::from_op(lowering.create_prepare_op(), vec![node]));
+            if state == InitState::Zero {
+                instrs.push(GateInstr::from_op(Operation::from(CliffordGate::H), vec![node]));
+            }
+        }
+    }
+    
+    for cmd in pattern.iter() {
+        match cmd {
+            Command::N { node } => {
+                instrs.push(GateInstr::from_op(lowering.create_prepare_op(), vec![*node]));
+                live.insert(*node);
+            }
+            Command::E { nodes: (a, b) } => {
+                check_live(&live, *a)?;
+                check_live(&live, *b)?;
+                instrs.push(GateInstr::from_op(lowering.create_cz_gate(), vec![*a, *b]));
+            }
+            Command::M { node, plane, angle }
+            | Command::MAdaptive {
+                node,
+                plane,
+                angle: Angle::Fixed(angle),
+            } => {
+                check_live(&live, *node)?;
+                check_angle(*node, *angle)?;
+                for op in lowering.measurement_basis_change(*plane, *angle) {
+                    instrs.push(GateInstr::from_op(op, vec![*node]));
+                }
+                instrs.push(GateInstr::from_op(lowering.create_measure_op(), vec![*node]));
+                live.remove(node);
+            }
+            Command::MSoft { node, plane, angle } => {
+                check_live(&live, *node)?;
+                check_angle(*node, *angle)?;
+                for op in lowering.measurement_basis_change(*plane, *angle) {
+                    instrs.push(GateInstr::from_op(op, vec![*node]));
+                }
+                instrs.push(GateInstr::from_op(lowering.create_measure_soft_op(), vec![*node]));
+                live.remove(node);
+            }
+            Command::MZ { node } => {
+                check_live(&live, *node)?;
+                instrs.push(GateInstr::from_op(lowering.create_measure_op(), vec![*node]));
+                live.remove(node);
+            }
+            Command::X { node, domain } | Command::Z { node, domain } => {
+                check_live(&live, *node)?;
+                let gate = if matches!(cmd, Command::X { .. }) {
+                    CliffordGate::X
+                } else {
+                    CliffordGate::Z
+                };
+                let mut instr = GateInstr::from_op(Operation::from(gate), vec![*node]);
+                instr.condition = Some(domain.iter().cloned().collect());
+                instrs.push(instr);
+            }
+            Command::XControlled {
+                node,
+                controls,
+                combiner,
+            } => {
+                check_live(&live, *node)?;
+                let mut instr = GateInstr::from_op(Operation::from(CliffordGate::X), vec![*node]);
+                instr.condition = Some(controls.iter().cloned().collect());
+                instr.combiner = *combiner;
+                instrs.push(instr);
+            }
+            Command::C { node, clifford } => {
+                check_live(&live, *node)?;
+                for &gate in clifford.iter().filter(|&&g| g != CliffordGate::I) {
+                    instrs.push(GateInstr::from_op(Operation::from(gate), vec![*node]));
+                }
+            }
+            Command::Gate {
+                node, name, params, ..
+            } => {
+                check_live(&live, *node)?;
+                instrs.push(GateInstr {
+                    name: name.clone(),
+                    qubits: vec![*node],
+                    params: params.clone(),
+                    condition: None,
+                    combiner: ClassicalOp::Xor,
+                });
+            }
+            Command::MAdaptive { .. } => return Err(ConversionError::NotFlat(CommandKind::MAdaptive)),
+            Command::Branch { .. } => return Err(ConversionError::NotFlat(CommandKind::Branch)),
+        }
+    }
+    
+    Ok(instrs)
+}
+
+/// Why a HUGR cannot be exported with `to_classical_register_circuit`
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RegisterCircuitError {
+    #[error("Operation {0} has no classical-register form")]
+    UnsupportedOperation(String),
+    
+    #[error("Node {0} mixes different classical combiners in one condition")]
+    MixedCondition(usize),
+    
+    #[error("Node {0} reads a wire with no known producer")]
+    DanglingWire(usize),
+}
+
+/// Classical condition of a `c_if`: the register bits, folded with
+/// `combiner`, must give 1
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegisterCondition {
+    pub bits: Vec<usize>,
+    pub combiner: ClassicalOp,
+}
+
+/// One instruction of a `RegisterCircuit`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegisterInstr {
+    /// Gate name, as emitted in the HUGR
+    pub name: String,
+    /// Circuit qubit indices the gate acts on
+    pub qubits: Vec<usize>,
+    pub params: Vec<f64>,
+    /// Register bit written by a measurement
+    pub bit: Option<usize>,
+    pub c_if: Option<RegisterCondition>,
+}
+
+/// Circuit over indexed qubits and a single classical register
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegisterCircuit {
+    /// Pattern node carried by each circuit qubit, where known
+    pub qubits: Vec<Option<usize>>,
+    /// Name of the classical register
+    pub register: String,
+    /// Pattern node whose outcome each register bit holds; `Bool` inputs of
+    /// the HUGR come first, then measurements in order
+    pub bits: Vec<Option<usize>>,
+    pub instrs: Vec<RegisterInstr>,
+}
+
+/// Export a HUGR, with any nested regions inlined, as a circuit with a
+/// classical register `c`. Every
+/// measured `Bool` (and `Bool` input) gets a register bit, and
+/// `ConditionalX`/`ConditionalZ` become `X`/`Z` with a `c_if` on the bits
+/// their condition folds. Conditions that are constantly false drop the
+/// gate. Branches (`Conditional`), angle wires and packed outputs are not
+/// supported.
+pub fn to_classical_register_circuit(hugr: &Hugr) -> Result<RegisterCircuit, RegisterCircuitError> {
+    let hugr = &hugr.flatten_regions();
+    let mut circuit = RegisterCircuit {
+        qubits: Vec::new(),
+        register: "c".to_string(),
+        bits: Vec::new(),
+        instrs: Vec::new(),
+    };
+    let mut qubit_of: HashMap<Wire, usize> = HashMap::new();
+    let mut condition_of: HashMap<Wire, RegisterCondition> = HashMap::new();
+    let bit_condition = |bit: usize| RegisterCondition {
+        bits: vec![bit],
+        combiner: ClassicalOp::Xor,
+    };
+    
+    let mut ids: Vec<usize> = hugr.nodes.keys().cloned().collect();
+    ids.sort();
+    for id in ids {
+        let node = &hugr.nodes[&id];
+        let (name, signature, args) = match &node.operation {
+            Operation::Input { types } => {
+                for (port, ty) in types.iter().enumerate() {
+                    match ty {
+                        HugrType::Qubit => {
+                            qubit_of.insert(node.out(port), circuit.qubits.len());
+                            circuit.qubits.push(node.qubit_label(port));
+                        }
+                        HugrType::Bool => {
+                            condition_of.insert(node.out(port), bit_condition(circuit.bits.len()));
+                            circuit.bits.push(None);
+                        }
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            Operation::LoadConst { const_node } => {
+                if let Some(Operation::Const {
+                    value: ConstValue::Bool(false),
+                }) = hugr.get_node(*const_node).map(|c| &c.operation)
+                {
+                    condition_of.insert(node.out(0), RegisterCondition {
+                        bits: vec![],
+                        combiner: ClassicalOp::Xor,
+                    });
+                }
+                continue;
+            }
+            Operation::Output { .. } | Operation::Const { .. } | Operation::DFG { .. } => continue,
+            Operation::Conditional { .. } | Operation::Case { .. } => {
+                return Err(RegisterCircuitError::UnsupportedOperation(
+                    node.operation.name().to_string(),
+                ))
+            }
+            Operation::Custom {
+                name,
+                signature,
+                args,
+                ..
+            } => (name.as_str(), signature, args),
+        };
+        
+        let qubit_in = |port: usize| {
+            qubit_of
+                .get(&node.inputs[port])
+                .copied()
+                .ok_or(RegisterCircuitError::DanglingWire(id))
+        };
+        let condition_in = |port: usize| {
+            condition_of
+                .get(&node.inputs[port])
+                .cloned()
+                .ok_or(RegisterCircuitError::DanglingWire(id))
+        };
+        match name {
+            "XOR" | "AND" | "OR" => {
+                let (lhs, rhs) = (condition_in(0)?, condition_in(1)?);
+                let combiner = match name {
+                    "XOR" => ClassicalOp::Xor,
+                    "AND" => ClassicalOp::And,
+                    _ => ClassicalOp::Or,
+                };
+                let single_or = |c: &RegisterCondition| c.bits.len() <= 1 || c.combiner == combiner;
+                if !single_or(&lhs) || !single_or(&rhs) {
+                    return Err(RegisterCircuitError::MixedCondition(id));
+                }
+                let mut bits = lhs.bits;
+                bits.extend(rhs.bits);
+                condition_of.insert(node.out(0), RegisterCondition { bits, combiner });
+            }
+            "Pack" => {}
+            "ConditionalX" | "ConditionalZ" => {
+                let condition = condition_in(0)?;
+                let qubit = qubit_in(1)?;
+                qubit_of.insert(node.out(0), qubit);
+                if condition.bits.is_empty() {
+                    continue;
+                }
+                circuit.instrs.push(RegisterInstr {
+                    name: name.trim_start_matches("Conditional").to_string(),
+                    qubits: vec![qubit],
+                    params: vec![],
+                    bit: None,
+                    c_if: Some(condition),
+                });
+            }
+            _ => {
+                if signature.inputs.iter().chain(&signature.outputs).any(|ty| *ty != HugrType::Qubit)
+                    && !matches!(name, "Measure" | "MeasureSoft")
+                {
+                    return Err(RegisterCircuitError::UnsupportedOperation(name.to_string()));
+                }
+                let qubits = (0..node.inputs.len()).map(qubit_in).collect::<Result<Vec<_>, _>>()?;
+                
+                // Qubits pass through in order (crossed over by `SWAP`);
+                // extra qubit outputs are fresh allocations
+                let mut passing = qubits.clone();
+                if name == "SWAP" {
+                    passing.reverse();
+                }
+                let mut passing = passing.into_iter();
+                let mut touched = qubits.clone();
+                let mut bit = None;
+                for (port, ty) in signature.outputs.iter().enumerate() {
+                    match ty {
+                        HugrType::Qubit => {
+                            let qubit = passing.next().unwrap_or_else(|| {
+                                circuit.qubits.push(node.qubit_label(port));
+                                touched.push(circuit.qubits.len() - 1);
+                                circuit.qubits.len() - 1
+                            });
+                            qubit_of.insert(node.out(port), qubit);
+                        }
+                        HugrType::Bool => {
+                            let index = circuit.bits.len();
+                            let measured = qubits.first().and_then(|&q| circuit.qubits[q]);
+                            circuit.bits.push(measured);
+                            condition_of.insert(node.out(port), bit_condition(index));
+                            bit = Some(index);
+                        }
+                        _ => {}
+                    }
+                }
+                circuit.instrs.push(RegisterInstr {
+                    name: name.to_string(),
+                    qubits: touched,
+                    params: args.clone(),
+                    bit,
+                    c_if: None,
+                });
+            }
+        }
+    }
+    Ok(circuit)
+}
@@ -0,0 +1,179 @@
+use crate::converter::LOGIC_EXTENSION;
+use crate::hugr::{ConstValue, DfgBuilder, Hugr, HugrType, Operation, Wire};
+use std::collections::{HashMap, HashSet};
+
+fn is_classical(op: &Operation) -> bool {
+    match op {
+        Operation::Custom { extension, .. } => extension == LOGIC_EXTENSION,
+        Operation::Const { .. } | Operation::LoadConst { .. } => true,
+        _ => false,
+    }
+}
+
+/// Wires entering the classical part from quantum nodes, in order of first use
+fn boundary_inputs(hugr: &Hugr, ids: &[usize], classical: &HashSet<usize>) -> Vec<Wire> {
+    let mut inputs: Vec<Wire> = Vec::new();
+    for id in ids.iter().filter(|id| classical.contains(id)) {
+        for wire in &hugr.nodes[id].inputs {
+            if !classical.contains(&wire.node_id) && !inputs.contains(wire) {
+                inputs.push(*wire);
+            }
+        }
+    }
+    inputs
+}
+
+/// Extract the classical post-processing of a converted HUGR into its own
+/// dataflow graph.
+///
+/// The result contains the logic-extension and constant nodes. Its inputs are
+/// the measurement outcomes they read, and its outputs are the bits consumed by
+/// the quantum part (correction conditions and classical results).
+pub fn extract_classical_subgraph(hugr: &Hugr) -> Hugr {
+    let hugr = &hugr.flatten_regions();
+    let mut ids: Vec<_> = hugr.nodes.keys().cloned().collect();
+    ids.sort();
+    
+    let classical: HashSet<usize> = ids
+        .iter()
+        .cloned()
+        .filter(|id| is_classical(&hugr.nodes[id].operation))
+        .collect();
+    
+    let external_inputs = boundary_inputs(hugr, &ids, &classical);
+    
+    let mut dfg = DfgBuilder::new(vec![HugrType::Bool; external_inputs.len()]);
+    let mut wire_map: HashMap<Wire, Wire> = external_inputs
+        .iter()
+        .cloned()
+        .zip(dfg.input_wires.iter().cloned())
+        .collect();
+    let mut node_map: HashMap<usize, usize> = HashMap::new();
+    
+    for id in ids.iter().filter(|id| classical.contains(id)) {
+        let node = &hugr.nodes[id];
+        let new_id = match &node.operation {
+            Operation::Const { value } => dfg.add_const(value.clone()),
+            other => {
+                let op = match other {
+                    Operation::LoadConst { const_node } => Operation::LoadConst {
+                        const_node: node_map[const_node],
+                    },
+                    op => op.clone(),
+                };
+                let inputs = node.inputs.iter().map(|w| wire_map[w]).collect();
+                dfg.add_op(op, inputs).id
+            }
+        };
+        node_map.insert(*id, new_id);
+        for port in 0..node.outputs.len() {
+            wire_map.insert(Wire::new(*id, port), Wire::new(new_id, port));
+        }
+    }
+    
+    // Classical wires read by quantum nodes or the outer Output become outputs
+    let mut outputs: Vec<Wire> = Vec::new();
+    for id in ids.iter().filter(|id| !classical.contains(id)) {
+        for wire in &hugr.nodes[id].inputs {
+            if classical.contains(&wire.node_id) && !outputs.contains(&wire_map[wire]) {
+                outputs.push(wire_map[wire]);
+            }
+        }
+    }
+    
+    let output_id = dfg.hugr.add_node(Operation::Output {
+        types: vec![HugrType::Bool; outputs.len()],
+    });
+    if let Some(node) = dfg.hugr.get_node_mut(output_id) {
+        node.inputs = outputs;
+    }
+    dfg.output_node_id = Some(output_id);
+    
+    dfg.hugr
+}
+
+/// Exhaustive input-to-output behaviour of a HUGR's correction logic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruthTable {
+    /// Measurement outcome wires read by the logic, one input bit each
+    pub inputs: Vec<Wire>,
+    /// Classical `Bool` wires the quantum part reads from the logic
+    pub outputs: Vec<Wire>,
+    /// One row per input combination: the input bits (first input most
+    /// significant, rows in counting order) and the resulting output bits
+    pub rows: Vec<(Vec<bool>, Vec<bool>)>,
+}
+
+/// Enumerate every combination of the outcome bits read by the `Bool` logic
+/// (`XOR`/`AND`/`OR`/`NOT` and constants) and compute each control bit it
+/// feeds to the quantum part. The table has `2^n` rows for `n` inputs, so
+/// this is only meant for small correction networks. Panics if a control bit
+/// depends on any other logic operation.
+pub fn correction_truth_table(hugr: &Hugr) -> TruthTable {
+    let hugr = &hugr.flatten_regions();
+    let mut ids: Vec<_> = hugr.nodes.keys().cloned().collect();
+    ids.sort();
+    let classical: HashSet<usize> = ids
+        .iter()
+        .cloned()
+        .filter(|id| is_classical(&hugr.nodes[id].operation))
+        .collect();
+    let inputs = boundary_inputs(hugr, &ids, &classical);
+    
+    let mut outputs: Vec<Wire> = Vec::new();
+    for id in ids.iter().filter(|id| !classical.contains(id)) {
+        for &wire in &hugr.nodes[id].inputs {
+            if classical.contains(&wire.node_id)
+                && hugr.wire_type(wire) == Some(HugrType::Bool)
+                && !outputs.contains(&wire)
+            {
+                outputs.push(wire);
+            }
+        }
+    }
+    
+    let rows = (0..1usize << inputs.len())
+        .map(|assignment| {
+            let bits: Vec<bool> = (0..inputs.len())
+                .map(|i| assignment >> (inputs.len() - 1 - i) & 1 == 1)
+                .collect();
+            let mut values: HashMap<Wire, bool> =
+                inputs.iter().cloned().zip(bits.iter().cloned()).collect();
+            let results = outputs
+                .iter()
+                .map(|&wire| evaluate(hugr, wire, &mut values))
+                .collect();
+            (bits, results)
+        })
+        .collect();
+    
+    TruthTable { inputs, outputs, rows }
+}
+
+/// Value of a `Bool` wire of the logic, given the values computed so far
+fn evaluate(hugr: &Hugr, wire: Wire, values: &mut HashMap<Wire, bool>) -> bool {
+    if let Some(&value) = values.get(&wire) {
+        return value;
+    }
+    let node = &hugr.nodes[&wire.node_id];
+    let value = match &node.operation {
+        Operation::LoadConst { const_node } => matches!(
+            hugr.get_node(*const_node).map(|n| &n.operation),
+            Some(Operation::Const {
+                value: ConstValue::Bool(true)
+            })
+        ),
+        op => {
+            let args: Vec<bool> = node.inputs.iter().map(|&w| evaluate(hugr, w, values)).collect();
+            match op.name() {
+                "XOR" => args.iter().fold(false, |acc, &b| acc ^ b),
+                "AND" => args.iter().all(|&b| b),
+                "OR" => args.iter().any(|&b| b),
+                "NOT" => !args[0],
+                other => panic!("{} is not a Bool logic operation", other),
+            }
+        }
+    };
+    values.insert(wire, value);
+    value
+}
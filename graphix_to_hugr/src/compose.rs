@@ -0,0 +1,189 @@
+use crate::converter::GraphixToHugrConverter;
+use crate::types::{Angle, Command, Pattern};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RepeatError {
+    #[error("Cannot feed {outputs} outputs into {inputs} inputs")]
+    ArityMismatch { outputs: usize, inputs: usize },
+}
+
+impl Command {
+    /// Copy of the command with every node (including domains) mapped by `f`
+    pub fn relabel(&self, f: impl Fn(usize) -> usize) -> Command {
+        self.relabel_with(&f)
+    }
+    
+    /// `relabel` through a trait object, so branches can recurse into their
+    /// cases
+    fn relabel_with(&self, f: &dyn Fn(usize) -> usize) -> Command {
+        match self {
+            Command::N { node } => Command::N { node: f(*node) },
+            Command::E { nodes: (a, b) } => Command::E {
+                nodes: (f(*a), f(*b)),
+            },
+            Command::M { node, plane, angle } => Command::M {
+                node: f(*node),
+                plane: *plane,
+                angle: *angle,
+            },
+            Command::MZ { node } => Command::MZ { node: f(*node) },
+            Command::MSoft { node, plane, angle } => Command::MSoft {
+                node: f(*node),
+                plane: *plane,
+                angle: *angle,
+            },
+            Command::MAdaptive { node, plane, angle } => Command::MAdaptive {
+                node: f(*node),
+                plane: *plane,
+                angle: match angle {
+                    Angle::Fixed(angle) => Angle::Fixed(*angle),
+                    Angle::Computed {
+                        base,
+                        shift_domain,
+                        shift,
+                    } => Angle::Computed {
+                        base: *base,
+                        shift_domain: shift_domain.iter().map(|&n| f(n)).collect(),
+                        shift: *shift,
+                    },
+                },
+            },
+            Command::X { node, domain } => Command::X {
+                node: f(*node),
+                domain: domain.iter().map(|&n| f(n)).collect(),
+            },
+            Command::Z { node, domain } => Command::Z {
+                node: f(*node),
+                domain: domain.iter().map(|&n| f(n)).collect(),
+            },
+            Command::XControlled {
+                node,
+                controls,
+                combiner,
+            } => Command::XControlled {
+                node: f(*node),
+                controls: controls.iter().map(|&n| f(n)).collect(),
+                combiner: *combiner,
+            },
+            Command::C { node, clifford } => Command::C {
+                node: f(*node),
+                clifford: clifford.clone(),
+            },
+            Command::Gate {
+                node,
+                name,
+                matrix,
+                params,
+            } => Command::Gate {
+                node: f(*node),
+                name: name.clone(),
+                matrix: *matrix,
+                params: params.clone(),
+            },
+            Command::Branch {
+                domain,
+                if_true,
+                if_false,
+            } => Command::Branch {
+                domain: domain.iter().map(|&n| f(n)).collect(),
+                if_true: if_true.iter().map(|cmd| cmd.relabel_with(f)).collect(),
+                if_false: if_false.iter().map(|cmd| cmd.relabel_with(f)).collect(),
+            },
+        }
+    }
+}
+
+impl Pattern {
+    /// Sequential composition: `next`'s external inputs are wired to this
+    /// pattern's outputs (positionally) and its other nodes are renumbered
+    /// past this pattern's largest node so they cannot collide. Inputs of
+    /// `next` with an initial state become inputs of the result with the same
+    /// state, and the classical outputs are those of `self` followed by those
+    /// of `next`.
+    pub fn compose(&self, next: &Pattern) -> Result<Pattern, RepeatError> {
+        let next_inputs = next.external_inputs();
+        if self.output_nodes.len() != next_inputs.len() {
+            return Err(RepeatError::ArityMismatch {
+                outputs: self.output_nodes.len(),
+                inputs: next_inputs.len(),
+            });
+        }
+        
+        let mut mapping: HashMap<usize, usize> = next_inputs
+            .iter()
+            .cloned()
+            .zip(self.output_nodes.iter().cloned())
+            .collect();
+        let mut fresh = self.max_node().map_or(0, |max| max + 1);
+        
+        let mut nodes_of_next: Vec<usize> = next
+            .iter()
+            .flat_map(|cmd| {
+                let mut nodes = cmd.nodes();
+                nodes.extend(cmd.domain().into_iter().flatten());
+                nodes
+            })
+            .chain(next.input_nodes.iter().cloned())
+            .chain(next.output_nodes.iter().cloned())
+            .chain(next.classical_input_nodes.iter().cloned())
+            .collect();
+        nodes_of_next.sort();
+        nodes_of_next.dedup();
+        for node in nodes_of_next {
+            mapping.entry(node).or_insert_with(|| {
+                fresh += 1;
+                fresh - 1
+            });
+        }
+        let map = |node: usize| mapping[&node];
+        
+        let mut composed = self.clone();
+        composed.output_nodes = next.output_nodes.iter().map(|&n| map(n)).collect();
+        for node in &next.input_nodes {
+            if let Some(&state) = next.input_states.get(node) {
+                composed.input_nodes.push(map(*node));
+                composed.input_states.insert(map(*node), state);
+            }
+        }
+        composed
+            .commands
+            .extend(next.iter().map(|cmd| cmd.relabel(map)));
+        // An empty list means "every measured node", which differs between the
+        // halves and the whole, so spell both sides out
+        composed.classical_output_nodes = GraphixToHugrConverter::get_measured_nodes(self);
+        composed.classical_output_nodes.extend(
+            GraphixToHugrConverter::get_measured_nodes(next)
+                .into_iter()
+                .map(map),
+        );
+        composed
+            .classical_input_nodes
+            .extend(next.classical_input_nodes.iter().map(|&n| map(n)));
+        composed.angle_parameters.extend(
+            next.angle_parameters
+                .iter()
+                .map(|(&n, name)| (map(n), name.clone())),
+        );
+        Ok(composed)
+    }
+    
+    /// The pattern composed with itself `times` times. Requires as many
+    /// outputs as external inputs; `times == 0` gives the identity on them.
+    pub fn repeat(&self, times: usize) -> Result<Pattern, RepeatError> {
+        let inputs = self.external_inputs();
+        if self.output_nodes.len() != inputs.len() {
+            return Err(RepeatError::ArityMismatch {
+                outputs: self.output_nodes.len(),
+                inputs: inputs.len(),
+            });
+        }
+        
+        let mut result = Pattern::new(inputs.clone(), inputs);
+        for _ in 0..times {
+            result = result.compose(self)?;
+        }
+        Ok(result)
+    }
+}
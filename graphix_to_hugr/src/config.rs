@@ -0,0 +1,169 @@
+use crate::converter::QUANTUM_EXTENSION;
+
+/// Two-qubit gate that `E` commands are lowered to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// A single `CZ`
+    #[default]
+    CZ,
+    /// `CX` with the second qubit as target, between `H` gates on it, for
+    /// runtimes whose native entangler is `CX`
+    CX,
+}
+
+/// Options controlling how a pattern is lowered to HUGR
+#[derive(Debug, Clone, Default)]
+pub struct ConverterConfig {
+    /// Insert a `Barrier` over all live qubits after the last command of each
+    /// pattern phase (preparation, entanglement, measurement)
+    pub emit_phase_barriers: bool,
+    /// Emit `Measure` as `[Qubit] -> [Qubit, Bool]` and keep the measured
+    /// qubit live afterwards
+    pub nondestructive_measure: bool,
+    /// Defer every measurement to the end of the circuit, turning Pauli
+    /// corrections into `CX`/`CZ` gates controlled by the measured qubits
+    pub defer_measurements: bool,
+    /// Emit each multi-gate Clifford correction as at most an `Rz`-`Ry`-`Rz`
+    /// rotation triple instead of one node per gate
+    pub fuse_cliffords: bool,
+    /// Pack all classical outcomes into a single `Int(n)` output (first
+    /// outcome in the least significant bit) instead of `n` `Bool` outputs
+    pub pack_classical_outputs: bool,
+    /// Group the ops into three child `DFG` regions of the root, one per
+    /// pattern phase (preparation, entanglement, measurement and corrections),
+    /// with the wires between them routed through region ports. No `N` or
+    /// `E` may follow the first measurement or correction.
+    pub nested_regions: bool,
+    /// Extension of the `Rx`/`Ry`/`Rz` rotations; defaults to `quantum.mbqc`
+    pub rotation_extension: Option<String>,
+    /// Extension of `Measure` and `MeasureSoft`; defaults to `quantum.mbqc`
+    pub measurement_extension: Option<String>,
+    /// Extension of the classically controlled Pauli corrections
+    /// (`ConditionalX`/`ConditionalZ`); defaults to `quantum.mbqc`
+    pub pauli_extension: Option<String>,
+    /// Emit a HUGR wiring inputs straight to outputs when
+    /// `Pattern::is_identity` recognizes the pattern
+    pub identity_fast_path: bool,
+    /// Record on each `Measure` node whether its outcome is deterministic,
+    /// random or input-dependent, as `outcome` metadata (Clifford patterns
+    /// only; see `compute_measurement_determinism`)
+    pub annotate_determinism: bool,
+    /// Hardware coupling graph over pattern nodes. An `E` between nodes that
+    /// are not coupled is routed with `SWAP`s along the shortest path of live
+    /// qubits, which are swapped back after the `CZ`.
+    pub connectivity: Option<Vec<(usize, usize)>>,
+    /// Bracket each qubit's lifetime with `QAlloc`/`QFree`: a `QAlloc`
+    /// feeds every `PrepareQubit` (then `[Qubit] -> [Qubit]`), `Measure`
+    /// keeps its qubit for a `QFree`, and qubits still live at the end that
+    /// are not outputs are freed there
+    pub explicit_qubit_scopes: bool,
+    /// Leave out the trailing `X`/`Z` corrections of each output node (those
+    /// after every other command on it) and record them instead, see
+    /// `GraphixToHugrConverter::output_byproduct`
+    pub track_output_byproduct: bool,
+    /// Run `Pattern::validate` before converting and fail with
+    /// `ConversionError::InvalidPattern` on any mistake it reports
+    pub validate_patterns: bool,
+    /// Gate each `E` command (and each routed entanglement) is lowered to
+    pub entangling_gate: EdgeKind,
+}
+
+impl ConverterConfig {
+    /// Extension the rotations are emitted in
+    pub fn rotation_ext(&self) -> &str {
+        self.rotation_extension.as_deref().unwrap_or(QUANTUM_EXTENSION)
+    }
+    
+    /// Extension the measurements are emitted in
+    pub fn measurement_ext(&self) -> &str {
+        self.measurement_extension.as_deref().unwrap_or(QUANTUM_EXTENSION)
+    }
+    
+    /// Extension the Pauli corrections are emitted in
+    pub fn pauli_ext(&self) -> &str {
+        self.pauli_extension.as_deref().unwrap_or(QUANTUM_EXTENSION)
+    }
+    
+    // Builder-style setters, for changing a few fields of the default:
+    // `ConverterConfig::default().with_fuse_cliffords(true)`
+    
+    pub fn with_emit_phase_barriers(mut self, emit_phase_barriers: bool) -> Self {
+        self.emit_phase_barriers = emit_phase_barriers;
+        self
+    }
+    
+    pub fn with_nondestructive_measure(mut self, nondestructive_measure: bool) -> Self {
+        self.nondestructive_measure = nondestructive_measure;
+        self
+    }
+    
+    pub fn with_defer_measurements(mut self, defer_measurements: bool) -> Self {
+        self.defer_measurements = defer_measurements;
+        self
+    }
+    
+    pub fn with_fuse_cliffords(mut self, fuse_cliffords: bool) -> Self {
+        self.fuse_cliffords = fuse_cliffords;
+        self
+    }
+    
+    pub fn with_pack_classical_outputs(mut self, pack_classical_outputs: bool) -> Self {
+        self.pack_classical_outputs = pack_classical_outputs;
+        self
+    }
+    
+    pub fn with_nested_regions(mut self, nested_regions: bool) -> Self {
+        self.nested_regions = nested_regions;
+        self
+    }
+    
+    pub fn with_identity_fast_path(mut self, identity_fast_path: bool) -> Self {
+        self.identity_fast_path = identity_fast_path;
+        self
+    }
+    
+    pub fn with_annotate_determinism(mut self, annotate_determinism: bool) -> Self {
+        self.annotate_determinism = annotate_determinism;
+        self
+    }
+    
+    pub fn with_explicit_qubit_scopes(mut self, explicit_qubit_scopes: bool) -> Self {
+        self.explicit_qubit_scopes = explicit_qubit_scopes;
+        self
+    }
+    
+    pub fn with_track_output_byproduct(mut self, track_output_byproduct: bool) -> Self {
+        self.track_output_byproduct = track_output_byproduct;
+        self
+    }
+    
+    pub fn with_validate_patterns(mut self, validate_patterns: bool) -> Self {
+        self.validate_patterns = validate_patterns;
+        self
+    }
+    
+    pub fn with_entangling_gate(mut self, entangling_gate: EdgeKind) -> Self {
+        self.entangling_gate = entangling_gate;
+        self
+    }
+    
+    pub fn with_rotation_extension(mut self, extension: impl Into<String>) -> Self {
+        self.rotation_extension = Some(extension.into());
+        self
+    }
+    
+    pub fn with_measurement_extension(mut self, extension: impl Into<String>) -> Self {
+        self.measurement_extension = Some(extension.into());
+        self
+    }
+    
+    pub fn with_pauli_extension(mut self, extension: impl Into<String>) -> Self {
+        self.pauli_extension = Some(extension.into());
+        self
+    }
+    
+    pub fn with_connectivity(mut self, edges: Vec<(usize, usize)>) -> Self {
+        self.connectivity = Some(edges);
+        self
+    }
+}
@@ -1,81 +1,470 @@
+use crate::config::{ConverterConfig, EdgeKind};
 use crate::hugr::{
     ConstValue, DfgBuilder, FunctionType, Hugr, HugrType, Operation, Wire,
 };
-use crate::types::{CliffordGate, Command, Pattern, Plane};
-use std::collections::{HashMap, HashSet};
+use crate::schema::ResultSchema;
+use crate::stabilizer::compute_measurement_determinism;
+use crate::sim::{clifford_matrix, zyz_angles};
+use crate::types::{
+    Angle, CliffordGate, Command, CommandKind, Complex, InitState, Pattern, PatternError, Plane,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use thiserror::Error;
 
-const QUANTUM_EXTENSION: &str = "quantum.mbqc";
-const LOGIC_EXTENSION: &str = "logic";
+pub(crate) const QUANTUM_EXTENSION: &str = "quantum.mbqc";
+pub(crate) const LOGIC_EXTENSION: &str = "logic";
+pub(crate) const FLOAT_EXTENSION: &str = "arithmetic.float";
 
 #[derive(Error, Debug)]
 pub enum ConversionError {
     #[error("Output node {0} not found in qubit wires")]
     OutputNodeNotFound(usize),
     
+    #[error("Output node {0} was measured, so its qubit is no longer available")]
+    OutputNodeMeasured(usize),
+    
     #[error("Node {0} not found in wires")]
     NodeNotFound(usize),
     
+    #[error("Node {0} is entangled with itself")]
+    SelfEntanglement(usize),
+    
+    #[error("Node {0} is measured more than once")]
+    AlreadyMeasured(usize),
+    
+    #[error("Node {0} is prepared while its qubit is still live")]
+    QubitAlreadyLive(usize),
+    
+    #[error("Type of output wire {node}:{port} cannot be resolved from its producer")]
+    UntypedWire { node: usize, port: usize },
+    
+    #[error("Operation {0} is not a Clifford gate")]
+    NotACliffordGate(String),
+    
+    #[error("Operation {extension}.{name} is not registered")]
+    UnregisteredOperation { extension: String, name: String },
+    
+    #[error("Operation {extension}.{name} does not match its registered signature")]
+    SignatureMismatch { extension: String, name: String },
+    
+    #[error("Conversion cancelled before command {at_command}")]
+    Cancelled { at_command: usize },
+    
+    #[error("Measurement angle {value} of node {node} is not finite")]
+    InvalidAngle { node: usize, value: f64 },
+    
+    #[error("Correction depends on node {0}, which has no measurement outcome")]
+    MissingMeasurement(usize),
+    
+    #[error("{command_kind:?} command cannot be lowered with this configuration: {reason}")]
+    UnsupportedUnderConfig {
+        command_kind: CommandKind,
+        reason: &'static str,
+    },
+    
+    #[error("Pattern has no causal flow")]
+    NoFlow,
+    
+    #[error("{0:?} commands are not allowed when corrections come from the flow")]
+    NotAnOpenGraph(CommandKind),
+    
+    #[error("Command {at_command} prepares or entangles after the first measurement or correction")]
+    NotStandardForm { at_command: usize },
+    
+    #[error("{0:?} commands cannot appear inside a branch")]
+    UnsupportedInBranch(CommandKind),
+    
+    #[error("Branch cases leave different qubits live or measure different nodes")]
+    BranchSignatureMismatch,
+    
+    #[error("Nodes {nodes:?} are not connected through live qubits of the coupling graph")]
+    Unroutable { nodes: (usize, usize) },
+    
+    #[error("{0:?} commands cannot be lowered to a flat gate list")]
+    NotFlat(CommandKind),
+    
+    #[error("Pattern fails validation: {0:?}")]
+    InvalidPattern(Vec<PatternError>),
+}
+
+/// Pauli byproduct left on the outputs when `track_output_byproduct` is set:
+/// output `node` still needs `X` if the parity of `x_domain` is 1, then `Z`
+/// if the parity of `z_domain` is 1
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutputByproduct {
+    /// `node -> (x_domain, z_domain)`, for every output node
+    pub domains: BTreeMap<usize, (BTreeSet<usize>, BTreeSet<usize>)>,
+}
+
+/// Non-fatal diagnostics collected by `convert_with_report`
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConversionWarning {
+    #[error("Outcome of node {0} is measured but never used")]
+    UnusedMeasurement(usize),
+}
+
+/// Signature of `PrepareQubit` under `config`: `[] -> [Qubit]`, or
+/// `[Qubit] -> [Qubit]` when it acts on a qubit from `QAlloc`
+pub(crate) fn prepare_signature(config: &ConverterConfig) -> FunctionType {
+    let inputs = if config.explicit_qubit_scopes {
+        vec![HugrType::Qubit]
+    } else {
+        vec![]
+    };
+    FunctionType::new(inputs, vec![HugrType::Qubit])
+}
+
+/// Reject NaN and infinite measurement angles
+pub(crate) fn check_angle(node: usize, angle: f64) -> Result<(), ConversionError> {
+    if angle.is_finite() {
+        Ok(())
+    } else {
+        Err(ConversionError::InvalidAngle { node, value: angle })
+    }
+}
+
+/// Signature of `Measure` under `config`: `[Qubit] -> [Bool]`, or
+/// `[Qubit] -> [Qubit, Bool]` when measurements are non-destructive or the
+/// qubit is freed explicitly afterwards
+pub(crate) fn measure_signature(config: &ConverterConfig) -> FunctionType {
+    if config.nondestructive_measure || config.explicit_qubit_scopes {
+        FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit, HugrType::Bool])
+    } else {
+        FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Bool])
+    }
+}
+
+/// Classical combinator used to fold measurement outcomes into a condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum ClassicalOp {
+    /// Parity of the outcomes (used for Pauli corrections)
+    #[default]
+    Xor,
+    /// All outcomes are 1
+    And,
+    /// At least one outcome is 1
+    Or,
+}
+
+impl ClassicalOp {
+    /// Name of the corresponding operation in the logic extension
+    pub fn op_name(&self) -> &'static str {
+        match self {
+            ClassicalOp::Xor => "XOR",
+            ClassicalOp::And => "AND",
+            ClassicalOp::Or => "OR",
+        }
+    }
 }
 
+/// Pattern-to-HUGR converter. Cloning captures the full in-progress state,
+/// so a conversion driven with `begin`/`push_command` can be checkpointed
+/// and the clone resumed or dropped independently.
+#[derive(Clone)]
 pub struct GraphixToHugrConverter {
-    dfg: Option<DfgBuilder>,
+    config: ConverterConfig,
+    dfg: DfgBuilder,
     qubit_wires: HashMap<usize, Wire>,
     classical_wires: HashMap<usize, Wire>,
     node_order: Vec<usize>,
+    /// Measured nodes whose `Measure` is postponed to the end, in order
+    deferred: Vec<usize>,
+    /// Likelihood (`Float64`) wires of soft measurements
+    soft_wires: HashMap<usize, Wire>,
+    /// Nodes whose current qubit has been measured; preparing the node again
+    /// starts a new qubit that may be measured once more
+    measured_qubits: HashSet<usize>,
+    /// Outcomes of earlier preparations of nodes measured more than once,
+    /// oldest first; `classical_wires` holds the latest
+    earlier_outcomes: HashMap<usize, Vec<Wire>>,
+    /// Phase region nodes when `nested_regions` is set, empty otherwise
+    regions: Vec<usize>,
+    /// Phase of the latest command: 0 preparation, 1 entanglement, 2
+    /// measurement and corrections
+    phase: usize,
+    /// Symbolic measurement angles of the pattern being converted
+    angle_parameters: HashMap<usize, String>,
+    /// Index given to the next pushed command
+    next_command: usize,
+    /// Command indices followed by a phase barrier
+    barriers: HashSet<usize>,
+    /// Command indices of the output corrections recorded in `byproduct`
+    /// instead of being emitted
+    tracked: HashSet<usize>,
+    byproduct: OutputByproduct,
 }
 
 impl GraphixToHugrConverter {
     pub fn new() -> Self {
+        Self::with_config(ConverterConfig::default())
+    }
+    
+    pub fn with_config(config: ConverterConfig) -> Self {
         Self {
-            dfg: None,
+            config,
+            dfg: DfgBuilder::default(),
             qubit_wires: HashMap::new(),
             classical_wires: HashMap::new(),
             node_order: Vec::new(),
+            deferred: Vec::new(),
+            soft_wires: HashMap::new(),
+            measured_qubits: HashSet::new(),
+            earlier_outcomes: HashMap::new(),
+            regions: Vec::new(),
+            phase: 0,
+            angle_parameters: HashMap::new(),
+            next_command: 0,
+            barriers: HashSet::new(),
+            tracked: HashSet::new(),
+            byproduct: OutputByproduct::default(),
         }
     }
     
     /// Convert a Graphix Pattern to a HUGR
     pub fn convert(&mut self, pattern: &Pattern) -> Result<Hugr, ConversionError> {
-        // Determine input and output qubits
-        let input_nodes: Vec<usize> = {
-            let mut nodes = pattern.input_nodes.clone();
-            nodes.sort();
-            nodes
+        self.convert_with_hook(pattern, |_| false)
+    }
+    
+    /// Convert a Graphix Pattern to a HUGR, stopping with
+    /// `ConversionError::Cancelled` once `cancel` is set. The flag is checked
+    /// before each command.
+    pub fn convert_with_cancel(
+        &mut self,
+        pattern: &Pattern,
+        cancel: &AtomicBool,
+    ) -> Result<Hugr, ConversionError> {
+        self.convert_with_hook(pattern, |_| cancel.load(Ordering::Relaxed))
+    }
+    
+    /// Corrections left on the outputs by the last conversion; empty unless
+    /// `track_output_byproduct` is set
+    pub fn output_byproduct(&self) -> &OutputByproduct {
+        &self.byproduct
+    }
+    
+    /// Likelihood wire of soft-measured `node` in the last converted HUGR
+    pub fn soft_wire(&self, node: usize) -> Option<Wire> {
+        self.soft_wires.get(&node).copied()
+    }
+    
+    /// Convert a pattern, also returning diagnostics about it. Warnings never
+    /// fail the conversion.
+    pub fn convert_with_report(
+        &mut self,
+        pattern: &Pattern,
+    ) -> Result<(Hugr, Vec<ConversionWarning>), ConversionError> {
+        let hugr = self.convert(pattern)?;
+        
+        let consumed: HashSet<Wire> = hugr
+            .nodes
+            .values()
+            .flat_map(|node| node.inputs.iter().cloned())
+            .collect();
+        let mut unused: Vec<usize> = self
+            .classical_wires
+            .iter()
+            .filter(|(node, wire)| {
+                !consumed.contains(wire) && !pattern.classical_input_nodes.contains(node)
+            })
+            .map(|(&node, _)| node)
+            .collect();
+        unused.sort();
+        
+        let warnings = unused.into_iter().map(ConversionWarning::UnusedMeasurement).collect();
+        Ok((hugr, warnings))
+    }
+    
+    /// Convert a pattern, also returning the schema labelling its classical
+    /// outputs under this converter's configuration
+    pub fn convert_with_schema(&mut self, pattern: &Pattern) -> Result<(Hugr, ResultSchema), ConversionError> {
+        let hugr = self.convert(pattern)?;
+        Ok((hugr, ResultSchema::for_config(pattern, &self.config)))
+    }
+    
+    /// Shared conversion driver; `should_cancel` is called with the index of
+    /// each command before it is processed
+    fn convert_with_hook(
+        &mut self,
+        pattern: &Pattern,
+        mut should_cancel: impl FnMut(usize) -> bool,
+    ) -> Result<Hugr, ConversionError> {
+        if self.config.validate_patterns {
+            pattern.validate().map_err(ConversionError::InvalidPattern)?;
+        }
+        self.begin(pattern);
+        
+        // A symbolic angle may be bound to anything, so the placeholder
+        // angles say nothing about whether the pattern is the identity
+        if self.config.identity_fast_path && pattern.angle_parameters.is_empty() && pattern.is_identity() {
+            let mut dfg = DfgBuilder::new(vec![HugrType::Qubit; pattern.input_nodes.len()]);
+            let wires = dfg.input_wires.clone();
+            dfg.set_outputs(wires)?;
+            return Ok(dfg.finish());
+        }
+        
+        for (at_command, cmd) in pattern.iter().enumerate() {
+            if should_cancel(at_command) {
+                return Err(ConversionError::Cancelled { at_command });
+            }
+            self.push_command(cmd)?;
+        }
+        self.finish(pattern)
+    }
+    
+    /// Start converting `pattern` incrementally: set up its inputs and
+    /// internally prepared states, discarding any earlier conversion. Its
+    /// commands are then fed with `push_command` and the HUGR completed
+    /// with `finish`, which is what `convert` does.
+    pub fn begin(&mut self, pattern: &Pattern) {
+        // Start from a clean slate so a converter can be reused
+        self.qubit_wires.clear();
+        self.classical_wires.clear();
+        self.node_order.clear();
+        self.deferred.clear();
+        self.soft_wires.clear();
+        self.measured_qubits.clear();
+        self.earlier_outcomes.clear();
+        self.regions.clear();
+        self.phase = 0;
+        self.angle_parameters = pattern.angle_parameters.clone();
+        self.next_command = 0;
+        self.barriers = if self.config.emit_phase_barriers {
+            Self::phase_ends(pattern)
+        } else {
+            HashSet::new()
         };
+        self.byproduct = OutputByproduct::default();
+        self.tracked = HashSet::new();
+        if self.config.track_output_byproduct {
+            self.tracked = Self::trailing_corrections(pattern);
+            for &node in &pattern.output_nodes {
+                self.byproduct.domains.insert(node, Default::default());
+            }
+        }
         
-        let output_nodes: Vec<usize> = {
-            let mut nodes = pattern.output_nodes.clone();
+        // Input qubits and the function signature: qubits, then classical
+        // inputs
+        let input_nodes: Vec<usize> = {
+            let mut nodes = pattern.external_inputs();
             nodes.sort();
             nodes
         };
-        
-        let measured_nodes = self.get_measured_nodes(pattern);
-        
-        // Calculate how many qubits we need
         let n_inputs = input_nodes.len();
-        let n_outputs = output_nodes.len();
-        let n_classical_outputs = measured_nodes.len();
+        let mut input_types = vec![HugrType::Qubit; n_inputs];
+        input_types.extend(vec![HugrType::Bool; pattern.classical_input_nodes.len()]);
+        
+        // Pre-size the wire maps and node table; most commands lower to a
+        // couple of nodes, so this avoids rehashing on large patterns
+        let n_nodes = pattern.max_node().map_or(0, |max| max + 1);
+        self.qubit_wires.reserve(n_nodes);
+        self.classical_wires.reserve(n_nodes);
         
-        // Build the function signature
-        let input_types = vec![HugrType::Qubit; n_inputs];
-        let mut output_types = vec![HugrType::Qubit; n_outputs];
-        output_types.extend(vec![HugrType::Bool; n_classical_outputs]);
+        // Create the dataflow graph, sized for about two nodes per command
+        let dfg = DfgBuilder::with_capacity(input_types, 2 * pattern.commands.len() + 2);
         
-        // Create a DFG (dataflow graph) - removed mut as it's not needed
-        let dfg = DfgBuilder::new(input_types);
+        let input_wires = dfg.input_wires.clone();
+        self.dfg = dfg;
         
         // Initialize input qubits
         for (i, &node_idx) in input_nodes.iter().enumerate() {
-            let wire = dfg.input_wires[i];
-            self.qubit_wires.insert(node_idx, wire);
+            self.set_qubit_wire(node_idx, input_wires[i]);
+        }
+        for (i, &node_idx) in pattern.classical_input_nodes.iter().enumerate() {
+            self.classical_wires.insert(node_idx, input_wires[n_inputs + i]);
+        }
+        
+        // One child region of the root per phase; signatures are filled in
+        // once the boundaries are known
+        if self.config.nested_regions {
+            let root = self.dfg.hugr.root;
+            for _ in 0..3 {
+                let region = self.dfg.hugr.add_node(Operation::DFG {
+                    signature: FunctionType::new(vec![], vec![]),
+                });
+                if let Some(node) = self.dfg.hugr.get_node_mut(region) {
+                    node.parent = Some(root);
+                }
+                self.regions.push(region);
+            }
         }
         
-        self.dfg = Some(dfg);
+        // Prepare inputs that are initialized internally
+        let mut internal: Vec<_> = pattern.input_states.iter().collect();
+        internal.sort_by_key(|(node, _)| **node);
+        for (&node, &state) in internal {
+            if pattern.input_nodes.contains(&node) {
+                let first = self.dfg.hugr.next_node_id;
+                self.process_prepare(node)
+                    .expect("internally prepared inputs are not boundary inputs");
+                if state == InitState::Zero {
+                    self.process_clifford(node, &[CliffordGate::H]);
+                }
+                self.annotate_since(first, None, &[node], 0);
+            }
+        }
         
-        // Process pattern commands in order
-        for cmd in pattern.iter() {
-            self.process_command(cmd);
+    }
+    
+    /// Lower the next command of a conversion started with `begin`
+    pub fn push_command(&mut self, cmd: &Command) -> Result<(), ConversionError> {
+        let at_command = self.next_command;
+        self.next_command += 1;
+        let phase = self.enter_phase(cmd.kind(), at_command)?;
+        if self.tracked.contains(&at_command) {
+            return self.record_byproduct(cmd);
+        }
+        let first = self.dfg.hugr.next_node_id;
+        self.process_command(cmd)?;
+        self.annotate_since(first, Some(at_command), &cmd.nodes(), phase);
+        if self.barriers.contains(&at_command) {
+            let first = self.dfg.hugr.next_node_id;
+            self.insert_barrier();
+            self.place_since(first, phase);
+        }
+        Ok(())
+    }
+    
+    /// HUGR built so far by `begin` and `push_command`, without outputs
+    pub fn in_progress(&self) -> &Hugr {
+        &self.dfg.hugr
+    }
+    
+    /// Complete a conversion started with `begin(pattern)`: emit deferred
+    /// measurements and wire up the outputs of `pattern`
+    pub fn finish(&mut self, pattern: &Pattern) -> Result<Hugr, ConversionError> {
+        let output_nodes: Vec<usize> = {
+            let mut nodes = pattern.output_nodes.clone();
+            nodes.sort();
+            nodes
+        };
+        let measured_nodes = Self::get_measured_nodes(pattern);
+        
+        // Final measurement layer for deferred measurements
+        for node in std::mem::take(&mut self.deferred) {
+            if let Some(&wire) = self.qubit_wires.get(&node) {
+                let first = self.dfg.hugr.next_node_id;
+                self.emit_measure(node, wire);
+                self.annotate_since(first, None, &[node], 2);
+            }
+        }
+        
+        // Close the scope of qubits that are dropped rather than returned
+        if self.config.explicit_qubit_scopes {
+            let mut dropped: Vec<usize> = self
+                .qubit_wires
+                .keys()
+                .cloned()
+                .filter(|node| !output_nodes.contains(node))
+                .collect();
+            dropped.sort();
+            for node in dropped {
+                let first = self.dfg.hugr.next_node_id;
+                let wire = self.qubit_wires.remove(&node).expect("dropped qubits are live");
+                self.dfg.add_op(self.create_scope_op("QFree"), vec![wire]);
+                self.annotate_since(first, None, &[node], 2);
+            }
         }
         
         // Collect outputs
@@ -85,295 +474,1153 @@ impl GraphixToHugrConverter {
         for &node_idx in &output_nodes {
             if let Some(&wire) = self.qubit_wires.get(&node_idx) {
                 output_wires.push(wire);
+            } else if self.classical_wires.contains_key(&node_idx)
+                || self.soft_wires.contains_key(&node_idx)
+            {
+                return Err(ConversionError::OutputNodeMeasured(node_idx));
             } else {
                 return Err(ConversionError::OutputNodeNotFound(node_idx));
             }
         }
         
         // Add classical measurement results
+        // A node measured once per preparation appears once per measurement,
+        // and its k-th appearance returns its k-th outcome
+        let mut classical_outputs = Vec::with_capacity(measured_nodes.len());
+        let mut seen: HashMap<usize, usize> = HashMap::new();
         for &node_idx in &measured_nodes {
-            if let Some(&wire) = self.classical_wires.get(&node_idx) {
-                output_wires.push(wire);
+            let occurrence = seen.entry(node_idx).or_default();
+            let outcome = self
+                .earlier_outcomes
+                .get(&node_idx)
+                .into_iter()
+                .flatten()
+                .chain(self.classical_wires.get(&node_idx))
+                .nth(*occurrence);
+            *occurrence += 1;
+            if let Some(&wire) = outcome {
+                classical_outputs.push(wire);
             } else {
                 // If no classical wire, create a constant false
-                let dfg = self.dfg.as_mut().unwrap();
+                let dfg = &mut self.dfg;
                 let false_const = dfg.add_const(ConstValue::Bool(false));
                 let false_wire = dfg.load_const(false_const);
-                output_wires.push(false_wire);
+                classical_outputs.push(false_wire);
             }
         }
+        if self.config.pack_classical_outputs && !classical_outputs.is_empty() {
+            let pack_op = self.create_pack_op(classical_outputs.len());
+            output_wires.extend(self.dfg.add_op_wires(pack_op, classical_outputs));
+        } else {
+            output_wires.extend(classical_outputs);
+        }
         
-        // Set the outputs
-        let dfg = self.dfg.as_mut().unwrap();
-        dfg.set_outputs(output_wires);
+        // Set the outputs and hand the finished graph over without copying it
+        let mut dfg = std::mem::take(&mut self.dfg);
+        dfg.set_outputs(output_wires)?;
+        let boundary = [Some(dfg.input_node_id), dfg.output_node_id];
         
-        Ok(dfg.hugr.clone())
+        let mut hugr = dfg.finish();
+        if let Some(&last) = self.regions.last() {
+            // Ops added while collecting the outputs belong to the last phase
+            for node in hugr.nodes.values_mut() {
+                if node.parent.is_none() && !boundary.contains(&Some(node.id)) {
+                    node.parent = Some(last);
+                }
+            }
+            for &region in &self.regions {
+                Self::wire_region(&mut hugr, region);
+            }
+        }
+        if self.config.annotate_determinism {
+            let outcomes = compute_measurement_determinism(pattern);
+            for node in hugr.nodes.values_mut() {
+                if !matches!(node.operation.name(), "Measure" | "MeasureSoft") {
+                    continue;
+                }
+                let measured = node.get_meta("pattern_node").and_then(|n| n.parse::<usize>().ok());
+                if let Some(determinism) = measured.and_then(|n| outcomes.get(&n)) {
+                    node.set_meta("outcome", determinism.as_str());
+                }
+            }
+        }
+        Ok(hugr)
     }
     
-    fn get_measured_nodes(&self, pattern: &Pattern) -> Vec<usize> {
+    /// Move to the phase of a command of kind `kind` and return it: `N` and
+    /// `E` start the preparation (0) and entanglement (1) phases,
+    /// measurements and corrections the last one (2), and `C` and custom
+    /// gates stay in the current phase. Phases never go back, so with
+    /// `nested_regions` an `N` or `E` after the first measurement or
+    /// correction is rejected: its region would both feed and consume the
+    /// last one.
+    fn enter_phase(&mut self, kind: CommandKind, at_command: usize) -> Result<usize, ConversionError> {
+        let phase = match kind {
+            CommandKind::N => 0,
+            CommandKind::E => 1,
+            CommandKind::C | CommandKind::Gate => self.phase,
+            _ => 2,
+        };
+        if self.config.nested_regions && self.phase == 2 && phase < 2 {
+            return Err(ConversionError::NotStandardForm { at_command });
+        }
+        self.phase = self.phase.max(phase);
+        Ok(self.phase)
+    }
+    
+    /// Route the wires crossing a region's boundary through its ports. A
+    /// child `Input` node stands in for the outside wires its nodes consume,
+    /// and a child `Output` node collects the wires it produces for outside
+    /// consumers, which then read the region's own outputs instead. The
+    /// region's signature lists the types of both, in first-use order.
+    fn wire_region(hugr: &mut Hugr, region: usize) {
+        // Nodes in the region, directly or inside one of its branches
+        let within = |mut id: usize| loop {
+            match hugr.get_node(id).and_then(|node| node.parent) {
+                Some(parent) if parent == region => break true,
+                Some(parent) => id = parent,
+                None => break false,
+            }
+        };
+        let mut ids: Vec<usize> = hugr.nodes.keys().cloned().collect();
+        ids.sort();
+        let inside: HashSet<usize> = ids.iter().cloned().filter(|&id| within(id)).collect();
+        
+        let mut incoming = Vec::new();
+        let mut outgoing = Vec::new();
+        for &id in &ids {
+            let consumer_inside = inside.contains(&id);
+            for &wire in &hugr.nodes[&id].inputs {
+                let from_inside = inside.contains(&wire.node_id);
+                if consumer_inside && !from_inside && !incoming.contains(&wire) {
+                    incoming.push(wire);
+                } else if !consumer_inside && from_inside && !outgoing.contains(&wire) {
+                    outgoing.push(wire);
+                }
+            }
+        }
+        
+        let types = |wires: &[Wire]| -> Vec<HugrType> {
+            wires
+                .iter()
+                .map(|&wire| hugr.wire_type(wire).unwrap_or(HugrType::Qubit))
+                .collect()
+        };
+        let labels = |wires: &[Wire]| -> HashMap<usize, usize> {
+            wires
+                .iter()
+                .enumerate()
+                .filter_map(|(port, wire)| Some((port, hugr.get_node(wire.node_id)?.qubit_label(wire.port)?)))
+                .collect()
+        };
+        let signature = FunctionType::new(types(&incoming), types(&outgoing));
+        let (input_labels, output_labels) = (labels(&incoming), labels(&outgoing));
+        
+        let region_input = hugr.add_node(Operation::Input {
+            types: signature.inputs.clone(),
+        });
+        for &id in &ids {
+            let (crossing, ports) = if inside.contains(&id) {
+                (&incoming, region_input)
+            } else {
+                (&outgoing, region)
+            };
+            if let Some(node) = hugr.get_node_mut(id) {
+                for wire in &mut node.inputs {
+                    if let Some(port) = crossing.iter().position(|crossed| crossed == wire) {
+                        *wire = Wire::new(ports, port);
+                    }
+                }
+            }
+        }
+        let region_output = hugr.add_node(Operation::Output {
+            types: signature.outputs.clone(),
+        });
+        
+        for (id, inputs, n_outputs, labels) in [
+            (region_input, vec![], incoming.len(), input_labels),
+            (region_output, outgoing.clone(), 0, HashMap::new()),
+        ] {
+            if let Some(node) = hugr.get_node_mut(id) {
+                node.inputs = inputs;
+                node.outputs = (0..n_outputs).map(|port| Wire::new(id, port)).collect();
+                node.qubit_labels = labels;
+                node.parent = Some(region);
+            }
+        }
+        if let Some(node) = hugr.get_node_mut(region) {
+            node.outputs = (0..outgoing.len()).map(|port| Wire::new(region, port)).collect();
+            node.inputs = incoming;
+            node.qubit_labels = output_labels;
+            node.operation = Operation::DFG { signature };
+        }
+    }
+    
+    /// Place every HUGR node created since id `first` that is not yet in a
+    /// region in the region for `phase`, if regions are enabled
+    fn place_since(&mut self, first: usize, phase: usize) {
+        let Some(&region) = self.regions.get(phase) else {
+            return;
+        };
+        for id in first..self.dfg.hugr.next_node_id {
+            if let Some(node) = self.dfg.hugr.get_node_mut(id) {
+                node.parent.get_or_insert(region);
+            }
+        }
+    }
+    
+    /// Record the producing command and pattern nodes on every HUGR node
+    /// created since id `first`, placing them in the region for `phase`
+    fn annotate_since(
+        &mut self,
+        first: usize,
+        source_command: Option<usize>,
+        nodes: &[usize],
+        phase: usize,
+    ) {
+        let pattern_node = nodes
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        for id in first..self.dfg.hugr.next_node_id {
+            if let Some(node) = self.dfg.hugr.get_node_mut(id) {
+                if let Some(index) = source_command {
+                    node.set_meta("source_command", index.to_string());
+                }
+                node.set_meta("pattern_node", pattern_node.clone());
+            }
+        }
+        self.place_since(first, phase);
+    }
+    
+    /// Indices of the `X`/`Z` corrections on output nodes that come after
+    /// every other command acting on the same node
+    fn trailing_corrections(pattern: &Pattern) -> HashSet<usize> {
+        let mut tracked = HashSet::new();
+        for &output in &pattern.output_nodes {
+            for (idx, cmd) in pattern.commands.iter().enumerate().rev() {
+                match cmd {
+                    Command::X { node, .. } | Command::Z { node, .. } if *node == output => {
+                        tracked.insert(idx);
+                    }
+                    other if other.nodes().contains(&output) => break,
+                    _ => {}
+                }
+            }
+        }
+        tracked
+    }
+    
+    /// Fold a trailing output correction into the byproduct. Paulis on one
+    /// qubit commute up to a global phase, so the domains just accumulate
+    /// parities.
+    fn record_byproduct(&mut self, cmd: &Command) -> Result<(), ConversionError> {
+        let (node, domain, is_x) = match cmd {
+            Command::X { node, domain } => (*node, domain, true),
+            Command::Z { node, domain } => (*node, domain, false),
+            _ => unreachable!("only X/Z corrections are tracked"),
+        };
+        // A deferred outcome is only read at the end, but it is still an
+        // outcome the byproduct can refer to
+        if let Some(&missing) = domain
+            .iter()
+            .find(|d| !self.classical_wires.contains_key(d) && !self.deferred.contains(d))
+        {
+            return Err(ConversionError::MissingMeasurement(missing));
+        }
+        
+        let (x_domain, z_domain) = self.byproduct.domains.entry(node).or_default();
+        let target = if is_x { x_domain } else { z_domain };
+        for &d in domain {
+            if !target.remove(&d) {
+                target.insert(d);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Indices of the last `N`, `E` and `M` commands, excluding the final command
+    fn phase_ends(pattern: &Pattern) -> HashSet<usize> {
+        let mut ends = HashSet::new();
+        for kind in [CommandKind::N, CommandKind::E, CommandKind::M] {
+            if let Some(idx) = pattern.commands.iter().rposition(|cmd| cmd.kind() == kind) {
+                if idx + 1 < pattern.commands.len() {
+                    ends.insert(idx);
+                }
+            }
+        }
+        ends
+    }
+    
+    /// Thread every live qubit through a single `Barrier` node
+    fn insert_barrier(&mut self) {
+        let mut live: Vec<usize> = self.qubit_wires.keys().cloned().collect();
+        live.sort();
+        
+        let barrier_op = self.create_barrier_op(live.len());
+        let inputs = live.iter().map(|node| self.qubit_wires[node]).collect();
+        let outputs = self.dfg.add_op_wires(barrier_op, inputs);
+        for (node, wire) in live.into_iter().zip(outputs) {
+            self.set_qubit_wire(node, wire);
+        }
+    }
+    
+    /// Nodes whose outcome is returned, in output-port order: the pattern's
+    /// `classical_output_nodes` if given, otherwise every measured non-output
+    /// node in ascending order, once per measurement of it
+    pub(crate) fn get_measured_nodes(pattern: &Pattern) -> Vec<usize> {
+        if !pattern.classical_output_nodes.is_empty() {
+            return pattern.classical_output_nodes.clone();
+        }
+        
         let mut measured = Vec::new();
         let output_set: HashSet<_> = pattern.output_nodes.iter().cloned().collect();
         
         for cmd in pattern.iter() {
-            if let Command::M { node, .. } = cmd {
-                if !output_set.contains(node) {
-                    measured.push(*node);
+            if let Some(node) = cmd.measured_node() {
+                if !output_set.contains(&node) {
+                    measured.push(node);
                 }
             }
         }
         
         measured.sort();
-        measured.dedup();
         measured
     }
     
-    fn process_command(&mut self, cmd: &Command) {
+    /// Record `wire` as the current wire of qubit `node`, labelling it in the HUGR
+    fn set_qubit_wire(&mut self, node: usize, wire: Wire) {
+        if let Some(producer) = self.dfg.hugr.get_node_mut(wire.node_id) {
+            producer.qubit_labels.insert(wire.port, node);
+        }
+        self.qubit_wires.insert(node, wire);
+    }
+    
+    fn process_command(&mut self, cmd: &Command) -> Result<(), ConversionError> {
         match cmd {
-            Command::N { node } => self.process_prepare(*node),
-            Command::E { nodes } => self.process_entangle(*nodes),
-            Command::M { node, plane, angle } => self.process_measure(*node, *plane, *angle),
-            Command::X { node, domain } => self.process_pauli_x(*node, domain),
-            Command::Z { node, domain } => self.process_pauli_z(*node, domain),
+            Command::N { node } => self.process_prepare(*node)?,
+            Command::E { nodes } => self.process_entangle(*nodes)?,
+            Command::M { node, plane, angle } => self.process_measure(*node, *plane, *angle)?,
+            Command::MZ { node } => self.process_measure_z(*node)?,
+            Command::MSoft { node, plane, angle } => self.process_measure_soft(*node, *plane, *angle)?,
+            Command::MAdaptive { node, plane, angle } => self.process_measure_adaptive(*node, *plane, angle)?,
+            Command::X { node, domain } => self.process_pauli_x(*node, domain)?,
+            Command::Z { node, domain } => self.process_pauli_z(*node, domain)?,
+            Command::XControlled {
+                node,
+                controls,
+                combiner,
+            } => self.process_x_controlled(*node, controls, *combiner)?,
             Command::C { node, clifford } => self.process_clifford(*node, clifford),
+            Command::Gate {
+                node, name, params, ..
+            } => self.process_custom_gate(*node, name, params),
+            Command::Branch {
+                domain,
+                if_true,
+                if_false,
+            } => self.process_branch(domain, if_true, if_false)?,
         }
+        Ok(())
     }
     
-    fn process_prepare(&mut self, node: usize) {
-        let dfg = self.dfg.as_mut().unwrap();
+    /// Lower a branch to a `Conditional` on the parity of `domain`, with case
+    /// 0 running `if_false` and case 1 `if_true`. The cases take the live
+    /// qubits either of them acts on, followed by the earlier outcomes their
+    /// corrections read, and return the qubits still live followed by the
+    /// outcomes they measure. Both cases must end with the same qubits live
+    /// and the same nodes measured.
+    fn process_branch(
+        &mut self,
+        domain: &HashSet<usize>,
+        if_true: &[Command],
+        if_false: &[Command],
+    ) -> Result<(), ConversionError> {
+        if self.config.defer_measurements {
+            return Err(ConversionError::UnsupportedUnderConfig {
+                command_kind: CommandKind::Branch,
+                reason: "branch conditions need outcomes, which are deferred",
+            });
+        }
+        /// Outcomes read by `commands` and nodes measured by them, recursively
+        fn scan(
+            commands: &[Command],
+            reads: &mut BTreeSet<usize>,
+            measures: &mut BTreeSet<usize>,
+        ) -> Result<(), ConversionError> {
+            for cmd in commands {
+                match cmd {
+                    Command::MSoft { .. } => {
+                        return Err(ConversionError::UnsupportedInBranch(CommandKind::MSoft))
+                    }
+                    Command::Branch {
+                        domain,
+                        if_true,
+                        if_false,
+                    } => {
+                        reads.extend(domain);
+                        scan(if_true, reads, measures)?;
+                        scan(if_false, reads, measures)?;
+                    }
+                    other => {
+                        reads.extend(other.domain().into_iter().flatten());
+                        measures.extend(other.measured_node());
+                    }
+                }
+            }
+            Ok(())
+        }
+        let (mut reads, mut measures) = (BTreeSet::new(), BTreeSet::new());
+        scan(if_true, &mut reads, &mut measures)?;
+        scan(if_false, &mut reads, &mut measures)?;
+        let outcomes: Vec<usize> = reads.difference(&measures).cloned().collect();
+        let touched: BTreeSet<usize> = if_true.iter().chain(if_false).flat_map(|cmd| cmd.nodes()).collect();
+        let qubits: Vec<usize> = touched
+            .iter()
+            .cloned()
+            .filter(|node| self.qubit_wires.contains_key(node))
+            .collect();
         
-        let prep_op = Operation::Custom {
-            name: "PrepareQubit".to_string(),
-            signature: FunctionType::new(vec![], vec![HugrType::Qubit]),
-            extension: QUANTUM_EXTENSION.to_string(),
-            args: vec![],
+        let mut inputs: Vec<Wire> = qubits.iter().map(|node| self.qubit_wires[node]).collect();
+        for &node in &outcomes {
+            inputs.push(
+                *self
+                    .classical_wires
+                    .get(&node)
+                    .ok_or(ConversionError::MissingMeasurement(node))?,
+            );
+        }
+        let condition = self.combine_measurements(domain, ClassicalOp::Xor)?;
+        let predicate = self.dfg.bool_to_sum(condition);
+        
+        let mut case_inputs = vec![HugrType::Qubit; qubits.len()];
+        case_inputs.extend(vec![HugrType::Bool; outcomes.len()]);
+        
+        // Qubits live and nodes measured at the end of the first case
+        let mut case_results: Option<(Vec<usize>, Vec<usize>)> = None;
+        let mut cases = Vec::with_capacity(2);
+        let saved_qubits = self.qubit_wires.clone();
+        let saved_outcomes = self.classical_wires.clone();
+        let saved_measured = (self.measured_qubits.clone(), self.earlier_outcomes.clone());
+        for commands in [if_false, if_true] {
+            let case = self.dfg.hugr.add_node(Operation::Case {
+                signature: FunctionType::new(case_inputs.clone(), vec![]),
+            });
+            let first = self.dfg.hugr.next_node_id;
+            let case_input = self.dfg.hugr.add_node(Operation::Input {
+                types: case_inputs.clone(),
+            });
+            
+            // Run the case against the case's own input wires
+            for (port, &node) in qubits.iter().enumerate() {
+                self.set_qubit_wire(node, Wire::new(case_input, port));
+            }
+            for (i, &node) in outcomes.iter().enumerate() {
+                self.classical_wires
+                    .insert(node, Wire::new(case_input, qubits.len() + i));
+            }
+            for cmd in commands {
+                self.process_command(cmd)?;
+            }
+            
+            let live: Vec<usize> = touched
+                .iter()
+                .cloned()
+                .filter(|node| self.qubit_wires.contains_key(node))
+                .collect();
+            let measured: Vec<usize> = measures
+                .iter()
+                .cloned()
+                .filter(|node| self.classical_wires.contains_key(node))
+                .collect();
+            match &case_results {
+                Some(expected) if *expected != (live.clone(), measured.clone()) => {
+                    return Err(ConversionError::BranchSignatureMismatch)
+                }
+                _ => {}
+            }
+            
+            let mut results: Vec<Wire> = live.iter().map(|node| self.qubit_wires[node]).collect();
+            results.extend(measured.iter().map(|node| self.classical_wires[node]));
+            let mut result_types = vec![HugrType::Qubit; live.len()];
+            result_types.extend(vec![HugrType::Bool; measured.len()]);
+            self.dfg.add_op(
+                Operation::Output {
+                    types: result_types.clone(),
+                },
+                results,
+            );
+            if let Some(node) = self.dfg.hugr.get_node_mut(case) {
+                node.operation = Operation::Case {
+                    signature: FunctionType::new(case_inputs.clone(), result_types),
+                };
+            }
+            self.qubit_wires = saved_qubits.clone();
+            self.classical_wires = saved_outcomes.clone();
+            (self.measured_qubits, self.earlier_outcomes) = saved_measured.clone();
+            
+            for id in first..self.dfg.hugr.next_node_id {
+                if let Some(node) = self.dfg.hugr.get_node_mut(id) {
+                    node.parent.get_or_insert(case);
+                }
+            }
+            case_results = Some((live, measured));
+            cases.push(case);
+        }
+        let (live, measured) = case_results.unwrap_or_default();
+        
+        let mut conditional_inputs = vec![HugrType::unit_sum(2)];
+        conditional_inputs.extend(case_inputs);
+        let mut conditional_outputs = vec![HugrType::Qubit; live.len()];
+        conditional_outputs.extend(vec![HugrType::Bool; measured.len()]);
+        let conditional = Operation::Conditional {
+            signature: FunctionType::new(conditional_inputs, conditional_outputs),
         };
+        let node = self
+            .dfg
+            .add_op(conditional, std::iter::once(predicate).chain(inputs).collect());
+        let (conditional_id, outputs) = (node.id, node.outputs.clone());
+        for case in cases {
+            if let Some(node) = self.dfg.hugr.get_node_mut(case) {
+                node.parent = Some(conditional_id);
+            }
+        }
         
-        let result_node = dfg.add_op(prep_op, vec![]);
-        let wire = result_node.out(0);
+        for node in &qubits {
+            self.qubit_wires.remove(node);
+        }
+        for (&node, &wire) in live.iter().zip(&outputs) {
+            self.set_qubit_wire(node, wire);
+        }
+        for (&node, &wire) in measured.iter().zip(&outputs[live.len()..]) {
+            self.record_outcome(node, wire);
+        }
+        Ok(())
+    }
+    
+    /// Prepare a fresh qubit for `node`, which must not have a live qubit
+    /// (input or prepared) that hasn't been measured away
+    fn process_prepare(&mut self, node: usize) -> Result<(), ConversionError> {
+        if self.qubit_wires.contains_key(&node) {
+            return Err(ConversionError::QubitAlreadyLive(node));
+        }
+        self.measured_qubits.remove(&node);
+        let prep_op = self.create_prepare_op();
+        let allocated = if self.config.explicit_qubit_scopes {
+            self.dfg.add_op_wires(self.create_scope_op("QAlloc"), vec![])
+        } else {
+            vec![]
+        };
+        let wires = self.dfg.add_op_wires(prep_op, allocated);
         
-        self.qubit_wires.insert(node, wire);
+        self.set_qubit_wire(node, wires[0]);
         self.node_order.push(node);
+        Ok(())
     }
     
-    fn process_entangle(&mut self, nodes: (usize, usize)) {
+    fn process_entangle(&mut self, nodes: (usize, usize)) -> Result<(), ConversionError> {
         let (node1, node2) = nodes;
+        if node1 == node2 {
+            return Err(ConversionError::SelfEntanglement(node1));
+        }
+        let q1 = *self.qubit_wires.get(&node1).ok_or(ConversionError::NodeNotFound(node1))?;
+        let q2 = *self.qubit_wires.get(&node2).ok_or(ConversionError::NodeNotFound(node2))?;
         
-        if let (Some(&q1), Some(&q2)) = (
-            self.qubit_wires.get(&node1),
-            self.qubit_wires.get(&node2),
-        ) {
-            // FIXED: Create the operation first (immutable borrow)
-            let cz_op = self.create_cz_gate();
-            
-            // Then get mutable reference to dfg
-            let dfg = self.dfg.as_mut().unwrap();
-            let result_node = dfg.add_op(cz_op, vec![q1, q2]);
-            
-            self.qubit_wires.insert(node1, result_node.out(0));
-            self.qubit_wires.insert(node2, result_node.out(1));
+        if let Some(path) = self.routing_path(node1, node2)? {
+            self.entangle_along(&path);
+            return Ok(());
         }
+        let [w1, w2] = self.add_entangler(q1, q2);
+        self.set_qubit_wire(node1, w1);
+        self.set_qubit_wire(node2, w2);
+        Ok(())
     }
     
-    fn process_measure(&mut self, node: usize, plane: Plane, angle: f64) {
-        if let Some(mut qubit_wire) = self.qubit_wires.get(&node).cloned() {
-            // Apply basis change based on measurement plane
-            match plane {
-                Plane::XY => {
-                    // XY plane: Rz(-angle) * H
-                    if angle.abs() > 1e-10 {
-                        let rz_op = self.create_rz_gate(-angle);
-                        let dfg = self.dfg.as_mut().unwrap();
-                        let node = dfg.add_op(rz_op, vec![qubit_wire]);
-                        qubit_wire = node.out(0);
-                    }
-                    
-                    let h_op = self.create_h_gate();
-                    let dfg = self.dfg.as_mut().unwrap();
-                    let node = dfg.add_op(h_op, vec![qubit_wire]);
-                    qubit_wire = node.out(0);
-                }
-                Plane::YZ => {
-                    if angle.abs() > 1e-10 {
-                        let rx_op = self.create_rx_gate(-angle);
-                        let dfg = self.dfg.as_mut().unwrap();
-                        let node = dfg.add_op(rx_op, vec![qubit_wire]);
-                        qubit_wire = node.out(0);
-                    }
+    /// Entangle two qubit wires with the configured `entangling_gate`,
+    /// returning the output wires in the same order
+    fn add_entangler(&mut self, q1: Wire, q2: Wire) -> [Wire; 2] {
+        match self.config.entangling_gate {
+            EdgeKind::CZ => {
+                let wires = self.dfg.add_op_wires(self.create_cz_gate(), vec![q1, q2]);
+                [wires[0], wires[1]]
+            }
+            EdgeKind::CX => {
+                let q2 = self.dfg.add_op_wires(Operation::from(CliffordGate::H), vec![q2])[0];
+                let wires = self.dfg.add_op_wires(self.create_cx_gate(), vec![q1, q2]);
+                let q2 = self.dfg.add_op_wires(Operation::from(CliffordGate::H), vec![wires[1]])[0];
+                [wires[0], q2]
+            }
+        }
+    }
+    
+    /// Shortest path from `from` to `to` through live qubits of the coupling
+    /// graph, or `None` if there is no connectivity constraint or the two are
+    /// already adjacent
+    fn routing_path(&self, from: usize, to: usize) -> Result<Option<Vec<usize>>, ConversionError> {
+        let Some(edges) = &self.config.connectivity else {
+            return Ok(None);
+        };
+        let adjacent = |a: usize, b: usize| edges.contains(&(a, b)) || edges.contains(&(b, a));
+        if adjacent(from, to) {
+            return Ok(None);
+        }
+        
+        let mut previous: HashMap<usize, usize> = HashMap::new();
+        let mut queue = std::collections::VecDeque::from([from]);
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                let mut path = vec![to];
+                while let Some(&prev) = previous.get(path.last().unwrap()) {
+                    path.push(prev);
                 }
-                Plane::XZ => {
-                    if angle.abs() > 1e-10 {
-                        let ry_op = self.create_ry_gate(angle);
-                        let dfg = self.dfg.as_mut().unwrap();
-                        let node = dfg.add_op(ry_op, vec![qubit_wire]);
-                        qubit_wire = node.out(0);
+                path.reverse();
+                return Ok(Some(path));
+            }
+            let mut neighbours: Vec<usize> = edges
+                .iter()
+                .filter_map(|&(a, b)| {
+                    if a == current {
+                        Some(b)
+                    } else if b == current {
+                        Some(a)
+                    } else {
+                        None
                     }
+                })
+                .filter(|n| *n != from && !previous.contains_key(n))
+                .filter(|n| self.qubit_wires.contains_key(n))
+                .collect();
+            neighbours.sort();
+            neighbours.dedup();
+            for next in neighbours {
+                previous.insert(next, current);
+                queue.push_back(next);
+            }
+        }
+        Err(ConversionError::Unroutable { nodes: (from, to) })
+    }
+    
+    /// `CZ` between the ends of `path`: swap the first qubit along the path
+    /// until it is adjacent to the last, entangle, then swap it back so every
+    /// qubit ends where it started
+    fn entangle_along(&mut self, path: &[usize]) {
+        let mut wires: Vec<Wire> = path.iter().map(|node| self.qubit_wires[node]).collect();
+        let last = path.len() - 1;
+        let swap = |this: &mut Self, wires: &mut Vec<Wire>, i: usize| {
+            let swap_op = this.create_swap_gate();
+            let out = this.dfg.add_op_wires(swap_op, vec![wires[i], wires[i + 1]]);
+            wires[i] = out[0];
+            wires[i + 1] = out[1];
+        };
+        
+        for i in 0..last - 1 {
+            swap(self, &mut wires, i);
+        }
+        [wires[last - 1], wires[last]] = self.add_entangler(wires[last - 1], wires[last]);
+        for i in (0..last - 1).rev() {
+            swap(self, &mut wires, i);
+        }
+        
+        for (&node, wire) in path.iter().zip(wires) {
+            self.set_qubit_wire(node, wire);
+        }
+    }
+    
+    fn process_measure(&mut self, node: usize, plane: Plane, angle: f64) -> Result<(), ConversionError> {
+        check_angle(node, angle)?;
+        
+        let qubit_wire = self.measurable_wire(node)?;
+        let qubit_wire = self.rotate_into_basis(node, qubit_wire, plane, angle);
+        self.measure_or_defer(node, qubit_wire);
+        Ok(())
+    }
+    
+    fn process_measure_z(&mut self, node: usize) -> Result<(), ConversionError> {
+        let qubit_wire = self.measurable_wire(node)?;
+        self.measure_or_defer(node, qubit_wire);
+        Ok(())
+    }
+    
+    /// Live qubit wire of `node`, whose current qubit must not have been
+    /// measured yet
+    fn measurable_wire(&self, node: usize) -> Result<Wire, ConversionError> {
+        if self.measured_qubits.contains(&node) || self.deferred.contains(&node) {
+            return Err(ConversionError::AlreadyMeasured(node));
+        }
+        self.qubit_wires
+            .get(&node)
+            .cloned()
+            .ok_or(ConversionError::NodeNotFound(node))
+    }
+    
+    /// Measure in the given basis with `MeasureSoft`, keeping the likelihood
+    /// wire in `soft_wires`
+    fn process_measure_soft(&mut self, node: usize, plane: Plane, angle: f64) -> Result<(), ConversionError> {
+        check_angle(node, angle)?;
+        if self.config.defer_measurements {
+            return Err(ConversionError::UnsupportedUnderConfig {
+                command_kind: CommandKind::MSoft,
+                reason: "soft measurements cannot be deferred",
+            });
+        }
+        if self.config.explicit_qubit_scopes {
+            return Err(ConversionError::UnsupportedUnderConfig {
+                command_kind: CommandKind::MSoft,
+                reason: "soft measurements consume their qubit, leaving nothing to free",
+            });
+        }
+        
+        let qubit_wire = self.measurable_wire(node)?;
+        let qubit_wire = self.rotate_into_basis(node, qubit_wire, plane, angle);
+        let wires = self.dfg.add_op_wires(self.create_measure_soft_op(), vec![qubit_wire]);
+        self.record_outcome(node, wires[0]);
+        self.soft_wires.insert(node, wires[1]);
+        self.qubit_wires.remove(&node);
+        Ok(())
+    }
+    
+    /// Measure at a fixed angle like `M`, or at a computed angle built as a
+    /// `Float64` wire `base + parity * shift` (negated outside the XZ plane,
+    /// as for fixed angles) feeding an angle-wire rotation
+    fn process_measure_adaptive(&mut self, node: usize, plane: Plane, angle: &Angle) -> Result<(), ConversionError> {
+        let (base, shift_domain, shift) = match angle {
+            Angle::Fixed(angle) => return self.process_measure(node, plane, *angle),
+            Angle::Computed {
+                base,
+                shift_domain,
+                shift,
+            } => (*base, shift_domain, *shift),
+        };
+        check_angle(node, base)?;
+        check_angle(node, shift)?;
+        if shift_domain.is_empty() {
+            return self.process_measure(node, plane, base);
+        }
+        if self.config.defer_measurements {
+            return Err(ConversionError::UnsupportedUnderConfig {
+                command_kind: CommandKind::MAdaptive,
+                reason: "computed angles need outcomes, which are deferred",
+            });
+        }
+        
+        let qubit_wire = self.measurable_wire(node)?;
+        let sign = if plane == Plane::XZ { 1.0 } else { -1.0 };
+        let parity = self.combine_measurements(shift_domain, ClassicalOp::Xor)?;
+        let parity = self.dfg.add_op_wires(self.create_float_op("BoolToFloat", 1), vec![parity])[0];
+        let shift_const = self.dfg.add_const(ConstValue::Float(sign * shift));
+        let shift = self.dfg.load_const(shift_const);
+        let shifted = self.dfg.add_op_wires(self.create_float_op("FMul", 2), vec![parity, shift])[0];
+        let base_const = self.dfg.add_const(ConstValue::Float(sign * base));
+        let base = self.dfg.load_const(base_const);
+        let angle = self.dfg.add_op_wires(self.create_float_op("FAdd", 2), vec![base, shifted])[0];
+        
+        let rotation = match plane {
+            Plane::XY => "RzWire",
+            Plane::YZ => "RxWire",
+            Plane::XZ => "RyWire",
+        };
+        let mut qubit_wire = self
+            .dfg
+            .add_op_wires(self.create_wire_rotation(rotation), vec![qubit_wire, angle])[0];
+        self.set_qubit_wire(node, qubit_wire);
+        if plane == Plane::XY {
+            qubit_wire = self.dfg.add_op_wires(Operation::from(CliffordGate::H), vec![qubit_wire])[0];
+            self.set_qubit_wire(node, qubit_wire);
+        }
+        
+        self.measure_or_defer(node, qubit_wire);
+        Ok(())
+    }
+    
+    /// Rotate `node` so that its measurement in `plane` at `angle` becomes a
+    /// Z measurement. A symbolic angle always gets its rotation, tagged with
+    /// `parameter` metadata (the name, prefixed with `-` when the rotation
+    /// angle is the negated measurement angle).
+    fn rotate_into_basis(&mut self, node: usize, mut qubit_wire: Wire, plane: Plane, angle: f64) -> Wire {
+        let parameter = self.angle_parameters.get(&node).cloned();
+        let mut ops = self.measurement_basis_change(plane, angle);
+        if parameter.is_some() && angle.abs() <= 1e-10 {
+            let rotation = match plane {
+                Plane::XY => self.create_rz_gate(-angle),
+                Plane::YZ => self.create_rx_gate(-angle),
+                Plane::XZ => self.create_ry_gate(angle),
+            };
+            ops.insert(0, rotation);
+        }
+        
+        for op in ops {
+            let is_rotation = matches!(&op, Operation::Custom { args, .. } if !args.is_empty());
+            qubit_wire = self.dfg.add_op_wires(op, vec![qubit_wire])[0];
+            self.set_qubit_wire(node, qubit_wire);
+            if let (true, Some(name)) = (is_rotation, &parameter) {
+                let sign = if plane == Plane::XZ { "" } else { "-" };
+                if let Some(rotation) = self.dfg.hugr.get_node_mut(qubit_wire.node_id) {
+                    rotation.set_meta("parameter", format!("{}{}", sign, name));
                 }
             }
-            
-            // Perform measurement in Z basis
-            let meas_op = self.create_measure_op();
-            let dfg = self.dfg.as_mut().unwrap();
-            let result_node = dfg.add_op(meas_op, vec![qubit_wire]);
-            
-            self.classical_wires.insert(node, result_node.out(0));
+        }
+        qubit_wire
+    }
+    
+    /// Measure now, or keep the qubit live (already rotated into the Z basis)
+    /// until the final measurement layer
+    fn measure_or_defer(&mut self, node: usize, qubit_wire: Wire) {
+        if self.config.defer_measurements {
+            self.deferred.push(node);
+        } else {
+            self.emit_measure(node, qubit_wire);
+        }
+    }
+    
+    /// Measure `qubit_wire` in the Z basis and record the outcome of `node`
+    fn emit_measure(&mut self, node: usize, qubit_wire: Wire) {
+        let meas_op = self.create_measure_op();
+        let wires = self.dfg.add_op_wires(meas_op, vec![qubit_wire]);
+        
+        if self.config.nondestructive_measure {
+            // The qubit stays live, left in the rotated measurement basis
+            self.record_outcome(node, wires[1]);
+            self.set_qubit_wire(node, wires[0]);
+        } else if self.config.explicit_qubit_scopes {
+            self.record_outcome(node, wires[1]);
+            self.dfg.add_op(self.create_scope_op("QFree"), vec![wires[0]]);
+            self.qubit_wires.remove(&node);
+        } else {
+            self.record_outcome(node, wires[0]);
             self.qubit_wires.remove(&node);
         }
     }
     
-    fn process_pauli_x(&mut self, node: usize, domain: &HashSet<usize>) {
-        if let Some(qubit_wire) = self.qubit_wires.get(&node).cloned() {
-            let condition = self.compute_xor_of_measurements(domain);
+    /// Record `outcome` as the latest outcome of `node`, moving an outcome of
+    /// an earlier preparation into `earlier_outcomes`
+    fn record_outcome(&mut self, node: usize, outcome: Wire) {
+        if let Some(earlier) = self.classical_wires.insert(node, outcome) {
+            self.earlier_outcomes.entry(node).or_default().push(earlier);
+        }
+        self.measured_qubits.insert(node);
+    }
+    
+    fn process_pauli_x(&mut self, node: usize, domain: &HashSet<usize>) -> Result<(), ConversionError> {
+        self.process_pauli(node, domain, "X")
+    }
+    
+    fn process_pauli_z(&mut self, node: usize, domain: &HashSet<usize>) -> Result<(), ConversionError> {
+        self.process_pauli(node, domain, "Z")
+    }
+    
+    /// X on `node` when `combiner` applied to the outcomes of `controls` is 1
+    fn process_x_controlled(
+        &mut self,
+        node: usize,
+        controls: &HashSet<usize>,
+        combiner: ClassicalOp,
+    ) -> Result<(), ConversionError> {
+        if combiner == ClassicalOp::Xor {
+            return self.process_pauli(node, controls, "X");
+        }
+        if self.config.defer_measurements && controls.iter().any(|c| self.deferred.contains(c)) {
+            return Err(ConversionError::UnsupportedUnderConfig {
+                command_kind: CommandKind::XControlled,
+                reason: "non-XOR combiners over deferred outcomes have no coherent form",
+            });
+        }
+        
+        if let Some(&qubit_wire) = self.qubit_wires.get(&node) {
+            let condition = self.combine_measurements(controls, combiner)?;
             let new_wire = self.apply_conditional_gate(qubit_wire, condition, "X");
-            self.qubit_wires.insert(node, new_wire);
+            self.set_qubit_wire(node, new_wire);
         }
+        Ok(())
     }
     
-    fn process_pauli_z(&mut self, node: usize, domain: &HashSet<usize>) {
-        if let Some(qubit_wire) = self.qubit_wires.get(&node).cloned() {
-            let condition = self.compute_xor_of_measurements(domain);
-            let new_wire = self.apply_conditional_gate(qubit_wire, condition, "Z");
-            self.qubit_wires.insert(node, new_wire);
+    /// Pauli `gate` (`"X"` or `"Z"`) on `node`, conditioned on the parity of
+    /// `domain`
+    fn process_pauli(&mut self, node: usize, domain: &HashSet<usize>, gate: &str) -> Result<(), ConversionError> {
+        if !self.qubit_wires.contains_key(&node) {
+            return Ok(());
+        }
+        
+        let mut classical = domain.clone();
+        if self.config.defer_measurements {
+            // Deferred outcomes are still live qubits and act through
+            // controlled gates; anything else (e.g. a classical input) is
+            // folded classically as usual
+            let mut controls: Vec<usize> = domain
+                .iter()
+                .cloned()
+                .filter(|d| self.deferred.contains(d))
+                .collect();
+            controls.sort();
+            classical.retain(|d| !controls.contains(d));
+            
+            let controlled = if gate == "X" {
+                self.create_cx_gate()
+            } else {
+                self.create_cz_gate()
+            };
+            self.apply_controlled_corrections(node, &controls, controlled);
+            if classical.is_empty() {
+                return Ok(());
+            }
+        }
+        
+        let condition = self.combine_measurements(&classical, ClassicalOp::Xor)?;
+        let new_wire = self.apply_conditional_gate(self.qubit_wires[&node], condition, gate);
+        self.set_qubit_wire(node, new_wire);
+        Ok(())
+    }
+    
+    /// Coherent form of a Pauli correction: `gate` (`CX` or `CZ`) controlled
+    /// by each deferred qubit in `controls`. The parity of the outcomes
+    /// becomes a product of controlled gates.
+    fn apply_controlled_corrections(&mut self, node: usize, controls: &[usize], gate: Operation) {
+        for &control in controls {
+            if let (Some(&c), Some(&t)) = (self.qubit_wires.get(&control), self.qubit_wires.get(&node)) {
+                let wires = self.dfg.add_op_wires(gate.clone(), vec![c, t]);
+                self.set_qubit_wire(control, wires[0]);
+                self.set_qubit_wire(node, wires[1]);
+            }
         }
     }
     
     fn process_clifford(&mut self, node: usize, clifford: &[CliffordGate]) {
+        let non_identity = clifford.iter().filter(|&&g| g != CliffordGate::I).count();
+        if self.config.fuse_cliffords && non_identity > 1 {
+            return self.process_fused_clifford(node, clifford);
+        }
+        
         if let Some(mut qubit_wire) = self.qubit_wires.get(&node).cloned() {
             for &gate in clifford {
-                let op = match gate {
-                    CliffordGate::H => self.create_h_gate(),
-                    CliffordGate::S => self.create_s_gate(),
-                    CliffordGate::X => self.create_x_gate(),
-                    CliffordGate::Y => self.create_y_gate(),
-                    CliffordGate::Z => self.create_z_gate(),
-                    CliffordGate::SDG => self.create_sdg_gate(),
-                    CliffordGate::I => continue, // Skip identity
-                };
-                
-                let dfg = self.dfg.as_mut().unwrap();
-                let result_node = dfg.add_op(op, vec![qubit_wire]);
-                qubit_wire = result_node.out(0);
+                if gate == CliffordGate::I {
+                    continue; // Skip identity
+                }
+                let op = Operation::from(gate);
+                qubit_wire = self.dfg.add_op_wires(op, vec![qubit_wire])[0];
+                self.set_qubit_wire(node, qubit_wire);
             }
+        }
+    }
+    
+    /// Emit the product of `clifford` as `Rz`-`Ry`-`Rz`, dropping rotations
+    /// by multiples of 2π
+    fn process_fused_clifford(&mut self, node: usize, clifford: &[CliffordGate]) {
+        let Some(mut qubit_wire) = self.qubit_wires.get(&node).cloned() else {
+            return;
+        };
+        
+        // Gates apply left to right, so later gates multiply from the left
+        let product = clifford.iter().fold(clifford_matrix(CliffordGate::I), |acc, &gate| {
+            let g = clifford_matrix(gate);
+            let mut out = [[Complex::ZERO; 2]; 2];
+            for (i, row) in out.iter_mut().enumerate() {
+                for (j, entry) in row.iter_mut().enumerate() {
+                    *entry = g[i][0] * acc[0][j] + g[i][1] * acc[1][j];
+                }
+            }
+            out
+        });
+        let (beta, gamma, delta) = zyz_angles(&product);
+        
+        let rotations = [
+            self.create_rz_gate(delta),
+            self.create_ry_gate(gamma),
+            self.create_rz_gate(beta),
+        ];
+        let nonzero = [delta, gamma, beta].map(|angle| {
+            let reduced = angle.rem_euclid(2.0 * std::f64::consts::PI);
+            reduced > 1e-10 && 2.0 * std::f64::consts::PI - reduced > 1e-10
+        });
+        for (op, keep) in rotations.into_iter().zip(nonzero) {
+            if keep {
+                qubit_wire = self.dfg.add_op_wires(op, vec![qubit_wire])[0];
+                self.set_qubit_wire(node, qubit_wire);
+            }
+        }
+    }
+    
+    fn process_custom_gate(&mut self, node: usize, name: &str, params: &[f64]) {
+        if let Some(qubit_wire) = self.qubit_wires.get(&node).cloned() {
+            let op = Operation::Custom {
+                name: name.to_string(),
+                signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]),
+                extension: QUANTUM_EXTENSION.to_string(),
+                args: params.to_vec(),
+            };
             
-            self.qubit_wires.insert(node, qubit_wire);
+            let wires = self.dfg.add_op_wires(op, vec![qubit_wire]);
+            self.set_qubit_wire(node, wires[0]);
         }
     }
     
-    fn compute_xor_of_measurements(&mut self, domain: &HashSet<usize>) -> Wire {
+    /// Fold the outcomes of `domain` with `op` as a balanced tree over the
+    /// sorted domain. Outcome wires are only read, never removed from
+    /// `classical_wires`: a `Bool` wire may fan out to several corrections as
+    /// well as to the classical outputs. Every domain node must already have
+    /// an outcome.
+    fn combine_measurements(&mut self, domain: &HashSet<usize>, op: ClassicalOp) -> Result<Wire, ConversionError> {
         if domain.is_empty() {
-            let dfg = self.dfg.as_mut().unwrap();
+            let dfg = &mut self.dfg;
             let false_const = dfg.add_const(ConstValue::Bool(false));
-            return dfg.load_const(false_const);
+            return Ok(dfg.load_const(false_const));
         }
         
         let mut domain_list: Vec<_> = domain.iter().cloned().collect();
         domain_list.sort();
         
-        let first_node = domain_list[0];
-        if let Some(&result) = self.classical_wires.get(&first_node) {
-            let mut xor_result = result;
-            
-            for &node_idx in &domain_list[1..] {
-                if let Some(&wire) = self.classical_wires.get(&node_idx) {
-                    let dfg = self.dfg.as_mut().unwrap();
-                    let xor_op = Operation::Custom {
-                        name: "XOR".to_string(),
-                        signature: FunctionType::new(
-                            vec![HugrType::Bool, HugrType::Bool],
-                            vec![HugrType::Bool],
-                        ),
-                        extension: LOGIC_EXTENSION.to_string(),
-                        args: vec![],
-                    };
-                    
-                    let result_node = dfg.add_op(xor_op, vec![xor_result, wire]);
-                    xor_result = result_node.out(0);
+        let outcome = |node: usize| {
+            self.classical_wires
+                .get(&node)
+                .copied()
+                .ok_or(ConversionError::MissingMeasurement(node))
+        };
+        let mut layer = domain_list
+            .into_iter()
+            .map(outcome)
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        // Combine neighbouring pairs level by level, giving a balanced tree
+        // of depth ceil(log2 n) rather than a chain of depth n - 1
+        while layer.len() > 1 {
+            let mut next = Vec::with_capacity(layer.len().div_ceil(2));
+            for pair in layer.chunks(2) {
+                match *pair {
+                    [lhs, rhs] => {
+                        let logic_op = Operation::Custom {
+                            name: op.op_name().to_string(),
+                            signature: FunctionType::new(
+                                vec![HugrType::Bool, HugrType::Bool],
+                                vec![HugrType::Bool],
+                            ),
+                            extension: LOGIC_EXTENSION.to_string(),
+                            args: vec![],
+                        };
+                        next.push(self.dfg.add_op_wires(logic_op, vec![lhs, rhs])[0]);
+                    }
+                    [single] => next.push(single),
+                    _ => unreachable!("chunks(2) yields one or two wires"),
                 }
             }
-            
-            xor_result
-        } else {
-            let dfg = self.dfg.as_mut().unwrap();
-            let false_const = dfg.add_const(ConstValue::Bool(false));
-            dfg.load_const(false_const)
+            layer = next;
         }
+        let combined = layer[0];
+        
+        Ok(combined)
     }
     
     fn apply_conditional_gate(&mut self, qubit_wire: Wire, condition: Wire, gate_name: &str) -> Wire {
-        let dfg = self.dfg.as_mut().unwrap();
-        
         let cond_gate_op = Operation::Custom {
             name: format!("Conditional{}", gate_name),
             signature: FunctionType::new(
                 vec![HugrType::Bool, HugrType::Qubit],
                 vec![HugrType::Qubit],
             ),
-            extension: QUANTUM_EXTENSION.to_string(),
+            extension: self.config.pauli_ext().to_string(),
             args: vec![],
         };
         
-        let result_node = dfg.add_op(cond_gate_op, vec![condition, qubit_wire]);
-        result_node.out(0)
+        self.dfg.add_op_wires(cond_gate_op, vec![condition, qubit_wire])[0]
+    }
+    
+    /// Gates rotating the measurement basis of `plane`/`angle` onto the Z axis
+    pub(crate) fn measurement_basis_change(&self, plane: Plane, angle: f64) -> Vec<Operation> {
+        let mut ops = Vec::new();
+        match plane {
+            Plane::XY => {
+                // XY plane: Rz(-angle) * H
+                if angle.abs() > 1e-10 {
+                    ops.push(self.create_rz_gate(-angle));
+                }
+                ops.push(Operation::from(CliffordGate::H));
+            }
+            Plane::YZ => {
+                if angle.abs() > 1e-10 {
+                    ops.push(self.create_rx_gate(-angle));
+                }
+            }
+            Plane::XZ => {
+                if angle.abs() > 1e-10 {
+                    ops.push(self.create_ry_gate(angle));
+                }
+            }
+        }
+        ops
     }
     
     // Gate creation methods
     
-    fn create_h_gate(&self) -> Operation {
+    /// `QAlloc` (`[] -> [Qubit]`) or `QFree` (`[Qubit] -> []`)
+    fn create_scope_op(&self, name: &str) -> Operation {
+        let signature = if name == "QAlloc" {
+            FunctionType::new(vec![], vec![HugrType::Qubit])
+        } else {
+            FunctionType::new(vec![HugrType::Qubit], vec![])
+        };
         Operation::Custom {
-            name: "H".to_string(),
-            signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]),
+            name: name.to_string(),
+            signature,
             extension: QUANTUM_EXTENSION.to_string(),
             args: vec![],
         }
     }
     
-    fn create_x_gate(&self) -> Operation {
+    pub(crate) fn create_prepare_op(&self) -> Operation {
         Operation::Custom {
-            name: "X".to_string(),
-            signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]),
+            name: "PrepareQubit".to_string(),
+            signature: prepare_signature(&self.config),
             extension: QUANTUM_EXTENSION.to_string(),
             args: vec![],
         }
     }
     
-    fn create_y_gate(&self) -> Operation {
+    fn create_barrier_op(&self, n_qubits: usize) -> Operation {
         Operation::Custom {
-            name: "Y".to_string(),
-            signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]),
+            name: "Barrier".to_string(),
+            signature: FunctionType::new(
+                vec![HugrType::Qubit; n_qubits],
+                vec![HugrType::Qubit; n_qubits],
+            ),
             extension: QUANTUM_EXTENSION.to_string(),
             args: vec![],
         }
     }
     
-    fn create_z_gate(&self) -> Operation {
+    fn create_pack_op(&self, n_bits: usize) -> Operation {
         Operation::Custom {
-            name: "Z".to_string(),
-            signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]),
-            extension: QUANTUM_EXTENSION.to_string(),
+            name: "Pack".to_string(),
+            signature: FunctionType::new(vec![HugrType::Bool; n_bits], vec![HugrType::Int(n_bits)]),
+            extension: LOGIC_EXTENSION.to_string(),
             args: vec![],
         }
     }
     
-    fn create_s_gate(&self) -> Operation {
+    pub(crate) fn create_cz_gate(&self) -> Operation {
         Operation::Custom {
-            name: "S".to_string(),
-            signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]),
+            name: "CZ".to_string(),
+            signature: FunctionType::new(
+                vec![HugrType::Qubit, HugrType::Qubit],
+                vec![HugrType::Qubit, HugrType::Qubit],
+            ),
             extension: QUANTUM_EXTENSION.to_string(),
             args: vec![],
         }
     }
     
-    fn create_sdg_gate(&self) -> Operation {
+    fn create_swap_gate(&self) -> Operation {
         Operation::Custom {
-            name: "Sdg".to_string(),
-            signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]),
+            name: "SWAP".to_string(),
+            signature: FunctionType::new(
+                vec![HugrType::Qubit, HugrType::Qubit],
+                vec![HugrType::Qubit, HugrType::Qubit],
+            ),
             extension: QUANTUM_EXTENSION.to_string(),
             args: vec![],
         }
     }
     
-    fn create_cz_gate(&self) -> Operation {
+    fn create_cx_gate(&self) -> Operation {
         Operation::Custom {
-            name: "CZ".to_string(),
+            name: "CX".to_string(),
             signature: FunctionType::new(
                 vec![HugrType::Qubit, HugrType::Qubit],
                 vec![HugrType::Qubit, HugrType::Qubit],
@@ -387,7 +1634,7 @@ impl GraphixToHugrConverter {
         Operation::Custom {
             name: "Rz".to_string(),
             signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]),
-            extension: QUANTUM_EXTENSION.to_string(),
+            extension: self.config.rotation_ext().to_string(),
             args: vec![angle],
         }
     }
@@ -396,7 +1643,7 @@ impl GraphixToHugrConverter {
         Operation::Custom {
             name: "Rx".to_string(),
             signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]),
-            extension: QUANTUM_EXTENSION.to_string(),
+            extension: self.config.rotation_ext().to_string(),
             args: vec![angle],
         }
     }
@@ -405,16 +1652,54 @@ impl GraphixToHugrConverter {
         Operation::Custom {
             name: "Ry".to_string(),
             signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]),
-            extension: QUANTUM_EXTENSION.to_string(),
+            extension: self.config.rotation_ext().to_string(),
             args: vec![angle],
         }
     }
     
-    fn create_measure_op(&self) -> Operation {
+    /// Rotation `[Qubit, Float64] -> [Qubit]` taking its angle from a wire
+    fn create_wire_rotation(&self, name: &str) -> Operation {
+        Operation::Custom {
+            name: name.to_string(),
+            signature: FunctionType::new(vec![HugrType::Qubit, HugrType::Float64], vec![HugrType::Qubit]),
+            extension: self.config.rotation_ext().to_string(),
+            args: vec![],
+        }
+    }
+    
+    /// Float arithmetic op with `n_inputs` inputs and a `Float64` output;
+    /// `BoolToFloat` takes a `Bool`, everything else `Float64`s
+    fn create_float_op(&self, name: &str, n_inputs: usize) -> Operation {
+        let input = if name == "BoolToFloat" {
+            HugrType::Bool
+        } else {
+            HugrType::Float64
+        };
+        Operation::Custom {
+            name: name.to_string(),
+            signature: FunctionType::new(vec![input; n_inputs], vec![HugrType::Float64]),
+            extension: FLOAT_EXTENSION.to_string(),
+            args: vec![],
+        }
+    }
+    
+    pub(crate) fn create_measure_soft_op(&self) -> Operation {
+        Operation::Custom {
+            name: "MeasureSoft".to_string(),
+            signature: FunctionType::new(
+                vec![HugrType::Qubit],
+                vec![HugrType::Bool, HugrType::Float64],
+            ),
+            extension: self.config.measurement_ext().to_string(),
+            args: vec![],
+        }
+    }
+    
+    pub(crate) fn create_measure_op(&self) -> Operation {
         Operation::Custom {
             name: "Measure".to_string(),
-            signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Bool]),
-            extension: QUANTUM_EXTENSION.to_string(),
+            signature: measure_signature(&self.config),
+            extension: self.config.measurement_ext().to_string(),
             args: vec![],
         }
     }
@@ -430,4 +1715,294 @@ impl Default for GraphixToHugrConverter {
 pub fn convert_graphix_pattern_to_hugr(pattern: &Pattern) -> Result<Hugr, ConversionError> {
     let mut converter = GraphixToHugrConverter::new();
     converter.convert(pattern)
-}
\ No newline at end of file
+}
+
+/// Split a pattern into its resource state and the computation consuming it:
+/// the leading `N`/`E` commands become one HUGR whose outputs are the graph
+/// state's qubits, and the remaining commands a second HUGR taking those
+/// qubits as inputs, in the same ascending node order. No `N` or `E` command
+/// may follow the first other command.
+pub fn split_resource_and_consumption(pattern: &Pattern) -> Result<(Hugr, Hugr), ConversionError> {
+    let is_resource = |cmd: &Command| matches!(cmd.kind(), CommandKind::N | CommandKind::E);
+    let split = pattern
+        .iter()
+        .position(|cmd| !is_resource(cmd))
+        .unwrap_or(pattern.commands.len());
+    if let Some(offset) = pattern.commands[split..].iter().position(is_resource) {
+        return Err(ConversionError::NotStandardForm {
+            at_command: split + offset,
+        });
+    }
+    
+    let resource = pattern.slice(0..split);
+    let mut consumption = pattern.slice(split..pattern.commands.len());
+    consumption.output_nodes = pattern.output_nodes.clone();
+    
+    let resource_hugr = GraphixToHugrConverter::new().convert(&resource)?;
+    let consumption_hugr = GraphixToHugrConverter::new().convert(&consumption)?;
+    Ok((resource_hugr, consumption_hugr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn converter_with_outcomes(nodes: &[usize]) -> GraphixToHugrConverter {
+        let mut converter = GraphixToHugrConverter::new();
+        let dfg = DfgBuilder::new(vec![HugrType::Bool; nodes.len()]);
+        for (i, &node) in nodes.iter().enumerate() {
+            converter.classical_wires.insert(node, dfg.input_wires[i]);
+        }
+        converter.dfg = dfg;
+        converter
+    }
+
+    fn logic_op_names(converter: &GraphixToHugrConverter) -> Vec<String> {
+        let hugr = &converter.dfg.hugr;
+        let mut ids: Vec<_> = hugr.nodes.keys().cloned().collect();
+        ids.sort();
+        ids.iter()
+            .filter_map(|id| match &hugr.nodes[id].operation {
+                Operation::Custom { name, extension, .. } if extension == LOGIC_EXTENSION => {
+                    Some(name.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn check_combinator(op: ClassicalOp, expected: &str) {
+        let mut converter = converter_with_outcomes(&[1, 2, 3, 4]);
+        let domain: HashSet<usize> = [1, 2, 3, 4].into_iter().collect();
+        let result = converter.combine_measurements(&domain, op).unwrap();
+
+        assert_eq!(logic_op_names(&converter), vec![expected; 3]);
+
+        // The tree is balanced: the root combines the two pair results
+        // rather than extending a chain with a raw outcome
+        let hugr = &converter.dfg.hugr;
+        let root = hugr.get_node(result.node_id).unwrap();
+        for input in &root.inputs {
+            let pair = hugr.get_node(input.node_id).unwrap();
+            assert!(matches!(&pair.operation, Operation::Custom { name, .. } if name == expected));
+        }
+    }
+
+    #[test]
+    fn test_combine_xor() {
+        check_combinator(ClassicalOp::Xor, "XOR");
+    }
+
+    #[test]
+    fn test_combine_and() {
+        check_combinator(ClassicalOp::And, "AND");
+    }
+
+    #[test]
+    fn test_combine_or() {
+        check_combinator(ClassicalOp::Or, "OR");
+    }
+
+    #[test]
+    fn test_cancel_stops_conversion() {
+        let mut pattern = Pattern::new(vec![], vec![0, 1, 2]);
+        for node in 0..3 {
+            pattern.add_command(Command::N { node });
+        }
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::E { nodes: (1, 2) });
+
+        // Raise the flag once a few commands have gone through
+        let cancel = AtomicBool::new(false);
+        let mut converter = GraphixToHugrConverter::new();
+        let result = converter.convert_with_hook(&pattern, |i| {
+            if i == 3 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            cancel.load(Ordering::Relaxed)
+        });
+
+        assert!(matches!(result, Err(ConversionError::Cancelled { at_command: 3 })));
+        assert_eq!(converter.node_order, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_convert_with_cancel_unset_flag() {
+        let mut pattern = Pattern::new(vec![], vec![0]);
+        pattern.add_command(Command::N { node: 0 });
+
+        let cancel = AtomicBool::new(false);
+        let mut converter = GraphixToHugrConverter::new();
+        assert!(converter.convert_with_cancel(&pattern, &cancel).is_ok());
+
+        let cancel = AtomicBool::new(true);
+        let mut converter = GraphixToHugrConverter::new();
+        assert!(matches!(
+            converter.convert_with_cancel(&pattern, &cancel),
+            Err(ConversionError::Cancelled { at_command: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_convert_moves_hugr_out_of_builder() {
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.3,
+        });
+        pattern.add_command(Command::X {
+            node: 1,
+            domain: [0].into_iter().collect(),
+        });
+
+        let mut converter = GraphixToHugrConverter::new();
+        let hugr = converter.convert(&pattern).unwrap();
+        assert_eq!(converter.dfg.hugr.len(), 1);
+
+        let again = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        assert_eq!(hugr.len(), again.len());
+        for id in 0..hugr.next_node_id {
+            let (a, b) = (&hugr.nodes[&id], &again.nodes[&id]);
+            assert_eq!(format!("{:?}", a.operation), format!("{:?}", b.operation));
+            assert_eq!(a.inputs, b.inputs);
+            assert_eq!(a.outputs, b.outputs);
+            assert_eq!(a.qubit_labels, b.qubit_labels);
+        }
+    }
+
+    #[test]
+    fn test_converter_reuse_and_malformed_patterns_do_not_panic() {
+        let mut converter = GraphixToHugrConverter::new();
+
+        // Entangling nodes that never existed is an error, not a panic
+        let mut bogus = Pattern::new(vec![], vec![]);
+        bogus.add_command(Command::E { nodes: (7, 8) });
+        assert!(matches!(
+            converter.convert(&bogus),
+            Err(ConversionError::NodeNotFound(7))
+        ));
+
+        // As is entangling a live node with itself
+        let mut bogus = Pattern::new(vec![0], vec![0]);
+        bogus.add_command(Command::E { nodes: (0, 0) });
+        assert!(matches!(
+            converter.convert(&bogus),
+            Err(ConversionError::SelfEntanglement(0))
+        ));
+
+        // So is measuring a node that never existed
+        let mut bogus = Pattern::new(vec![], vec![]);
+        bogus.add_command(Command::M {
+            node: 9,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        assert!(matches!(
+            converter.convert(&bogus),
+            Err(ConversionError::NodeNotFound(9))
+        ));
+
+        // Corrections on missing nodes are ignored rather than panicking
+        let mut bogus = Pattern::new(vec![], vec![]);
+        bogus.add_command(Command::X {
+            node: 7,
+            domain: [9].into_iter().collect(),
+        });
+        assert!(converter.convert(&bogus).is_ok());
+
+        // A reused converter gives the same result as a fresh one
+        let pattern = Pattern::ghz(3);
+        let first = converter.convert(&pattern).unwrap();
+        let second = converter.convert(&pattern).unwrap();
+        let fresh = GraphixToHugrConverter::new().convert(&pattern).unwrap();
+        assert_eq!(first.structural_hash(), fresh.structural_hash());
+        assert_eq!(second.structural_hash(), fresh.structural_hash());
+    }
+
+    #[test]
+    fn test_measuring_twice_errors() {
+        let mut pattern = Pattern::new(vec![0], vec![]);
+        for _ in 0..2 {
+            pattern.add_command(Command::M {
+                node: 0,
+                plane: Plane::XY,
+                angle: 0.0,
+            });
+        }
+        assert!(matches!(
+            convert_graphix_pattern_to_hugr(&pattern),
+            Err(ConversionError::AlreadyMeasured(0))
+        ));
+
+        // The qubit is still live after a nondestructive measurement
+        let config = ConverterConfig::default().with_nondestructive_measure(true);
+        assert!(matches!(
+            GraphixToHugrConverter::with_config(config).convert(&pattern),
+            Err(ConversionError::AlreadyMeasured(0))
+        ));
+    }
+
+    #[test]
+    fn test_prepare_requires_no_live_qubit() {
+        // Preparing over an input leaks the input qubit
+        let mut pattern = Pattern::new(vec![0], vec![0]);
+        pattern.add_command(Command::N { node: 0 });
+        assert!(matches!(
+            convert_graphix_pattern_to_hugr(&pattern),
+            Err(ConversionError::QubitAlreadyLive(0))
+        ));
+
+        // Once measured, the node's qubit can be prepared again
+        let mut pattern = Pattern::new(vec![0], vec![0]);
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::N { node: 0 });
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        assert_eq!(hugr.output_types(), Some(&[HugrType::Qubit][..]));
+    }
+
+    #[test]
+    fn test_default_classical_op_is_xor() {
+        assert_eq!(ClassicalOp::default(), ClassicalOp::Xor);
+    }
+
+    #[test]
+    fn test_entangling_input_with_fresh_ancilla_rewires_both() {
+        let mut pattern = Pattern::new(vec![0], vec![0, 1, 2]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::N { node: 2 });
+        pattern.add_command(Command::E { nodes: (2, 0) });
+
+        let mut converter = GraphixToHugrConverter::new();
+        let hugr = converter.convert(&pattern).unwrap();
+        let is_cz = |wire: Wire| {
+            matches!(&hugr.get_node(wire.node_id).unwrap().operation,
+                Operation::Custom { name, .. } if name == "CZ")
+        };
+
+        // The input's wire ends on the second CZ (as its second operand),
+        // which itself consumed the first CZ's output for node 0
+        let input_wire = converter.qubit_wires[&0];
+        assert!(is_cz(input_wire));
+        assert_eq!(input_wire.port, 1);
+        let second_cz = hugr.get_node(input_wire.node_id).unwrap();
+        let first_cz_out = second_cz.inputs[1];
+        assert!(is_cz(first_cz_out));
+        assert_eq!(first_cz_out.port, 0);
+        let first_cz = hugr.get_node(first_cz_out.node_id).unwrap();
+        assert_eq!(first_cz.inputs[0].node_id, hugr.input_node().unwrap().id);
+
+        // Both ancillas also moved onto their CZ outputs
+        assert_eq!(converter.qubit_wires[&1], Wire::new(first_cz_out.node_id, 1));
+        assert_eq!(converter.qubit_wires[&2], Wire::new(second_cz.id, 0));
+        let outputs = vec![input_wire, converter.qubit_wires[&1], converter.qubit_wires[&2]];
+        assert_eq!(hugr.output_node().unwrap().inputs, outputs);
+    }
+}
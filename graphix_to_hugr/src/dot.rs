@@ -0,0 +1,54 @@
+use crate::hugr::{Hugr, HugrType, Operation};
+use std::fmt::Write;
+
+impl Hugr {
+    /// Render the graph in Graphviz DOT format. Qubit edges are solid and
+    /// labelled with the pattern node they carry, `Bool` edges are dashed and
+    /// other classical edges dotted; the root node is drawn bold.
+    pub fn to_dot(&self) -> String {
+        let mut ids: Vec<_> = self.nodes.keys().cloned().collect();
+        ids.sort();
+        
+        let mut dot = String::from("digraph hugr {\n");
+        for id in &ids {
+            let node = &self.nodes[id];
+            let highlight = if *id == self.root { ", style=bold, color=blue" } else { "" };
+            let _ = writeln!(
+                dot,
+                "  n{} [shape=box, label=\"{}\"{}];",
+                id,
+                op_label(&node.operation),
+                highlight
+            );
+        }
+        for id in &ids {
+            for wire in &self.nodes[id].inputs {
+                let label = self
+                    .get_node(wire.node_id)
+                    .and_then(|producer| producer.qubit_label(wire.port))
+                    .map(|q| format!(", label=\"q{}\"", q))
+                    .unwrap_or_default();
+                let style = match self.wire_type(*wire) {
+                    Some(HugrType::Qubit) | None => "solid",
+                    Some(HugrType::Bool) => "dashed",
+                    Some(_) => "dotted",
+                };
+                let _ = writeln!(
+                    dot,
+                    "  n{} -> n{} [taillabel=\"{}\", style={}{}];",
+                    wire.node_id, id, wire.port, style, label
+                );
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn op_label(op: &Operation) -> String {
+    match op {
+        Operation::Custom { name, .. } => format!("Custom \\\"{}\\\"", name),
+        Operation::Const { value } => format!("Const {:?}", value),
+        other => other.name().to_string(),
+    }
+}
@@ -0,0 +1,257 @@
+use crate::converter::{ConversionError, GraphixToHugrConverter};
+use crate::hugr::Hugr;
+use crate::types::{Command, CommandKind, Pattern, Plane};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Invalid open graph JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    
+    #[error("Measurement key {0:?} is not a node id")]
+    InvalidNodeKey(String),
+    
+    #[error("Node {0} is not listed in the open graph's nodes")]
+    UnknownNode(usize),
+}
+
+/// Measurement basis of one node in the open graph JSON
+#[derive(Deserialize)]
+struct OpenGraphMeasurement {
+    plane: Plane,
+    angle: f64,
+}
+
+/// Open graph JSON schema, as written by `Pattern::to_open_graph_json`
+#[derive(Deserialize)]
+struct OpenGraph {
+    nodes: Vec<usize>,
+    edges: Vec<[usize; 2]>,
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+    measurements: BTreeMap<String, OpenGraphMeasurement>,
+}
+
+/// Causal flow of a pattern's open graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flow {
+    /// Flow function: the node that absorbs each measured node's byproducts
+    pub successor: HashMap<usize, usize>,
+    /// Distance from the outputs (outputs are layer 0); higher layers are
+    /// measured first
+    pub layers: HashMap<usize, usize>,
+}
+
+impl Pattern {
+    /// Open graph of the pattern: every node and its neighbours under the
+    /// `E` commands
+    pub(crate) fn open_graph(&self) -> BTreeMap<usize, BTreeSet<usize>> {
+        let mut graph: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+        for &node in self.input_nodes.iter().chain(&self.output_nodes) {
+            graph.entry(node).or_default();
+        }
+        for cmd in self.commands_of_kind(CommandKind::N) {
+            graph.entry(cmd.nodes()[0]).or_default();
+        }
+        for (a, b) in self.entanglements() {
+            graph.entry(a).or_default().insert(b);
+            graph.entry(b).or_default().insert(a);
+        }
+        graph
+    }
+    
+    /// Open graph as JSON for graph tools: node list, `[a, b]` edges with
+    /// `a < b`, inputs, outputs and each measured node's plane and angle
+    pub fn to_open_graph_json(&self) -> String {
+        let graph = self.open_graph();
+        let edges: Vec<[usize; 2]> = graph
+            .iter()
+            .flat_map(|(&a, neighbours)| neighbours.range(a + 1..).map(move |&b| [a, b]))
+            .collect();
+        let measurements: BTreeMap<String, Value> = self
+            .measured_nodes()
+            .into_iter()
+            .filter_map(|node| self.measurement_of(node).map(|m| (node, m)))
+            .map(|(node, (plane, angle))| {
+                (node.to_string(), json!({ "plane": plane, "angle": angle }))
+            })
+            .collect();
+        
+        let open_graph = json!({
+            "nodes": graph.keys().collect::<Vec<_>>(),
+            "edges": edges,
+            "inputs": self.input_nodes,
+            "outputs": self.output_nodes,
+            "measurements": measurements,
+        });
+        serde_json::to_string_pretty(&open_graph).expect("open graph JSON is always serializable")
+    }
+    
+    /// Pattern for an open graph in the `to_open_graph_json` schema: `N` for
+    /// every non-input node, then `E` for every edge, then `M` for every
+    /// measured node, in causal flow order when the graph has a flow and in
+    /// node order otherwise
+    pub fn from_open_graph_json(json: &str) -> Result<Pattern, ImportError> {
+        let graph: OpenGraph = serde_json::from_str(json)?;
+        let nodes: BTreeSet<usize> = graph.nodes.iter().cloned().collect();
+        let mut measurements = BTreeMap::new();
+        for (key, measurement) in graph.measurements {
+            let node = key
+                .parse::<usize>()
+                .map_err(|_| ImportError::InvalidNodeKey(key.clone()))?;
+            measurements.insert(node, measurement);
+        }
+        if let Some(&node) = graph
+            .edges
+            .iter()
+            .flatten()
+            .chain(&graph.inputs)
+            .chain(&graph.outputs)
+            .chain(measurements.keys())
+            .find(|node| !nodes.contains(node))
+        {
+            return Err(ImportError::UnknownNode(node));
+        }
+        
+        let mut pattern = Pattern::new(graph.inputs.clone(), graph.outputs);
+        for &node in nodes.iter().filter(|node| !graph.inputs.contains(node)) {
+            pattern.add_command(Command::N { node });
+        }
+        for [a, b] in graph.edges {
+            pattern.add_command(Command::E { nodes: (a, b) });
+        }
+        
+        let mut order: Vec<usize> = measurements.keys().cloned().collect();
+        if let Some(flow) = pattern.causal_flow() {
+            order.sort_by_key(|node| {
+                (std::cmp::Reverse(flow.layers.get(node).copied().unwrap_or(0)), *node)
+            });
+        }
+        for node in order {
+            let OpenGraphMeasurement { plane, angle } = measurements[&node];
+            pattern.add_command(Command::M { node, plane, angle });
+        }
+        Ok(pattern)
+    }
+    
+    /// Causal flow of the open graph (Mhalla–Perdrix), or `None` if it has
+    /// none
+    pub fn causal_flow(&self) -> Option<Flow> {
+        let graph = self.open_graph();
+        let inputs: BTreeSet<usize> = self.input_nodes.iter().cloned().collect();
+        let mut processed: BTreeSet<usize> = self.output_nodes.iter().cloned().collect();
+        let mut correctors: BTreeSet<usize> = processed.difference(&inputs).cloned().collect();
+        let mut flow = Flow {
+            successor: HashMap::new(),
+            layers: processed.iter().map(|&node| (node, 0)).collect(),
+        };
+        
+        for layer in 1.. {
+            let mut newly_processed = BTreeSet::new();
+            let mut used = BTreeSet::new();
+            for &corrector in &correctors {
+                let mut unprocessed = graph[&corrector].difference(&processed);
+                if let (Some(&node), None) = (unprocessed.next(), unprocessed.next()) {
+                    if newly_processed.insert(node) {
+                        flow.successor.insert(node, corrector);
+                        flow.layers.insert(node, layer);
+                        used.insert(corrector);
+                    }
+                }
+            }
+            
+            if newly_processed.is_empty() {
+                break;
+            }
+            processed.extend(newly_processed.iter().cloned());
+            correctors = correctors
+                .difference(&used)
+                .cloned()
+                .chain(newly_processed.difference(&inputs).cloned())
+                .collect();
+        }
+        
+        (processed.len() == graph.len()).then_some(flow)
+    }
+    
+    /// Whether the pattern is deterministic: its open graph has a causal flow
+    /// and every correction only depends on outcomes that are already known
+    /// (measured earlier, or classical inputs)
+    pub fn is_deterministic(&self) -> bool {
+        if self.causal_flow().is_none() {
+            return false;
+        }
+        
+        let mut known: HashSet<usize> = self.classical_input_nodes.iter().cloned().collect();
+        for cmd in self.iter() {
+            if cmd.domain().is_some_and(|domain| !domain.is_subset(&known)) {
+                return false;
+            }
+            if let Some(node) = cmd.measured_node() {
+                known.insert(node);
+            }
+        }
+        true
+    }
+    
+    /// Runnable pattern built from the flow: the `N`/`E` commands followed by
+    /// each measurement (in flow order) and the `X`/`Z` corrections it
+    /// induces. The pattern must contain only `N`, `E` and `XY`-plane `M`
+    /// commands.
+    pub fn with_flow_corrections(&self) -> Result<Pattern, ConversionError> {
+        let mut angles = HashMap::new();
+        for cmd in self.iter() {
+            match cmd {
+                Command::N { .. } | Command::E { .. } => {}
+                Command::M {
+                    node,
+                    plane: Plane::XY,
+                    angle,
+                } => {
+                    angles.insert(*node, *angle);
+                }
+                other => return Err(ConversionError::NotAnOpenGraph(other.kind())),
+            }
+        }
+        
+        let flow = self.causal_flow().ok_or(ConversionError::NoFlow)?;
+        let graph = self.open_graph();
+        let mut order: Vec<usize> = flow.successor.keys().cloned().collect();
+        order.sort_by_key(|node| (std::cmp::Reverse(flow.layers[node]), *node));
+        
+        let mut runnable = self.clone();
+        runnable
+            .commands
+            .retain(|cmd| matches!(cmd.kind(), CommandKind::N | CommandKind::E));
+        for node in order {
+            let angle = *angles.get(&node).ok_or(ConversionError::NoFlow)?;
+            let successor = flow.successor[&node];
+            runnable.add_command(Command::M {
+                node,
+                plane: Plane::XY,
+                angle,
+            });
+            runnable.add_command(Command::X {
+                node: successor,
+                domain: [node].into_iter().collect(),
+            });
+            for &neighbour in graph[&successor].iter().filter(|&&n| n != node) {
+                runnable.add_command(Command::Z {
+                    node: neighbour,
+                    domain: [node].into_iter().collect(),
+                });
+            }
+        }
+        Ok(runnable)
+    }
+}
+
+/// Convert a correction-free pattern, synthesizing its corrections from its
+/// causal flow
+pub fn convert_with_flow(pattern: &Pattern) -> Result<Hugr, ConversionError> {
+    let runnable = pattern.with_flow_corrections()?;
+    GraphixToHugrConverter::new().convert(&runnable)
+}
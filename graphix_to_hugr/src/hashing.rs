@@ -0,0 +1,325 @@
+use crate::hugr::{Hugr, Operation};
+use crate::types::{Angle, Command, Complex, Pattern};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+impl Hugr {
+    /// Hash of the graph structure, invariant under node-id renumbering.
+    ///
+    /// Each node is labelled by its operation and the labels and ports of the
+    /// nodes feeding it, so isomorphic HUGRs hash equal.
+    pub fn structural_hash(&self) -> u64 {
+        let mut all: Vec<u64> = self.node_labels().into_values().collect();
+        all.sort();
+        let mut hasher = DefaultHasher::new();
+        all.hash(&mut hasher);
+        hasher.finish()
+    }
+    
+    /// Whether `other` is the same graph up to node renumbering.
+    ///
+    /// Nodes are matched in id order against nodes of `other` with the same
+    /// structural label, backtracking when their inputs disagree.
+    pub fn is_isomorphic(&self, other: &Hugr) -> bool {
+        if self.nodes.len() != other.nodes.len() {
+            return false;
+        }
+        let (labels, other_labels) = (self.node_labels(), other.node_labels());
+        let mut by_label: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (&id, &label) in &other_labels {
+            by_label.entry(label).or_default().push(id);
+        }
+        for ids in by_label.values_mut() {
+            ids.sort();
+        }
+        
+        let mut order: Vec<usize> = self.nodes.keys().cloned().collect();
+        order.sort();
+        if order.is_empty() {
+            return true;
+        }
+        let mut mapping: HashMap<usize, usize> = HashMap::with_capacity(order.len());
+        let mut used: HashSet<usize> = HashSet::with_capacity(order.len());
+        let candidates = |id: usize, mapping: &HashMap<usize, usize>, used: &HashSet<usize>| {
+            by_label
+                .get(&labels[&id])
+                .into_iter()
+                .flatten()
+                .cloned()
+                .filter(|c| !used.contains(c) && self.node_matches(id, other, *c, mapping))
+                .collect::<Vec<_>>()
+        };
+        
+        // Explicit stack of (candidates, next candidate) per matched depth
+        let mut stack = vec![(candidates(order[0], &mapping, &used), 0)];
+        while let Some(depth) = stack.len().checked_sub(1) {
+            if let Some(previous) = mapping.remove(&order[depth]) {
+                used.remove(&previous);
+            }
+            let (cands, next) = &mut stack[depth];
+            let Some(&candidate) = cands.get(*next) else {
+                stack.pop();
+                continue;
+            };
+            *next += 1;
+            mapping.insert(order[depth], candidate);
+            used.insert(candidate);
+            
+            if depth + 1 == order.len() {
+                if order.iter().all(|&id| self.node_matches(id, other, mapping[&id], &mapping)) {
+                    return true;
+                }
+                continue;
+            }
+            stack.push((candidates(order[depth + 1], &mapping, &used), 0));
+        }
+        false
+    }
+    
+    /// Whether `id` can map to `other`'s node `candidate`: same operation and
+    /// every already-mapped input agrees
+    fn node_matches(&self, id: usize, other: &Hugr, candidate: usize, mapping: &HashMap<usize, usize>) -> bool {
+        let (node, theirs) = (&self.nodes[&id], &other.nodes[&candidate]);
+        if node.inputs.len() != theirs.inputs.len() {
+            return false;
+        }
+        let inputs_agree = node.inputs.iter().zip(&theirs.inputs).all(|(mine, theirs)| {
+            mine.port == theirs.port
+                && mapping.get(&mine.node_id).is_none_or(|&mapped| mapped == theirs.node_id)
+        });
+        let op_agrees = match (&node.operation, &theirs.operation) {
+            (Operation::LoadConst { const_node: a }, Operation::LoadConst { const_node: b }) => {
+                mapping.get(a).is_none_or(|mapped| mapped == b)
+            }
+            (Operation::LoadConst { .. }, _) | (_, Operation::LoadConst { .. }) => false,
+            (a, b) => format!("{:?}", a) == format!("{:?}", b),
+        };
+        inputs_agree && op_agrees
+    }
+    
+    /// Structural label of every node: its operation and the labels and
+    /// ports of the nodes feeding it
+    fn node_labels(&self) -> HashMap<usize, u64> {
+        let mut labels: HashMap<usize, u64> = HashMap::with_capacity(self.nodes.len());
+        
+        let mut ids: Vec<_> = self.nodes.keys().cloned().collect();
+        ids.sort();
+        for &root in &ids {
+            // Iterative post-order so long chains don't exhaust the stack
+            let mut stack = vec![(root, false)];
+            while let Some((id, expanded)) = stack.pop() {
+                if labels.contains_key(&id) {
+                    continue;
+                }
+                let Some(node) = self.get_node(id) else {
+                    continue;
+                };
+                if expanded {
+                    let label = self.node_label(id, &labels);
+                    labels.insert(id, label);
+                } else {
+                    stack.push((id, true));
+                    for dep in self.dependencies(id) {
+                        if !labels.contains_key(&dep) && dep != node.id {
+                            stack.push((dep, false));
+                        }
+                    }
+                }
+            }
+        }
+        labels
+    }
+    
+    fn dependencies(&self, id: usize) -> Vec<usize> {
+        let node = &self.nodes[&id];
+        let mut deps: Vec<usize> = node.inputs.iter().map(|w| w.node_id).collect();
+        if let Operation::LoadConst { const_node } = &node.operation {
+            deps.push(*const_node);
+        }
+        deps
+    }
+    
+    fn node_label(&self, id: usize, labels: &HashMap<usize, u64>) -> u64 {
+        let node = &self.nodes[&id];
+        let mut hasher = DefaultHasher::new();
+        match &node.operation {
+            Operation::LoadConst { const_node } => {
+                "LoadConst".hash(&mut hasher);
+                labels.get(const_node).hash(&mut hasher);
+            }
+            op => format!("{:?}", op).hash(&mut hasher),
+        }
+        for wire in &node.inputs {
+            labels.get(&wire.node_id).hash(&mut hasher);
+            wire.port.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+impl Pattern {
+    /// Content hash of the pattern, suitable as a conversion cache key
+    pub fn hash_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Floats are compared and hashed by bit pattern, so `0.0` and `-0.0` are
+/// different commands while a NaN angle equals itself.
+impl PartialEq for Command {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Command::N { node: a }, Command::N { node: b })
+            | (Command::MZ { node: a }, Command::MZ { node: b }) => a == b,
+            (Command::E { nodes: a }, Command::E { nodes: b }) => a == b,
+            (
+                Command::M { node: n1, plane: p1, angle: a1 },
+                Command::M { node: n2, plane: p2, angle: a2 },
+            )
+            | (
+                Command::MSoft { node: n1, plane: p1, angle: a1 },
+                Command::MSoft { node: n2, plane: p2, angle: a2 },
+            ) => n1 == n2 && p1 == p2 && a1.to_bits() == a2.to_bits(),
+            (
+                Command::MAdaptive { node: n1, plane: p1, angle: a1 },
+                Command::MAdaptive { node: n2, plane: p2, angle: a2 },
+            ) => n1 == n2 && p1 == p2 && angle_key(a1) == angle_key(a2),
+            (Command::X { node: n1, domain: d1 }, Command::X { node: n2, domain: d2 })
+            | (Command::Z { node: n1, domain: d1 }, Command::Z { node: n2, domain: d2 }) => {
+                n1 == n2 && d1 == d2
+            }
+            (
+                Command::XControlled { node: n1, controls: d1, combiner: o1 },
+                Command::XControlled { node: n2, controls: d2, combiner: o2 },
+            ) => n1 == n2 && d1 == d2 && o1 == o2,
+            (Command::C { node: n1, clifford: c1 }, Command::C { node: n2, clifford: c2 }) => {
+                n1 == n2 && c1 == c2
+            }
+            (
+                Command::Gate { node: n1, name: s1, matrix: m1, params: p1 },
+                Command::Gate { node: n2, name: s2, matrix: m2, params: p2 },
+            ) => {
+                n1 == n2
+                    && s1 == s2
+                    && m1.map(matrix_bits) == m2.map(matrix_bits)
+                    && p1.iter().map(|p| p.to_bits()).eq(p2.iter().map(|p| p.to_bits()))
+            }
+            (
+                Command::Branch { domain: d1, if_true: t1, if_false: f1 },
+                Command::Branch { domain: d2, if_true: t2, if_false: f2 },
+            ) => d1 == d2 && t1 == t2 && f1 == f2,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Command {}
+
+impl Hash for Command {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.kind().hash(hasher);
+        match self {
+            Command::N { node } | Command::MZ { node } => node.hash(hasher),
+            Command::E { nodes } => nodes.hash(hasher),
+            Command::M { node, plane, angle } | Command::MSoft { node, plane, angle } => {
+                node.hash(hasher);
+                plane.hash(hasher);
+                angle.to_bits().hash(hasher);
+            }
+            Command::MAdaptive { node, plane, angle } => {
+                node.hash(hasher);
+                plane.hash(hasher);
+                angle_key(angle).hash(hasher);
+            }
+            Command::X { node, domain } | Command::Z { node, domain } => {
+                node.hash(hasher);
+                let mut domain: Vec<_> = domain.iter().collect();
+                domain.sort();
+                domain.hash(hasher);
+            }
+            Command::XControlled {
+                node,
+                controls,
+                combiner,
+            } => {
+                node.hash(hasher);
+                let mut controls: Vec<_> = controls.iter().collect();
+                controls.sort();
+                controls.hash(hasher);
+                combiner.hash(hasher);
+            }
+            Command::C { node, clifford } => {
+                node.hash(hasher);
+                clifford.hash(hasher);
+            }
+            Command::Gate {
+                node,
+                name,
+                matrix,
+                params,
+            } => {
+                node.hash(hasher);
+                name.hash(hasher);
+                matrix.map(matrix_bits).hash(hasher);
+                for p in params {
+                    p.to_bits().hash(hasher);
+                }
+            }
+            Command::Branch {
+                domain,
+                if_true,
+                if_false,
+            } => {
+                let mut domain: Vec<_> = domain.iter().collect();
+                domain.sort();
+                domain.hash(hasher);
+                if_true.hash(hasher);
+                if_false.hash(hasher);
+            }
+        }
+    }
+}
+
+/// Input states and angle parameters are hashed in node order, so equal
+/// patterns hash equal regardless of map iteration order.
+impl Hash for Pattern {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.input_nodes.hash(hasher);
+        self.output_nodes.hash(hasher);
+        
+        let mut states: Vec<_> = self.input_states.iter().collect();
+        states.sort_by_key(|(node, _)| **node);
+        states.hash(hasher);
+        
+        self.commands.hash(hasher);
+        self.classical_output_nodes.hash(hasher);
+        self.classical_input_nodes.hash(hasher);
+        
+        let mut parameters: Vec<_> = self.angle_parameters.iter().collect();
+        parameters.sort_by_key(|(node, _)| **node);
+        parameters.hash(hasher);
+    }
+}
+
+/// Bit patterns of an angle's floats, with the shift domain sorted
+fn angle_key(angle: &Angle) -> (u64, Option<(Vec<usize>, u64)>) {
+    match angle {
+        Angle::Fixed(angle) => (angle.to_bits(), None),
+        Angle::Computed {
+            base,
+            shift_domain,
+            shift,
+        } => {
+            let mut domain: Vec<usize> = shift_domain.iter().cloned().collect();
+            domain.sort();
+            (base.to_bits(), Some((domain, shift.to_bits())))
+        }
+    }
+}
+
+fn matrix_bits(matrix: [[Complex; 2]; 2]) -> [[(u64, u64); 2]; 2] {
+    matrix.map(|row| row.map(|entry| (entry.re.to_bits(), entry.im.to_bits())))
+}
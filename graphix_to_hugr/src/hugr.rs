@@ -1,5 +1,8 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use crate::converter::{ConversionError, LOGIC_EXTENSION};
+use crate::registry::ExtensionRegistry;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use thiserror::Error;
 
 /// HUGR wire handle - represents a dataflow wire
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -20,10 +23,21 @@ pub enum HugrType {
     Qubit,
     Bool,
     Float64,
+    /// Unsigned integer of the given bit width
+    Int(usize),
+    /// Tagged union; each variant is a row of types
+    Sum(Vec<Vec<HugrType>>),
+}
+
+impl HugrType {
+    /// Sum of `n` empty variants, the predicate type of an `n`-way branch
+    pub fn unit_sum(n: usize) -> Self {
+        HugrType::Sum(vec![vec![]; n])
+    }
 }
 
 /// Function signature type
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FunctionType {
     pub inputs: Vec<HugrType>,
     pub outputs: Vec<HugrType>,
@@ -70,6 +84,33 @@ pub enum Operation {
     DFG {
         signature: FunctionType,
     },
+    
+    /// Branch on a `Sum` predicate (the first input); its children are one
+    /// `Case` per variant, in tag order
+    Conditional {
+        signature: FunctionType,
+    },
+    
+    /// One branch of a `Conditional`, taking the conditional's other inputs
+    Case {
+        signature: FunctionType,
+    },
+}
+
+impl Operation {
+    /// Canonical name: the op name for `Custom`, the variant name otherwise
+    pub fn name(&self) -> &str {
+        match self {
+            Operation::Input { .. } => "Input",
+            Operation::Output { .. } => "Output",
+            Operation::Custom { name, .. } => name,
+            Operation::Const { .. } => "Const",
+            Operation::LoadConst { .. } => "LoadConst",
+            Operation::DFG { .. } => "DFG",
+            Operation::Conditional { .. } => "Conditional",
+            Operation::Case { .. } => "Case",
+        }
+    }
 }
 
 /// Constant values
@@ -86,6 +127,15 @@ pub struct Node {
     pub operation: Operation,
     pub inputs: Vec<Wire>,
     pub outputs: Vec<Wire>,
+    /// Pattern node carried by each labelled output port
+    #[serde(default, serialize_with = "serialize_sorted_map")]
+    pub qubit_labels: HashMap<usize, usize>,
+    /// Free-form annotations, e.g. the pattern command that produced the node
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Enclosing region node; `None` for nodes placed directly in the root
+    #[serde(default)]
+    pub parent: Option<usize>,
 }
 
 impl Node {
@@ -95,17 +145,59 @@ impl Node {
             operation,
             inputs: Vec::new(),
             outputs: Vec::new(),
+            qubit_labels: HashMap::new(),
+            metadata: BTreeMap::new(),
+            parent: None,
         }
     }
     
     pub fn out(&self, port: usize) -> Wire {
         Wire::new(self.id, port)
     }
+    
+    /// Pattern node carried by output `port`, if known
+    pub fn qubit_label(&self, port: usize) -> Option<usize> {
+        self.qubit_labels.get(&port).copied()
+    }
+    
+    pub fn set_meta(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.metadata.insert(key.into(), value.into());
+    }
+    
+    pub fn get_meta(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(String::as_str)
+    }
+}
+
+/// Why `Hugr::from_json_string` rejected a document
+#[derive(Error, Debug)]
+pub enum HugrParseError {
+    #[error("Invalid HUGR JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    
+    #[error("Root {0} is not a node of the HUGR")]
+    MissingRoot(usize),
+    
+    #[error("Node {node} reads from node {source_node}, which does not exist")]
+    DanglingWire { node: usize, source_node: usize },
+    
+    #[error("Node id {node} is not below next_node_id {next_node_id}")]
+    NodeIdOutOfRange { node: usize, next_node_id: usize },
+}
+
+/// Serialize a map in ascending key order so output is deterministic
+fn serialize_sorted_map<S: Serializer, V: Serialize>(
+    map: &HashMap<usize, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let sorted: BTreeMap<&usize, &V> = map.iter().collect();
+    sorted.serialize(serializer)
 }
 
 /// HUGR graph representation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Hugr {
+    #[serde(serialize_with = "serialize_sorted_map")]
     pub nodes: HashMap<usize, Node>,
     pub next_node_id: usize,
     pub root: usize,
@@ -113,8 +205,13 @@ pub struct Hugr {
 
 impl Hugr {
     pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+    
+    /// Empty HUGR with room for `capacity` nodes
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            nodes: HashMap::new(),
+            nodes: HashMap::with_capacity(capacity),
             next_node_id: 0,
             root: 0,
         }
@@ -138,9 +235,246 @@ impl Hugr {
         self.nodes.get_mut(&id)
     }
     
+    pub fn input_node(&self) -> Option<&Node> {
+        self.find_node(|op| matches!(op, Operation::Input { .. }))
+    }
+    
+    pub fn output_node(&self) -> Option<&Node> {
+        self.find_node(|op| matches!(op, Operation::Output { .. }))
+    }
+    
+    pub fn input_node_mut(&mut self) -> Option<&mut Node> {
+        let id = self.input_node()?.id;
+        self.get_node_mut(id)
+    }
+    
+    pub fn output_node_mut(&mut self) -> Option<&mut Node> {
+        let id = self.output_node()?.id;
+        self.get_node_mut(id)
+    }
+    
+    /// Types declared by the Input node
+    pub fn input_types(&self) -> Option<&[HugrType]> {
+        match &self.input_node()?.operation {
+            Operation::Input { types } => Some(types),
+            _ => None,
+        }
+    }
+    
+    /// Types declared by the Output node
+    pub fn output_types(&self) -> Option<&[HugrType]> {
+        match &self.output_node()?.operation {
+            Operation::Output { types } => Some(types),
+            _ => None,
+        }
+    }
+    
+    /// Ids of the nodes whose parent is `parent`, in ascending order
+    pub fn children(&self, parent: usize) -> Vec<usize> {
+        let mut ids: Vec<usize> = self
+            .nodes
+            .values()
+            .filter(|node| node.parent == Some(parent))
+            .map(|node| node.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+    
+    /// Copy with every `DFG` region inlined: its contents move to the
+    /// region's own parent and the region, with its `Input`/`Output` nodes,
+    /// is removed. Wires through region ports are reconnected to the ops
+    /// producing them, so passes that walk the graph in id order see the
+    /// same dataflow as for a flat conversion.
+    pub fn flatten_regions(&self) -> Hugr {
+        let regions: HashSet<usize> = self
+            .nodes
+            .values()
+            .filter(|node| matches!(node.operation, Operation::DFG { .. }))
+            .map(|node| node.id)
+            .collect();
+        let in_region = |node: &Node| node.parent.is_some_and(|p| regions.contains(&p));
+        let is_boundary = |node: &Node| {
+            in_region(node) && matches!(node.operation, Operation::Input { .. } | Operation::Output { .. })
+        };
+        let region_output: HashMap<usize, &Node> = self
+            .nodes
+            .values()
+            .filter(|node| is_boundary(node) && matches!(node.operation, Operation::Output { .. }))
+            .map(|node| (node.parent.unwrap_or_default(), node))
+            .collect();
+        
+        // Follow a wire out of region inputs and region outputs to its producer
+        let source = |mut wire: Wire| loop {
+            let Some(node) = self.get_node(wire.node_id) else {
+                break wire;
+            };
+            let through = match &node.operation {
+                Operation::Input { .. } if in_region(node) => node
+                    .parent
+                    .and_then(|region| self.nodes[&region].inputs.get(wire.port)),
+                Operation::DFG { .. } => region_output
+                    .get(&node.id)
+                    .and_then(|output| output.inputs.get(wire.port)),
+                _ => None,
+            };
+            match through {
+                Some(&next) => wire = next,
+                None => break wire,
+            }
+        };
+        // Nearest enclosing node that is not a region, `None` for the root
+        let enclosing = |mut parent: Option<usize>| {
+            while let Some(p) = parent.filter(|p| regions.contains(p)) {
+                parent = self.nodes[&p].parent;
+            }
+            parent.filter(|&p| p != self.root)
+        };
+        
+        let mut flat = self.clone();
+        flat.nodes.retain(|id, node| !regions.contains(id) && !is_boundary(node));
+        for node in flat.nodes.values_mut() {
+            for wire in &mut node.inputs {
+                *wire = source(*wire);
+            }
+            if in_region(node) {
+                node.parent = enclosing(node.parent);
+            }
+        }
+        flat
+    }
+    
+    /// Give the symbolic angle `name` the value `value` in every rotation
+    /// tagged with it (see `Pattern::angle_parameters`), dropping the tag.
+    /// Returns the number of rotations bound.
+    pub fn bind_parameter(&mut self, name: &str, value: f64) -> usize {
+        let mut bound = 0;
+        for node in self.nodes.values_mut() {
+            let angle = match node.get_meta("parameter") {
+                Some(tag) if tag == name => value,
+                Some(tag) if tag.strip_prefix('-') == Some(name) => -value,
+                _ => continue,
+            };
+            if let Operation::Custom { args, .. } = &mut node.operation {
+                args[0] = angle;
+                node.metadata.remove("parameter");
+                bound += 1;
+            }
+        }
+        bound
+    }
+    
+    /// Type carried by `wire`, as declared by its producer
+    pub fn wire_type(&self, wire: Wire) -> Option<HugrType> {
+        match &self.get_node(wire.node_id)?.operation {
+            Operation::Input { types } => types.get(wire.port).cloned(),
+            Operation::Custom { signature, .. }
+            | Operation::Conditional { signature }
+            | Operation::DFG { signature } => signature.outputs.get(wire.port).cloned(),
+            Operation::LoadConst { const_node } => match &self.get_node(*const_node)?.operation {
+                Operation::Const {
+                    value: ConstValue::Bool(_),
+                } => Some(HugrType::Bool),
+                Operation::Const {
+                    value: ConstValue::Float(_),
+                } => Some(HugrType::Float64),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    
+    /// Lowest-id top-level node whose operation matches `pred`; the boundary
+    /// nodes of nested regions are skipped
+    fn find_node(&self, pred: impl Fn(&Operation) -> bool) -> Option<&Node> {
+        self.nodes
+            .values()
+            .filter(|node| node.parent.is_none() && pred(&node.operation))
+            .min_by_key(|node| node.id)
+    }
+    
+    /// Serde form as compact JSON, with nodes in ascending id order
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+    
+    /// Serde form as indented JSON, with nodes in ascending id order
+    pub fn to_json_string_pretty(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+    
+    /// Read a HUGR written by `to_json_string` or `to_json_string_pretty`,
+    /// checking that the root exists, every input wire comes from an
+    /// existing node and every node id is below `next_node_id`
+    pub fn from_json_string(json: &str) -> Result<Hugr, HugrParseError> {
+        let hugr: Hugr = serde_json::from_str(json)?;
+        if !hugr.nodes.contains_key(&hugr.root) {
+            return Err(HugrParseError::MissingRoot(hugr.root));
+        }
+        
+        let mut ids: Vec<usize> = hugr.nodes.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            if id >= hugr.next_node_id {
+                return Err(HugrParseError::NodeIdOutOfRange {
+                    node: id,
+                    next_node_id: hugr.next_node_id,
+                });
+            }
+            if let Some(wire) = hugr.nodes[&id].inputs.iter().find(|w| !hugr.nodes.contains_key(&w.node_id)) {
+                return Err(HugrParseError::DanglingWire {
+                    node: id,
+                    source_node: wire.node_id,
+                });
+            }
+        }
+        Ok(hugr)
+    }
+    
     pub fn len(&self) -> usize {
         self.nodes.len()
     }
+    
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+    
+    /// Check that every custom operation is registered with a matching signature
+    pub fn validate_against_registry(&self, reg: &ExtensionRegistry) -> Result<(), ConversionError> {
+        let mut ids: Vec<_> = self.nodes.keys().cloned().collect();
+        ids.sort();
+        
+        for id in ids {
+            if let Operation::Custom {
+                name,
+                signature,
+                extension,
+                ..
+            } = &self.nodes[&id].operation
+            {
+                if reg.is_variadic(extension, name) {
+                    continue;
+                }
+                match reg.get(extension, name) {
+                    None => {
+                        return Err(ConversionError::UnregisteredOperation {
+                            extension: extension.clone(),
+                            name: name.clone(),
+                        })
+                    }
+                    Some(expected) if expected != signature => {
+                        return Err(ConversionError::SignatureMismatch {
+                            extension: extension.clone(),
+                            name: name.clone(),
+                        })
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        
+        Ok(())
+    }
 }
 
 impl Default for Hugr {
@@ -150,6 +484,7 @@ impl Default for Hugr {
 }
 
 /// Dataflow graph builder
+#[derive(Debug, Clone)]
 pub struct DfgBuilder {
     pub hugr: Hugr,
     pub input_node_id: usize,
@@ -159,7 +494,12 @@ pub struct DfgBuilder {
 
 impl DfgBuilder {
     pub fn new(input_types: Vec<HugrType>) -> Self {
-        let mut hugr = Hugr::new();
+        Self::with_capacity(input_types, 0)
+    }
+    
+    /// Builder whose HUGR has room for `capacity` nodes
+    pub fn with_capacity(input_types: Vec<HugrType>, capacity: usize) -> Self {
+        let mut hugr = Hugr::with_capacity(capacity);
         
         // Create input node
         let input_op = Operation::Input {
@@ -188,7 +528,9 @@ impl DfgBuilder {
             
             // Determine number of outputs based on operation
             let num_outputs = match &node.operation {
-                Operation::Custom { signature, .. } => signature.outputs.len(),
+                Operation::Custom { signature, .. } | Operation::Conditional { signature } => {
+                    signature.outputs.len()
+                }
                 Operation::LoadConst { .. } => 1,
                 _ => 0,
             };
@@ -201,6 +543,24 @@ impl DfgBuilder {
         self.hugr.get_node(node_id).unwrap()
     }
     
+    /// Add an operation and return its output wires, for callers that don't
+    /// need the node itself
+    pub fn add_op_wires(&mut self, operation: Operation, inputs: Vec<Wire>) -> Vec<Wire> {
+        self.add_op(operation, inputs).outputs.clone()
+    }
+    
+    /// Convert a `Bool` wire into a two-variant unit sum (`false` is tag 0,
+    /// `true` tag 1), the predicate of a binary conditional
+    pub fn bool_to_sum(&mut self, condition: Wire) -> Wire {
+        let op = Operation::Custom {
+            name: "BoolToSum".to_string(),
+            signature: FunctionType::new(vec![HugrType::Bool], vec![HugrType::unit_sum(2)]),
+            extension: LOGIC_EXTENSION.to_string(),
+            args: vec![],
+        };
+        self.add_op_wires(op, vec![condition])[0]
+    }
+    
     pub fn add_const(&mut self, value: ConstValue) -> usize {
         let const_op = Operation::Const { value };
         self.hugr.add_node(const_op)
@@ -214,11 +574,30 @@ impl DfgBuilder {
         node.out(0)
     }
     
-    pub fn set_outputs(&mut self, outputs: Vec<Wire>) {
-        let output_types: Vec<HugrType> = outputs
+    /// Type carried by `wire`, as declared by its producer: a builder input,
+    /// an `add_op` output (from the op's signature) or a loaded constant
+    pub fn wire_type(&self, wire: Wire) -> Option<HugrType> {
+        self.hugr.wire_type(wire)
+    }
+    
+    /// Consume the builder, returning the built HUGR
+    pub fn finish(self) -> Hugr {
+        self.hugr
+    }
+    
+    /// Add the output node. Each output's type is taken from the wire's
+    /// producer; a wire whose type cannot be resolved (unknown node or port)
+    /// is an error rather than being assumed to carry a qubit.
+    pub fn set_outputs(&mut self, outputs: Vec<Wire>) -> Result<(), ConversionError> {
+        let output_types = outputs
             .iter()
-            .map(|_| HugrType::Qubit) // Simplified - would need proper type tracking
-            .collect();
+            .map(|&wire| {
+                self.wire_type(wire).ok_or(ConversionError::UntypedWire {
+                    node: wire.node_id,
+                    port: wire.port,
+                })
+            })
+            .collect::<Result<Vec<HugrType>, _>>()?;
         
         let output_op = Operation::Output { types: output_types };
         let output_node_id = self.hugr.add_node(output_op);
@@ -228,5 +607,11 @@ impl DfgBuilder {
         }
         
         self.output_node_id = Some(output_node_id);
+        Ok(())
+    }
+}
+impl Default for DfgBuilder {
+    fn default() -> Self {
+        Self::new(vec![])
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,29 @@
+use crate::types::Pattern;
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PatternIoError {
+    #[error("Cannot access pattern file: {0}")]
+    Io(#[from] std::io::Error),
+    
+    #[error("Invalid pattern JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl Pattern {
+    /// Write the pattern to `path` as indented JSON
+    pub fn save_to_path(&self, path: impl AsRef<Path>) -> Result<(), PatternIoError> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n")?;
+        Ok(())
+    }
+    
+    /// Read a pattern saved with `save_to_path`. Node indices must be
+    /// non-negative integers; anything else is a `Json` error.
+    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Pattern, PatternIoError> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
@@ -1,15 +1,67 @@
+pub mod angle;
+pub mod classical;
+pub mod circuit;
+pub mod compose;
+pub mod config;
 pub mod converter;
+pub mod dot;
+pub mod flow;
+pub mod hashing;
 pub mod hugr;
+pub mod io;
+pub mod lint;
+pub mod mermaid;
+pub mod order;
+pub mod parser;
+pub mod qasm;
+pub mod registry;
+pub mod schema;
+pub mod sim;
+pub mod spec;
+pub mod stabilizer;
+pub mod stats;
+/// Structural assertions on converted HUGRs, for tests
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil;
 pub mod types;
+#[cfg(feature = "yaml")]
+pub mod yaml;
 
-pub use converter::{convert_graphix_pattern_to_hugr, ConversionError, GraphixToHugrConverter};
-pub use hugr::{ConstValue, DfgBuilder, FunctionType, Hugr, HugrType, Node, Operation, Wire};
-pub use types::{CliffordGate, Command, CommandKind, Pattern, Plane};
+pub use angle::PiFraction;
+pub use classical::{correction_truth_table, extract_classical_subgraph, TruthTable};
+pub use circuit::{
+    to_classical_register_circuit, to_gate_list, GateInstr, RegisterCircuit, RegisterCircuitError,
+    RegisterCondition, RegisterInstr,
+};
+pub use compose::RepeatError;
+pub use config::{ConverterConfig, EdgeKind};
+pub use converter::{
+    convert_graphix_pattern_to_hugr, split_resource_and_consumption, ClassicalOp,
+    ConversionError, ConversionWarning, GraphixToHugrConverter, OutputByproduct,
+};
+pub use flow::{convert_with_flow, Flow, ImportError};
+pub use hugr::{
+    ConstValue, DfgBuilder, FunctionType, Hugr, HugrParseError, HugrType, Node, Operation, Wire,
+};
+pub use io::PatternIoError;
+pub use lint::Lint;
+pub use mermaid::to_mermaid;
+pub use order::PartialOrder;
+pub use parser::{parse_pattern, ParseError};
+pub use qasm::ExportError;
+pub use registry::ExtensionRegistry;
+pub use schema::{ResultEntry, ResultSchema};
+pub use stabilizer::{compute_measurement_determinism, Determinism};
+pub use stats::PatternSummary;
+pub use types::{
+    Angle, CliffordGate, Command, CommandKind, Complex, InitState, Pattern, PatternError, Pauli,
+    PauliString, Plane,
+};
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashSet;
+    use std::collections::{BTreeSet, HashSet};
 
     #[test]
     fn test_simple_prepare() {
@@ -20,7 +72,7 @@ mod tests {
         assert!(result.is_ok());
         
         let hugr = result.unwrap();
-        assert!(hugr.len() > 0);
+        assert!(!hugr.is_empty());
     }
     
     #[test]
@@ -80,4 +132,3098 @@ mod tests {
         let result = convert_graphix_pattern_to_hugr(&pattern);
         assert!(result.is_ok());
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_clifford_operation_round_trip() {
+        for gate in CliffordGate::ALL {
+            let op = Operation::from(gate);
+            assert_eq!(CliffordGate::try_from(&op).unwrap(), gate);
+        }
+        
+        let cz = Operation::Custom {
+            name: "CZ".to_string(),
+            signature: FunctionType::new(
+                vec![HugrType::Qubit, HugrType::Qubit],
+                vec![HugrType::Qubit, HugrType::Qubit],
+            ),
+            extension: "quantum.mbqc".to_string(),
+            args: vec![],
+        };
+        assert!(CliffordGate::try_from(&cz).is_err());
+    }
+    
+    fn teleportation_pattern() -> Pattern {
+        let mut pattern = Pattern::new(vec![0], vec![2]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::N { node: 2 });
+        pattern.add_command(Command::C {
+            node: 1,
+            clifford: vec![CliffordGate::H],
+        });
+        pattern.add_command(Command::E { nodes: (1, 2) });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::H],
+        });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::M {
+            node: 1,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::X {
+            node: 2,
+            domain: [1].into_iter().collect(),
+        });
+        pattern.add_command(Command::Z {
+            node: 2,
+            domain: [0].into_iter().collect(),
+        });
+        pattern
+    }
+    
+    #[test]
+    fn test_slice_teleportation_prefix() {
+        let pattern = teleportation_pattern();
+        let sliced = pattern.slice(0..3);
+        
+        assert_eq!(sliced.commands.len(), 3);
+        assert_eq!(sliced.input_nodes, vec![0]);
+        assert_eq!(sliced.output_nodes, vec![0, 1, 2]);
+        assert!(convert_graphix_pattern_to_hugr(&sliced).is_ok());
+    }
+    
+    #[test]
+    fn test_slice_after_measurements() {
+        let pattern = teleportation_pattern();
+        let sliced = pattern.slice(7..10);
+        
+        assert_eq!(sliced.input_nodes, vec![1, 2]);
+        assert_eq!(sliced.output_nodes, vec![2]);
+        assert_eq!(sliced.classical_input_nodes, vec![0]);
+        assert!(convert_graphix_pattern_to_hugr(&sliced).is_ok());
+    }
+    
+    #[test]
+    fn test_converted_hugr_matches_registry() {
+        let hugr = convert_graphix_pattern_to_hugr(&teleportation_pattern()).unwrap();
+        assert!(hugr
+            .validate_against_registry(&ExtensionRegistry::standard())
+            .is_ok());
+    }
+    
+    #[test]
+    fn test_misspelled_op_fails_registry_validation() {
+        let mut hugr = convert_graphix_pattern_to_hugr(&teleportation_pattern()).unwrap();
+        let typo = Operation::Custom {
+            name: "Hadamrd".to_string(),
+            signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]),
+            extension: "quantum.mbqc".to_string(),
+            args: vec![],
+        };
+        hugr.add_node(typo);
+        
+        let result = hugr.validate_against_registry(&ExtensionRegistry::standard());
+        assert!(matches!(
+            result,
+            Err(ConversionError::UnregisteredOperation { ref name, .. }) if name == "Hadamrd"
+        ));
+    }
+    
+    #[test]
+    fn test_pi_fraction_reduction() {
+        assert_eq!(PiFraction::new(2, 8), PiFraction::new(1, 4));
+        assert_eq!(PiFraction::new(3, -6), PiFraction::new(-1, 2));
+        assert_eq!(PiFraction::new(1, 4) + PiFraction::new(1, 4), PiFraction::new(1, 2));
+        assert_eq!(PiFraction::new(1, 2) - PiFraction::new(3, 4), PiFraction::new(-1, 4));
+        assert_eq!(PiFraction::new(3, 4).to_string(), "3*pi/4");
+    }
+    
+    #[test]
+    fn test_pi_fraction_radians() {
+        use std::f64::consts::PI;
+        
+        assert!((PiFraction::new(1, 4).to_radians() - PI / 4.0).abs() < 1e-12);
+        assert_eq!(PiFraction::from_radians(-PI / 2.0, 16), Some(PiFraction::new(-1, 2)));
+        assert_eq!(PiFraction::from_radians(1.0, 16), None);
+        
+        match Command::measure_pi(1, Plane::XY, PiFraction::new(2, 8)) {
+            Command::M { angle, .. } => assert!((angle - PI / 4.0).abs() < 1e-12),
+            _ => panic!("expected a measurement"),
+        }
+    }
+    
+    #[test]
+    fn test_line_graph_stabilizers() {
+        let mut pattern = Pattern::new(vec![], vec![0, 1, 2]);
+        for node in 0..3 {
+            pattern.add_command(Command::N { node });
+        }
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::E { nodes: (1, 2) });
+        
+        let expected: Vec<PauliString> = vec![
+            [(0, Pauli::X), (1, Pauli::Z)].into_iter().collect(),
+            [(0, Pauli::Z), (1, Pauli::X), (2, Pauli::Z)].into_iter().collect(),
+            [(1, Pauli::Z), (2, Pauli::X)].into_iter().collect(),
+        ];
+        assert_eq!(pattern.stabilizers(), expected);
+        
+        // A self-loop leaves the X factor of its node alone
+        pattern.add_command(Command::E { nodes: (1, 1) });
+        assert_eq!(pattern.stabilizers(), expected);
+    }
+    
+    #[test]
+    fn test_bell_pattern_gate_list() {
+        let mut pattern = Pattern::new(vec![0, 1], vec![0, 1]);
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::H],
+        });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        
+        let gates = to_gate_list(&pattern).unwrap();
+        let rendered: Vec<String> = gates.iter().map(|g| g.to_string()).collect();
+        assert_eq!(rendered, vec!["H q0", "CZ q0 q1"]);
+    }
+    
+    #[test]
+    fn test_bell_pattern_mermaid() {
+        let mut pattern = Pattern::new(vec![0, 1], vec![0, 1]);
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::H],
+        });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        
+        let mermaid = to_mermaid(&pattern);
+        assert!(mermaid.starts_with("graph LR\n"));
+        assert!(mermaid.contains("n0[\"0\"]:::input"));
+        assert!(mermaid.contains("n1[\"1\"]:::input"));
+        assert!(mermaid.contains("n0 --- n1"));
+        
+        let teleport = to_mermaid(&teleportation_pattern());
+        assert!(teleport.contains("n1[\"1\"]:::measured"));
+        assert!(teleport.contains("n1 -.->|X| n2"));
+        assert!(teleport.contains("n0 -.->|Z| n2"));
+    }
+    
+    /// Compare the spec JSON of `pattern`'s HUGR with
+    /// `tests/golden/<name>_spec.json`. Set `UPDATE_GOLDEN=1` to rewrite the
+    /// golden file instead.
+    fn assert_matches_golden(name: &str, pattern: &Pattern) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{}_spec.json", name));
+        let actual = convert_graphix_pattern_to_hugr(pattern).unwrap().to_spec_json();
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, actual + "\n").unwrap();
+            return;
+        }
+        
+        let golden = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("cannot read {}: {} (regenerate with UPDATE_GOLDEN=1)", path.display(), e));
+        assert_eq!(
+            actual,
+            golden.trim_end(),
+            "{} changed; rerun with UPDATE_GOLDEN=1 if this is intended",
+            name
+        );
+    }
+    
+    #[test]
+    fn test_bell_pattern_spec_json_golden() {
+        let mut pattern = Pattern::new(vec![0, 1], vec![0, 1]);
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::H],
+        });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        
+        assert_matches_golden("bell", &pattern);
+    }
+    
+    #[test]
+    fn test_teleportation_spec_json_golden() {
+        assert_matches_golden("teleportation", &teleportation_pattern());
+    }
+    
+    #[test]
+    fn test_measurement_spec_json_golden() {
+        let mut pattern = Pattern::new(vec![0], vec![]);
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: std::f64::consts::FRAC_PI_4,
+        });
+        
+        assert_matches_golden("measurement", &pattern);
+    }
+    
+    #[test]
+    fn test_mbqc_corrections_spec_json_golden() {
+        let mut pattern = Pattern::new(vec![0], vec![0]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 1,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::X {
+            node: 0,
+            domain: [1].into_iter().collect(),
+        });
+        pattern.add_command(Command::Z {
+            node: 0,
+            domain: [1].into_iter().collect(),
+        });
+        
+        assert_matches_golden("mbqc_corrections", &pattern);
+    }
+    
+    #[test]
+    fn test_mbqc_computation_spec_json_golden() {
+        use std::f64::consts::PI;
+        
+        let mut pattern = Pattern::new(vec![0], vec![4]);
+        for node in 1..5 {
+            pattern.add_command(Command::N { node });
+        }
+        for node in 1..5 {
+            pattern.add_command(Command::E {
+                nodes: (node - 1, node),
+            });
+        }
+        pattern.add_command(Command::M {
+            node: 1,
+            plane: Plane::XY,
+            angle: PI / 4.0,
+        });
+        pattern.add_command(Command::M {
+            node: 2,
+            plane: Plane::XY,
+            angle: PI / 8.0,
+        });
+        pattern.add_command(Command::Z {
+            node: 3,
+            domain: [1].into_iter().collect(),
+        });
+        pattern.add_command(Command::M {
+            node: 3,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::X {
+            node: 4,
+            domain: [2, 3].into_iter().collect(),
+        });
+        pattern.add_command(Command::Z {
+            node: 4,
+            domain: [1].into_iter().collect(),
+        });
+        
+        assert_matches_golden("mbqc_computation", &pattern);
+    }
+    
+    #[test]
+    fn test_cluster_state_spec_json_golden() {
+        let mut pattern = Pattern::new(vec![], (0..9).collect());
+        for node in 0..9 {
+            pattern.add_command(Command::N { node });
+        }
+        for row in 0..3 {
+            for col in 0..2 {
+                pattern.add_command(Command::E {
+                    nodes: (row * 3 + col, row * 3 + col + 1),
+                });
+            }
+        }
+        for row in 0..2 {
+            for col in 0..3 {
+                pattern.add_command(Command::E {
+                    nodes: (row * 3 + col, (row + 1) * 3 + col),
+                });
+            }
+        }
+        
+        assert_matches_golden("cluster_state", &pattern);
+    }
+    
+    #[test]
+    fn test_branch_lowers_to_conditional_with_two_cases() {
+        use crate::testutil::{assert_op_count, find_ops};
+        
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::Branch {
+            domain: [0].into_iter().collect(),
+            if_true: vec![Command::C {
+                node: 1,
+                clifford: vec![CliffordGate::H],
+            }],
+            if_false: vec![],
+        });
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        
+        assert_op_count(&hugr, "Conditional", 1);
+        let conditional = find_ops(&hugr, "Conditional")[0];
+        let cases = hugr.children(conditional.id);
+        assert_eq!(cases.len(), 2);
+        let qubit_only = FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]);
+        for &case in &cases {
+            let Operation::Case { signature } = &hugr.nodes[&case].operation else {
+                panic!("child of a Conditional must be a Case");
+            };
+            assert_eq!(*signature, qubit_only);
+            
+            // Each case has its own boundary matching the case signature
+            let body = hugr.children(case);
+            let case_input = &hugr.nodes[&body[0]];
+            let case_output = &hugr.nodes[body.last().unwrap()];
+            assert!(matches!(
+                &case_input.operation,
+                Operation::Input { types } if *types == signature.inputs
+            ));
+            assert!(matches!(
+                &case_output.operation,
+                Operation::Output { types } if *types == signature.outputs
+            ));
+        }
+        let hs: Vec<usize> = find_ops(&hugr, "H").iter().filter_map(|node| node.parent).collect();
+        assert!(hs.contains(&cases[1]) && !hs.contains(&cases[0]));
+        
+        assert_eq!(hugr.output_node().unwrap().inputs[0], conditional.out(0));
+        assert!(hugr
+            .validate_against_registry(&ExtensionRegistry::standard())
+            .is_ok());
+        assert!(matches!(
+            to_gate_list(&pattern),
+            Err(ConversionError::NotFlat(CommandKind::Branch))
+        ));
+    }
+    
+    #[test]
+    fn test_branch_cases_must_agree_on_measurements() {
+        let branch_pattern = |if_false: Vec<Command>| {
+            let mut pattern = Pattern::new(vec![0, 1], vec![]);
+            pattern.add_command(Command::MZ { node: 0 });
+            pattern.add_command(Command::Branch {
+                domain: [0].into_iter().collect(),
+                if_true: vec![
+                    Command::C {
+                        node: 1,
+                        clifford: vec![CliffordGate::H],
+                    },
+                    Command::MZ { node: 1 },
+                ],
+                if_false,
+            });
+            pattern
+        };
+        
+        let result = convert_graphix_pattern_to_hugr(&branch_pattern(vec![]));
+        assert!(matches!(result, Err(ConversionError::BranchSignatureMismatch)));
+        
+        // Measuring the same node in both cases returns its outcome
+        let both_measure = branch_pattern(vec![Command::MZ { node: 1 }]);
+        let hugr = convert_graphix_pattern_to_hugr(&both_measure).unwrap();
+        let conditional = testutil::find_ops(&hugr, "Conditional")[0];
+        let Operation::Conditional { signature } = &conditional.operation else {
+            unreachable!()
+        };
+        assert_eq!(signature.outputs, vec![HugrType::Bool]);
+    }
+    
+    #[test]
+    fn test_measurement_determinism_on_graph_state() {
+        use crate::testutil::find_ops;
+        
+        // Two-node graph state: X on one end is random, after which Z on the
+        // other end is fixed by the stabilizer X0 Z1
+        let mut pattern = Pattern::new(vec![], vec![]);
+        pattern.add_command(Command::N { node: 0 });
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::MZ { node: 1 });
+        
+        let outcomes = compute_measurement_determinism(&pattern);
+        assert_eq!(outcomes[&0], Determinism::Random);
+        assert_eq!(outcomes[&1], Determinism::Deterministic);
+        
+        let config = ConverterConfig {
+            annotate_determinism: true,
+            ..Default::default()
+        };
+        let hugr = GraphixToHugrConverter::with_config(config)
+            .convert(&pattern)
+            .unwrap();
+        let annotated: Vec<(&str, &str)> = find_ops(&hugr, "Measure")
+            .iter()
+            .map(|node| {
+                let outcome = node.get_meta("outcome").unwrap();
+                (node.get_meta("pattern_node").unwrap(), outcome)
+            })
+            .collect();
+        assert_eq!(annotated, vec![("0", "random"), ("1", "deterministic")]);
+        
+        // Z on an unmeasured graph-state node is random; an outside input is
+        // input-dependent; a non-Pauli angle stops the analysis
+        let mut leaf = Pattern::new(vec![2], vec![]);
+        leaf.add_command(Command::N { node: 0 });
+        leaf.add_command(Command::N { node: 1 });
+        leaf.add_command(Command::E { nodes: (0, 1) });
+        leaf.add_command(Command::MZ { node: 1 });
+        leaf.add_command(Command::MZ { node: 2 });
+        leaf.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.3,
+        });
+        let outcomes = compute_measurement_determinism(&leaf);
+        assert_eq!(outcomes[&1], Determinism::Random);
+        assert_eq!(outcomes[&2], Determinism::InputDependent);
+        assert!(!outcomes.contains_key(&0));
+    }
+    
+    #[test]
+    fn test_pattern_summary() {
+        let summary = teleportation_pattern().summary();
+        assert_eq!(
+            summary,
+            PatternSummary {
+                n_inputs: 1,
+                n_outputs: 1,
+                n_commands: 10,
+                n_prepared: 2,
+                n_measured: 2,
+                n_entanglements: 2,
+                max_correction_domain_size: 1,
+            }
+        );
+        assert_eq!(summary.to_string(), "in=1 out=1 cmds=10 N=2 M=2 E=2 max_domain=1");
+    }
+    
+    #[test]
+    fn test_ancilla_and_data_nodes() {
+        let pattern = teleportation_pattern();
+        assert_eq!(pattern.ancilla_nodes(), BTreeSet::from([1]));
+        assert_eq!(pattern.data_nodes(), BTreeSet::from([0, 2]));
+    }
+    
+    #[test]
+    fn test_self_cancelling_pattern_is_identity() {
+        let mut pattern = Pattern::new(vec![0, 1, 2], vec![0, 1, 2]);
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::C {
+            node: 2,
+            clifford: vec![CliffordGate::H, CliffordGate::H],
+        });
+        pattern.add_command(Command::E { nodes: (1, 2) });
+        pattern.add_command(Command::E { nodes: (1, 0) });
+        pattern.add_command(Command::E { nodes: (2, 1) });
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::S, CliffordGate::SDG],
+        });
+        assert!(pattern.is_identity());
+        assert!(Pattern::new(vec![0, 1], vec![0, 1]).is_identity());
+        
+        let config = ConverterConfig {
+            identity_fast_path: true,
+            ..Default::default()
+        };
+        let hugr = GraphixToHugrConverter::with_config(config)
+            .convert(&pattern)
+            .unwrap();
+        assert_eq!(hugr.len(), 2);
+        let input = hugr.input_node().unwrap();
+        let routed: Vec<Wire> = (0..3).map(|port| input.out(port)).collect();
+        assert_eq!(hugr.output_node().unwrap().inputs, routed);
+        
+        // Edges cancel and each Clifford squares away, but H between the two
+        // CZs does not commute with them
+        let mut conjugated = Pattern::new(vec![0, 1], vec![0, 1]);
+        for _ in 0..2 {
+            conjugated.add_command(Command::E { nodes: (0, 1) });
+            conjugated.add_command(Command::C {
+                node: 0,
+                clifford: vec![CliffordGate::H],
+            });
+        }
+        assert!(!conjugated.is_identity());
+        assert!(!teleportation_pattern().is_identity());
+        
+        // Cancelling edges to an undeclared node still reach the converter,
+        // which rejects the missing node
+        let mut undeclared = Pattern::new(vec![0], vec![0]);
+        for _ in 0..2 {
+            undeclared.add_command(Command::E { nodes: (0, 5) });
+        }
+        assert!(!undeclared.is_identity());
+        let config = ConverterConfig {
+            identity_fast_path: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            GraphixToHugrConverter::with_config(config).convert(&undeclared),
+            Err(ConversionError::NodeNotFound(5))
+        ));
+    }
+    
+    #[test]
+    fn test_split_resource_and_consumption() {
+        use crate::testutil::assert_op_count;
+        
+        let mut pattern = Pattern::new(vec![0], vec![2]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::N { node: 2 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::E { nodes: (1, 2) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::M {
+            node: 1,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::X {
+            node: 2,
+            domain: [1].into_iter().collect(),
+        });
+        pattern.add_command(Command::Z {
+            node: 2,
+            domain: [0].into_iter().collect(),
+        });
+        
+        let (resource, consumption) = split_resource_and_consumption(&pattern).unwrap();
+        assert_op_count(&resource, "Measure", 0);
+        assert_op_count(&resource, "PrepareQubit", 2);
+        assert_op_count(&consumption, "PrepareQubit", 0);
+        assert_op_count(&consumption, "Measure", 2);
+        
+        // The graph state's three qubits are the interface
+        assert_eq!(resource.output_types().unwrap(), vec![HugrType::Qubit; 3]);
+        assert_eq!(consumption.input_types().unwrap(), vec![HugrType::Qubit; 3]);
+        
+        assert!(matches!(
+            split_resource_and_consumption(&teleportation_pattern()),
+            Err(ConversionError::NotStandardForm { at_command: 3 })
+        ));
+    }
+    
+    #[test]
+    fn test_bell_pattern_structure() {
+        use crate::testutil::{assert_op_count, assert_wire_feeds, find_ops};
+        
+        let mut pattern = Pattern::new(vec![0, 1], vec![0, 1]);
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::H],
+        });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        
+        assert_op_count(&hugr, "H", 1);
+        assert_op_count(&hugr, "CZ", 1);
+        assert_op_count(&hugr, "Measure", 0);
+        
+        let input = hugr.input_node().unwrap().id;
+        let h = find_ops(&hugr, "H")[0];
+        let cz = find_ops(&hugr, "CZ")[0];
+        assert_wire_feeds(&hugr, input, 0, "H");
+        assert_wire_feeds(&hugr, input, 1, "CZ");
+        assert_wire_feeds(&hugr, h.id, 0, "CZ");
+        assert_wire_feeds(&hugr, cz.id, 0, "Output");
+        assert_wire_feeds(&hugr, cz.id, 1, "Output");
+    }
+    
+    #[test]
+    #[should_panic(expected = "expected 2 CZ node(s), found 1")]
+    fn test_assert_op_count_reports_mismatch() {
+        let mut pattern = Pattern::new(vec![0, 1], vec![0, 1]);
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        testutil::assert_op_count(&hugr, "CZ", 2);
+    }
+    
+    #[test]
+    fn test_gate_list_marks_conditionals() {
+        let gates = to_gate_list(&teleportation_pattern()).unwrap();
+        let conditional: Vec<&GateInstr> = gates.iter().filter(|g| g.is_conditional()).collect();
+        
+        assert_eq!(conditional.len(), 2);
+        assert_eq!(conditional[0].name, "X");
+        assert_eq!(conditional[0].condition, Some([1].into_iter().collect()));
+    }
+    
+    #[test]
+    fn test_measure_in_degrees_matches_radians() {
+        use std::f64::consts::PI;
+        
+        let build = |measure: Command| {
+            let mut pattern = Pattern::new(vec![0], vec![]);
+            pattern.add_command(measure);
+            let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+            let mut ids: Vec<_> = hugr.nodes.keys().cloned().collect();
+            ids.sort();
+            ids.iter()
+                .map(|id| format!("{:?}", hugr.nodes[id]))
+                .collect::<Vec<_>>()
+        };
+        
+        assert_eq!(
+            build(Command::measure_deg(0, Plane::XY, 45.0)),
+            build(Command::M {
+                node: 0,
+                plane: Plane::XY,
+                angle: PI / 4.0,
+            })
+        );
+    }
+    
+    #[test]
+    fn test_input_output_accessors() {
+        let pattern = teleportation_pattern();
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        
+        assert_eq!(
+            hugr.input_types().unwrap(),
+            vec![HugrType::Qubit; pattern.input_nodes.len()].as_slice()
+        );
+        assert_eq!(hugr.output_types().unwrap().len(), 3);
+        assert_eq!(hugr.input_node().unwrap().id, 0);
+        assert_eq!(hugr.output_node().unwrap().inputs.len(), 3);
+        
+        let mut empty = Hugr::new();
+        assert!(empty.input_node_mut().is_none());
+        assert!(empty.output_types().is_none());
+    }
+    
+    #[test]
+    fn test_phase_barriers() {
+        let mut pattern = Pattern::new(vec![0], vec![2]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::N { node: 2 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::E { nodes: (1, 2) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::M {
+            node: 1,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        
+        let count_barriers = |hugr: &Hugr| {
+            hugr.nodes
+                .values()
+                .filter(|n| matches!(&n.operation, Operation::Custom { name, .. } if name == "Barrier"))
+                .count()
+        };
+        
+        let config = ConverterConfig {
+            emit_phase_barriers: true,
+            ..Default::default()
+        };
+        let hugr = GraphixToHugrConverter::with_config(config)
+            .convert(&pattern)
+            .unwrap();
+        assert_eq!(count_barriers(&hugr), 2);
+        assert!(hugr
+            .validate_against_registry(&ExtensionRegistry::standard())
+            .is_ok());
+        
+        let plain = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        assert_eq!(count_barriers(&plain), 0);
+    }
+    
+    #[test]
+    fn test_large_domain_xor_tree_is_balanced() {
+        let mut pattern = Pattern::new(vec![], vec![16]);
+        for node in 0..=16 {
+            pattern.add_command(Command::N { node });
+        }
+        for node in 0..16 {
+            pattern.add_command(Command::M {
+                node,
+                plane: Plane::XY,
+                angle: 0.0,
+            });
+        }
+        pattern.add_command(Command::X {
+            node: 16,
+            domain: (0..16).collect(),
+        });
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        
+        fn xor_depth(hugr: &Hugr, id: usize) -> usize {
+            let node = &hugr.nodes[&id];
+            if node.operation.name() != "XOR" {
+                return 0;
+            }
+            1 + node
+                .inputs
+                .iter()
+                .map(|wire| xor_depth(hugr, wire.node_id))
+                .max()
+                .unwrap_or(0)
+        }
+        let correction = hugr
+            .nodes
+            .values()
+            .find(|node| node.operation.name() == "ConditionalX")
+            .unwrap();
+        assert_eq!(xor_depth(&hugr, correction.inputs[0].node_id), 4);
+        let n_xors = hugr.nodes.values().filter(|node| node.operation.name() == "XOR").count();
+        assert_eq!(n_xors, 15);
+    }
+    
+    #[test]
+    fn test_nested_regions() {
+        let config = ConverterConfig {
+            nested_regions: true,
+            ..Default::default()
+        };
+        let hugr = GraphixToHugrConverter::with_config(config.clone())
+            .convert(&teleportation_pattern())
+            .unwrap();
+        
+        let regions = hugr.children(hugr.root);
+        assert_eq!(regions.len(), 3);
+        assert!(regions
+            .iter()
+            .all(|id| matches!(hugr.nodes[id].operation, Operation::DFG { .. })));
+        
+        // Every gate sits in one of the phase regions, and each region has
+        // its own boundary nodes
+        for node in hugr.nodes.values() {
+            if matches!(node.operation, Operation::Custom { .. }) {
+                assert!(regions.contains(&node.parent.unwrap()));
+            }
+        }
+        for &region in &regions {
+            let boundary: Vec<&str> = hugr
+                .children(region)
+                .iter()
+                .map(|id| hugr.nodes[id].operation.name())
+                .filter(|name| matches!(*name, "Input" | "Output"))
+                .collect();
+            assert_eq!(boundary, ["Input", "Output"]);
+        }
+        
+        // No wire crosses a region boundary: every node reads from siblings
+        let container = |id: usize| hugr.nodes[&id].parent.filter(|&p| p != hugr.root);
+        for node in hugr.nodes.values() {
+            for wire in &node.inputs {
+                assert_eq!(container(wire.node_id), container(node.id), "{:?} -> {}", wire, node.id);
+            }
+        }
+        
+        // Preparation feeds entanglement, which feeds the measurements
+        let signature = |id: usize| match &hugr.nodes[&id].operation {
+            Operation::DFG { signature } => signature.clone(),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            signature(regions[0]),
+            FunctionType::new(vec![], vec![HugrType::Qubit, HugrType::Qubit])
+        );
+        assert_eq!(signature(regions[1]).inputs, vec![HugrType::Qubit; 3]);
+        assert_eq!(signature(regions[1]).outputs, vec![HugrType::Qubit; 3]);
+        assert!(hugr.nodes[&regions[1]].inputs.iter().any(|w| w.node_id == regions[0]));
+        assert!(hugr.nodes[&regions[2]].inputs.iter().all(|w| w.node_id == regions[1]));
+        let output = hugr.output_node().unwrap();
+        assert_eq!(output.inputs, (0..3).map(|port| Wire::new(regions[2], port)).collect::<Vec<_>>());
+        
+        // Inlining the regions gives back the flat conversion
+        let plain = convert_graphix_pattern_to_hugr(&teleportation_pattern()).unwrap();
+        assert!(plain.nodes.values().all(|node| node.parent.is_none()));
+        assert!(hugr.flatten_regions().is_isomorphic(&plain));
+        let input = [Complex::new(0.6, 0.0), Complex::new(0.0, 0.8)];
+        assert_eq!(
+            sim::hugr_distribution(&hugr, &input).unwrap(),
+            sim::hugr_distribution(&plain, &input).unwrap()
+        );
+        
+        // A preparation after a measurement would need a cycle of regions
+        let mut late = teleportation_pattern();
+        late.add_command(Command::N { node: 3 });
+        assert!(matches!(
+            GraphixToHugrConverter::with_config(config).convert(&late),
+            Err(ConversionError::NotStandardForm { at_command: 10 })
+        ));
+    }
+    
+    #[test]
+    fn test_custom_gate_passthrough() {
+        let one = Complex::new(1.0, 0.0);
+        let zero = Complex::new(0.0, 0.0);
+        let mut pattern = Pattern::new(vec![0], vec![0]);
+        pattern.add_command(Command::Gate {
+            node: 0,
+            name: "Foo".to_string(),
+            matrix: Some([[one, zero], [zero, one]]),
+            params: vec![0.5, 1.5],
+        });
+        
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        let foo = hugr
+            .nodes
+            .values()
+            .find_map(|n| match &n.operation {
+                Operation::Custom { name, args, signature, .. } if name == "Foo" => {
+                    Some((args.clone(), signature.clone()))
+                }
+                _ => None,
+            })
+            .expect("Foo gate missing from HUGR");
+        
+        assert_eq!(foo.0, vec![0.5, 1.5]);
+        assert_eq!(foo.1, FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]));
+    }
+    
+    #[test]
+    fn test_measurement_basis_query() {
+        use std::f64::consts::PI;
+        
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: PI / 4.0,
+        });
+        
+        assert_eq!(pattern.measurement_of(0), Some((Plane::XY, PI / 4.0)));
+        assert_eq!(pattern.measurement_of(1), None);
+        assert_eq!(pattern.measured_nodes().into_iter().collect::<Vec<_>>(), vec![0]);
+        
+        pattern.add_command(Command::MSoft {
+            node: 1,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::N { node: 2 });
+        pattern.add_command(Command::MAdaptive {
+            node: 2,
+            plane: Plane::XY,
+            angle: Angle::Fixed(0.0),
+        });
+        assert_eq!(pattern.measured_nodes().into_iter().collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+    
+    #[test]
+    fn test_ghz_state() {
+        let pattern = Pattern::ghz(4);
+        assert!(convert_graphix_pattern_to_hugr(&pattern).is_ok());
+        
+        let state = sim::simulate(&pattern, &[Complex::ONE]).unwrap();
+        let amp = std::f64::consts::FRAC_1_SQRT_2;
+        for (idx, a) in state.iter().enumerate() {
+            let expected = if idx == 0 || idx == 0b1111 { amp } else { 0.0 };
+            assert!((a.re - expected).abs() < 1e-12 && a.im.abs() < 1e-12, "amplitude {}", idx);
+        }
+    }
+    
+    #[test]
+    fn test_ghz_edge_cases() {
+        assert!(Pattern::ghz(0).commands.is_empty());
+        assert!(Pattern::ghz(0).output_nodes.is_empty());
+        
+        let single = Pattern::ghz(1);
+        assert_eq!(single.commands.len(), 1);
+        let state = sim::simulate(&single, &[Complex::ONE]).unwrap();
+        assert!(state.iter().all(|a| (a.re - std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-12));
+    }
+    
+    #[test]
+    fn test_dot_labels_entanglement_edges() {
+        use crate::testutil::find_ops;
+        
+        let mut pattern = Pattern::new(vec![0, 1], vec![0, 1]);
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::H],
+        });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        let cz = hugr
+            .nodes
+            .values()
+            .find(|n| matches!(&n.operation, Operation::Custom { name, .. } if name == "CZ"))
+            .unwrap();
+        
+        let dot = hugr.to_dot();
+        let into_cz: Vec<&str> = dot
+            .lines()
+            .filter(|l| l.contains(&format!("-> n{} ", cz.id)))
+            .collect();
+        assert_eq!(into_cz.len(), 2);
+        assert!(into_cz.iter().any(|l| l.contains("label=\"q0\"")));
+        assert!(into_cz.iter().any(|l| l.contains("label=\"q1\"")));
+        assert!(into_cz.iter().all(|l| l.contains("style=solid")));
+        assert!(dot.contains(&format!("n{} [shape=box, label=\"Input\", style=bold", hugr.root)));
+        
+        // Outcomes feeding the corrections are dashed
+        let hugr = convert_graphix_pattern_to_hugr(&teleportation_pattern()).unwrap();
+        let x = find_ops(&hugr, "ConditionalX")[0];
+        let dot = hugr.to_dot();
+        let condition = x.inputs[0];
+        let condition = format!(
+            "n{} -> n{} [taillabel=\"{}\", style=dashed",
+            condition.node_id, x.id, condition.port
+        );
+        assert!(dot.contains(&condition), "{}", dot);
+    }
+    
+    #[test]
+    fn test_non_finite_angles_rejected() {
+        for bad in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let mut pattern = Pattern::new(vec![], vec![]);
+            pattern.add_command(Command::N { node: 3 });
+            pattern.add_command(Command::M {
+                node: 3,
+                plane: Plane::XZ,
+                angle: bad,
+            });
+            
+            let result = convert_graphix_pattern_to_hugr(&pattern);
+            assert!(matches!(result, Err(ConversionError::InvalidAngle { node: 3, .. })));
+            assert!(matches!(
+                to_gate_list(&pattern),
+                Err(ConversionError::InvalidAngle { node: 3, .. })
+            ));
+        }
+    }
+    
+    #[test]
+    fn test_correction_truth_table_is_xor() {
+        let mut pattern = Pattern::new(vec![0, 1], vec![2]);
+        pattern.add_command(Command::N { node: 2 });
+        pattern.add_command(Command::MZ { node: 0 });
+        pattern.add_command(Command::MZ { node: 1 });
+        pattern.add_command(Command::X {
+            node: 2,
+            domain: [0, 1].into_iter().collect(),
+        });
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        
+        let table = correction_truth_table(&hugr);
+        assert_eq!(table.inputs.len(), 2);
+        assert!(table
+            .inputs
+            .iter()
+            .all(|wire| hugr.nodes[&wire.node_id].operation.name() == "Measure"));
+        assert_eq!(table.outputs.len(), 1);
+        assert_eq!(
+            table.rows,
+            vec![
+                (vec![false, false], vec![false]),
+                (vec![false, true], vec![true]),
+                (vec![true, false], vec![true]),
+                (vec![true, true], vec![false]),
+            ]
+        );
+    }
+    
+    #[test]
+    fn test_extract_classical_subgraph() {
+        let mut pattern = Pattern::new(vec![0], vec![3]);
+        for node in 1..4 {
+            pattern.add_command(Command::N { node });
+        }
+        for edge in [(0, 1), (1, 2), (2, 3)] {
+            pattern.add_command(Command::E { nodes: edge });
+        }
+        for node in 0..3 {
+            pattern.add_command(Command::M {
+                node,
+                plane: Plane::XY,
+                angle: 0.0,
+            });
+        }
+        pattern.add_command(Command::X {
+            node: 3,
+            domain: [0, 2].into_iter().collect(),
+        });
+        pattern.add_command(Command::Z {
+            node: 3,
+            domain: [0, 1, 2].into_iter().collect(),
+        });
+        
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        let classical = extract_classical_subgraph(&hugr);
+        
+        let names: Vec<String> = classical
+            .nodes
+            .values()
+            .filter_map(|n| match &n.operation {
+                Operation::Custom { name, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names.iter().filter(|n| *n == "XOR").count(), 3);
+        assert!(names.iter().all(|n| n == "XOR"));
+        
+        assert_eq!(classical.input_types().unwrap(), vec![HugrType::Bool; 3].as_slice());
+        // One condition bit per correction
+        assert_eq!(classical.output_types().unwrap().len(), 2);
+    }
+    
+    #[test]
+    fn test_hadamard_pattern_unitary() {
+        let mut pattern = Pattern::new(vec![0], vec![0]);
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::H],
+        });
+        
+        let h = sim::clifford_matrix(CliffordGate::H);
+        let expected: sim::Matrix = h.iter().map(|row| row.to_vec()).collect();
+        let unitary = sim::pattern_unitary(&pattern).unwrap();
+        assert!(sim::unitary_close(&unitary, &expected, 1e-9));
+        
+        // Global phase is ignored, but a different gate is not
+        let phased: sim::Matrix = expected
+            .iter()
+            .map(|row| row.iter().map(|&v| v * Complex::I).collect())
+            .collect();
+        assert!(sim::unitary_close(&unitary, &phased, 1e-9));
+        let x = sim::clifford_matrix(CliffordGate::X);
+        let x: sim::Matrix = x.iter().map(|row| row.to_vec()).collect();
+        assert!(!sim::unitary_close(&unitary, &x, 1e-9));
+    }
+    
+    #[test]
+    fn test_partial_input_initialization() {
+        let mut pattern = Pattern::new(vec![0, 1], vec![0, 1]);
+        pattern.input_states.insert(1, InitState::Zero);
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        assert_eq!(hugr.input_types().unwrap(), [HugrType::Qubit].as_slice());
+        assert_eq!(hugr.output_node().unwrap().inputs.len(), 2);
+        
+        // CZ with |0⟩ on node 1 acts as identity on node 0
+        let state = sim::simulate(&pattern, &[Complex::ZERO, Complex::ONE]).unwrap();
+        assert!((state[0b10].re - 1.0).abs() < 1e-12);
+    }
+    
+    #[test]
+    fn test_presized_conversion_unchanged() {
+        let pattern = teleportation_pattern();
+        assert_eq!(pattern.max_node(), Some(2));
+        assert_eq!(Pattern::new(vec![], vec![]).max_node(), None);
+        
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        let names: Vec<String> = (0..hugr.next_node_id)
+            .map(|id| match &hugr.nodes[&id].operation {
+                Operation::Custom { name, .. } => name.clone(),
+                other => format!("{:?}", other).split_whitespace().next().unwrap().to_string(),
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "Input", "PrepareQubit", "PrepareQubit", "H", "CZ", "CZ", "H", "H", "Measure",
+                "H", "Measure", "ConditionalX", "ConditionalZ", "Output",
+            ]
+        );
+    }
+    
+    #[test]
+    fn test_structural_hash_ignores_node_ids() {
+        let hugr = convert_graphix_pattern_to_hugr(&teleportation_pattern()).unwrap();
+        
+        // Renumber every node by a constant offset
+        let shift = |id: usize| id + 100;
+        let mut shifted = Hugr::new();
+        for node in hugr.nodes.values() {
+            let mut copy = node.clone();
+            copy.id = shift(node.id);
+            for wire in copy.inputs.iter_mut().chain(copy.outputs.iter_mut()) {
+                wire.node_id = shift(wire.node_id);
+            }
+            if let Operation::LoadConst { const_node } = &mut copy.operation {
+                *const_node = shift(*const_node);
+            }
+            shifted.nodes.insert(copy.id, copy);
+        }
+        assert_eq!(hugr.structural_hash(), shifted.structural_hash());
+        
+        let other = convert_graphix_pattern_to_hugr(&Pattern::ghz(3)).unwrap();
+        assert_ne!(hugr.structural_hash(), other.structural_hash());
+    }
+    
+    #[test]
+    fn test_pattern_hash_key() {
+        assert_eq!(teleportation_pattern().hash_key(), teleportation_pattern().hash_key());
+        assert_ne!(teleportation_pattern().hash_key(), Pattern::ghz(3).hash_key());
+    }
+    
+    #[test]
+    fn test_equal_patterns_dedupe_in_hash_set() {
+        let patterns: HashSet<Pattern> = [teleportation_pattern(), teleportation_pattern()]
+            .into_iter()
+            .collect();
+        assert_eq!(patterns.len(), 1);
+        
+        // Angles compare by bit pattern
+        let measure = |angle: f64| Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle,
+        };
+        assert_eq!(measure(f64::NAN), measure(f64::NAN));
+        assert_ne!(measure(0.0), measure(-0.0));
+    }
+    
+    #[test]
+    fn test_nondestructive_measure_keeps_qubit() {
+        let mut pattern = Pattern::new(vec![0], vec![0]);
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::YZ,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::X],
+        });
+        
+        let config = ConverterConfig {
+            nondestructive_measure: true,
+            ..Default::default()
+        };
+        let registry = ExtensionRegistry::for_config(&config);
+        let hugr = GraphixToHugrConverter::with_config(config)
+            .convert(&pattern)
+            .unwrap();
+        
+        let measure = hugr
+            .nodes
+            .values()
+            .find(|n| matches!(&n.operation, Operation::Custom { name, .. } if name == "Measure"))
+            .unwrap();
+        assert_eq!(measure.outputs.len(), 2);
+        
+        // The X gate consumes the post-measurement qubit
+        let x_gate = hugr
+            .nodes
+            .values()
+            .find(|n| matches!(&n.operation, Operation::Custom { name, .. } if name == "X"))
+            .unwrap();
+        assert_eq!(x_gate.inputs, vec![measure.out(0)]);
+        assert!(hugr.validate_against_registry(&registry).is_ok());
+        
+        // Destructive measurement leaves nothing for the X gate to act on
+        assert!(matches!(
+            convert_graphix_pattern_to_hugr(&pattern),
+            Err(ConversionError::OutputNodeMeasured(0))
+        ));
+    }
+    
+    #[test]
+    fn test_classical_output_also_feeds_corrections() {
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::X {
+            node: 1,
+            domain: [0].into_iter().collect(),
+        });
+        pattern.add_command(Command::Z {
+            node: 1,
+            domain: [0].into_iter().collect(),
+        });
+        
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        let measure = hugr
+            .nodes
+            .values()
+            .find(|n| matches!(&n.operation, Operation::Custom { name, .. } if name == "Measure"))
+            .unwrap();
+        let outcome = measure.out(0);
+        
+        // The outcome is routed to the classical output and to both corrections
+        let readers: Vec<String> = hugr
+            .nodes
+            .values()
+            .filter(|n| n.inputs.contains(&outcome))
+            .map(|n| match &n.operation {
+                Operation::Custom { name, .. } => name.clone(),
+                _ => "Output".to_string(),
+            })
+            .collect();
+        assert_eq!(readers.len(), 3);
+        assert!(readers.contains(&"ConditionalX".to_string()));
+        assert!(readers.contains(&"ConditionalZ".to_string()));
+        assert_eq!(hugr.output_node().unwrap().inputs[1], outcome);
+    }
+    
+    #[test]
+    fn test_dependents_of() {
+        let pattern = teleportation_pattern();
+        
+        // Preparing ancilla 1 feeds its Hadamard, both entanglements and its measurement
+        assert_eq!(pattern.dependents_of(0), vec![2, 3, 4, 7]);
+        // Measuring node 1 feeds the X correction through its domain
+        assert_eq!(pattern.dependents_of(7), vec![8]);
+        assert!(pattern.dependents_of(9).is_empty());
+    }
+    
+    #[test]
+    fn test_custom_classical_output_order() {
+        let mut pattern = teleportation_pattern();
+        let default_hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        
+        pattern.classical_output_nodes = vec![1, 0];
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        
+        // Port 0 is the output qubit; the Bool ports follow in the requested
+        // order. Both conversions number their nodes identically.
+        let default_ports = &default_hugr.output_node().unwrap().inputs;
+        let ports = &hugr.output_node().unwrap().inputs;
+        assert_eq!(ports[0], default_ports[0]);
+        assert_eq!(ports[1], default_ports[2]);
+        assert_eq!(ports[2], default_ports[1]);
+    }
+    
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_pattern_from_yaml() {
+        let yaml = "
+input_nodes: [0]
+output_nodes: [1]
+commands:
+  - N: {node: 1}
+  - E: {nodes: [0, 1]}
+  - M: {node: 0, plane: XY, angle: 0.0}
+  - X: {node: 1, domain: [0]}
+";
+        let pattern = Pattern::from_yaml(yaml).unwrap();
+        assert_eq!(pattern.commands.len(), 4);
+        assert!(convert_graphix_pattern_to_hugr(&pattern).is_ok());
+        
+        let emitted = pattern.to_yaml().unwrap();
+        assert!(emitted.contains("plane: XY"));
+        let round_trip = Pattern::from_yaml(&emitted).unwrap();
+        assert_eq!(round_trip.hash_key(), pattern.hash_key());
+    }
+    
+    #[test]
+    fn test_domains_serialize_sorted() {
+        let mut pattern = Pattern::new(vec![], vec![]);
+        pattern.add_command(Command::Z {
+            node: 0,
+            domain: [9, 3, 5, 1].into_iter().collect(),
+        });
+        let json = serde_json::to_string(&pattern).unwrap();
+        assert!(json.contains("\"domain\":[1,3,5,9]"));
+    }
+    
+    #[test]
+    fn test_cancel_adjacent_edges() {
+        let mut pattern = Pattern::new(vec![0, 1], vec![0, 1]);
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::E { nodes: (1, 0) });
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::H],
+        });
+        
+        let simplified = pattern.cancel_redundant_edges();
+        assert_eq!(simplified.commands.len(), 1);
+        assert_eq!(simplified.commands[0].kind(), CommandKind::C);
+    }
+    
+    #[test]
+    fn test_intervening_measurement_blocks_cancellation() {
+        let mut pattern = Pattern::new(vec![0, 1], vec![1]);
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        
+        assert_eq!(pattern.cancel_redundant_edges().commands.len(), 3);
+    }
+    
+    #[test]
+    fn test_measure_z_emits_no_rotation() {
+        let mut pattern = Pattern::new(vec![0], vec![]);
+        pattern.add_command(Command::MZ { node: 0 });
+        
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        let names: Vec<&str> = hugr
+            .nodes
+            .values()
+            .filter_map(|n| match &n.operation {
+                Operation::Custom { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["Measure"]);
+        assert_eq!(hugr.output_node().unwrap().inputs.len(), 1);
+        assert_eq!(pattern.measurement_of(0), Some((Plane::YZ, 0.0)));
+    }
+    
+    #[test]
+    fn test_prune_dangling_ancilla() {
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::N { node: 2 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::C {
+            node: 2,
+            clifford: vec![CliffordGate::H],
+        });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::M {
+            node: 2,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::X {
+            node: 1,
+            domain: [0].into_iter().collect(),
+        });
+        
+        // Ancilla 2 is never entangled and its outcome is unused
+        let pruned = pattern.prune_unused_measurements();
+        assert_eq!(pruned.commands.len(), 4);
+        assert!(pruned.iter().all(|cmd| !cmd.nodes().contains(&2)));
+        
+        // Node 0 is entangled and feeds a correction, so it is kept
+        assert_eq!(pruned.measured_nodes().into_iter().collect::<Vec<_>>(), vec![0]);
+        assert!(convert_graphix_pattern_to_hugr(&pruned).is_ok());
+    }
+    
+    #[test]
+    fn test_add_op_wires_for_cz() {
+        let mut dfg = DfgBuilder::new(vec![HugrType::Qubit; 2]);
+        let inputs = dfg.input_wires.clone();
+        let cz = Operation::Custom {
+            name: "CZ".to_string(),
+            signature: FunctionType::new(
+                vec![HugrType::Qubit, HugrType::Qubit],
+                vec![HugrType::Qubit, HugrType::Qubit],
+            ),
+            extension: "quantum.mbqc".to_string(),
+            args: vec![],
+        };
+        
+        let wires = dfg.add_op_wires(cz, inputs);
+        assert_eq!(wires.len(), 2);
+        assert_eq!(wires[0].node_id, wires[1].node_id);
+        assert_eq!((wires[0].port, wires[1].port), (0, 1));
+    }
+    
+    #[test]
+    fn test_repeat_gadget() {
+        let mut gadget = Pattern::new(vec![0], vec![1]);
+        gadget.add_command(Command::N { node: 1 });
+        gadget.add_command(Command::E { nodes: (0, 1) });
+        gadget.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.25,
+        });
+        gadget.add_command(Command::X {
+            node: 1,
+            domain: [0].into_iter().collect(),
+        });
+        
+        let repeated = gadget.repeat(3).unwrap();
+        assert_eq!(repeated.commands.len(), 3 * gadget.commands.len());
+        assert_eq!(repeated.input_nodes, vec![0]);
+        
+        // Every repetition prepares a fresh node
+        let prepared: HashSet<usize> = repeated
+            .iter()
+            .filter(|cmd| cmd.kind() == CommandKind::N)
+            .flat_map(|cmd| cmd.nodes())
+            .collect();
+        assert_eq!(prepared.len(), 3);
+        assert!(prepared.contains(&repeated.output_nodes[0]));
+        assert!(convert_graphix_pattern_to_hugr(&repeated).is_ok());
+        
+        let mismatched = Pattern::new(vec![0], vec![0, 1]);
+        assert_eq!(
+            mismatched.repeat(2).unwrap_err(),
+            RepeatError::ArityMismatch { outputs: 2, inputs: 1 }
+        );
+    }
+    
+    #[test]
+    fn test_compose_keeps_prepared_inputs_and_classical_outputs() {
+        let mut gadget = Pattern::new(vec![0], vec![1]);
+        gadget.add_command(Command::N { node: 1 });
+        gadget.add_command(Command::E { nodes: (0, 1) });
+        gadget.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.25,
+        });
+        gadget.add_command(Command::X {
+            node: 1,
+            domain: [0].into_iter().collect(),
+        });
+        
+        // Node 5 is an input prepared in |0>, so only node 0 is external
+        let mut next = Pattern::new(vec![0, 5], vec![5]);
+        next.input_states.insert(5, InitState::Zero);
+        next.add_command(Command::E { nodes: (0, 5) });
+        next.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        next.add_command(Command::X {
+            node: 5,
+            domain: [0].into_iter().collect(),
+        });
+        
+        let composed = gadget.compose(&next).unwrap();
+        assert_eq!(composed.input_nodes, vec![0, 2]);
+        assert_eq!(composed.input_states.get(&2), Some(&InitState::Zero));
+        assert_eq!(composed.external_inputs(), vec![0]);
+        assert_eq!(composed.output_nodes, vec![2]);
+        assert_eq!(composed.classical_output_nodes, vec![0, 1]);
+        assert!(convert_graphix_pattern_to_hugr(&composed).is_ok());
+        
+        // An explicit list on one side no longer hides the other's outcomes
+        gadget.classical_output_nodes = vec![0];
+        let composed = gadget.compose(&next).unwrap();
+        assert_eq!(composed.classical_output_nodes, vec![0, 1]);
+        
+        let mut prepared_only = Pattern::new(vec![0], vec![0]);
+        prepared_only.input_states.insert(0, InitState::Plus);
+        assert_eq!(
+            gadget.compose(&prepared_only).unwrap_err(),
+            RepeatError::ArityMismatch { outputs: 1, inputs: 0 }
+        );
+    }
+    
+    #[test]
+    fn test_flow_corrections_match_hand_written() {
+        // Linear cluster 0-1-2-3 without corrections
+        let mut open = Pattern::new(vec![0], vec![3]);
+        for node in 1..4 {
+            open.add_command(Command::N { node });
+        }
+        for node in 1..4 {
+            open.add_command(Command::E {
+                nodes: (node - 1, node),
+            });
+        }
+        for node in 0..3 {
+            open.add_command(Command::M {
+                node,
+                plane: Plane::XY,
+                angle: 0.1 * node as f64,
+            });
+        }
+        
+        let flow = open.causal_flow().unwrap();
+        assert_eq!(flow.successor, [(0, 1), (1, 2), (2, 3)].into_iter().collect());
+        
+        let mut expected = Pattern::new(vec![0], vec![3]);
+        expected.commands = open.commands[..6].to_vec();
+        for node in 0..3 {
+            expected.add_command(open.commands[6 + node].clone());
+            expected.add_command(Command::X {
+                node: node + 1,
+                domain: [node].into_iter().collect(),
+            });
+            if node + 2 < 4 {
+                expected.add_command(Command::Z {
+                    node: node + 2,
+                    domain: [node].into_iter().collect(),
+                });
+            }
+        }
+        
+        let runnable = open.with_flow_corrections().unwrap();
+        assert_eq!(
+            serde_json::to_string(&runnable.commands).unwrap(),
+            serde_json::to_string(&expected.commands).unwrap()
+        );
+        assert_eq!(
+            convert_with_flow(&open).unwrap().structural_hash(),
+            convert_graphix_pattern_to_hugr(&expected).unwrap().structural_hash()
+        );
+        
+        // A measured node with no unique neighbour to correct it
+        let mut no_flow = Pattern::new(vec![0, 1], vec![2]);
+        no_flow.add_command(Command::N { node: 2 });
+        no_flow.add_command(Command::E { nodes: (0, 2) });
+        no_flow.add_command(Command::E { nodes: (1, 2) });
+        assert!(matches!(convert_with_flow(&no_flow), Err(ConversionError::NoFlow)));
+    }
+    
+    #[test]
+    fn test_deferred_measurement_matches_distribution() {
+        let mut pattern = teleportation_pattern();
+        pattern.commands[6] = Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.7,
+        };
+        let input = [Complex::new(0.6, 0.0), Complex::new(0.0, 0.8)];
+        
+        let standard = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        let config = ConverterConfig {
+            defer_measurements: true,
+            ..Default::default()
+        };
+        let deferred = GraphixToHugrConverter::with_config(config.clone())
+            .convert(&pattern)
+            .unwrap();
+        
+        // Corrections became controlled gates ahead of a final measurement layer
+        let names: Vec<&str> = {
+            let mut ids: Vec<_> = deferred.nodes.keys().collect();
+            ids.sort();
+            ids.into_iter()
+                .filter_map(|id| match &deferred.nodes[id].operation {
+                    Operation::Custom { name, .. } => Some(name.as_str()),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert!(!names.iter().any(|name| name.starts_with("Conditional")));
+        let first_measure = names.iter().position(|&name| name == "Measure").unwrap();
+        assert!(names[..first_measure].contains(&"CX"));
+        assert!(names[first_measure..].iter().all(|&name| name == "Measure"));
+        assert!(deferred
+            .validate_against_registry(&ExtensionRegistry::for_config(&config))
+            .is_ok());
+        
+        let expected = sim::hugr_distribution(&standard, &input).unwrap();
+        let actual = sim::hugr_distribution(&deferred, &input).unwrap();
+        assert_eq!(expected.keys().collect::<Vec<_>>(), actual.keys().collect::<Vec<_>>());
+        for (outcome, p) in &expected {
+            assert!((p - actual[outcome]).abs() < 1e-9, "{:?}: {} vs {}", outcome, p, actual[outcome]);
+        }
+    }
+    
+    #[test]
+    fn test_pattern_editing_is_reflected_in_conversion() {
+        let count_named = |hugr: &Hugr, wanted: &str| {
+            hugr.nodes
+                .values()
+                .filter(|node| node.operation.name() == wanted)
+                .count()
+        };
+        let mut pattern = teleportation_pattern();
+        let before = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        
+        // Insert an S on the output qubit after entanglement
+        pattern.insert_command(5, Command::C {
+            node: 2,
+            clifford: vec![CliffordGate::S],
+        });
+        assert_eq!(pattern.commands[5].kind(), CommandKind::C);
+        let inserted = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        assert_eq!(count_named(&inserted, "S"), count_named(&before, "S") + 1);
+        
+        // Swap it for a Z, then remove it again
+        let old = pattern.replace_command(5, Command::C {
+            node: 2,
+            clifford: vec![CliffordGate::Z],
+        });
+        assert_eq!(old, Command::C {
+            node: 2,
+            clifford: vec![CliffordGate::S],
+        });
+        let replaced = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        assert_eq!(count_named(&replaced, "S"), count_named(&before, "S"));
+        assert_eq!(count_named(&replaced, "Z"), count_named(&before, "Z") + 1);
+        
+        pattern.remove_command(5);
+        assert_eq!(pattern, teleportation_pattern());
+    }
+    
+    #[test]
+    fn test_result_schema_labels_measurements() {
+        let (hugr, schema) = GraphixToHugrConverter::new()
+            .convert_with_schema(&teleportation_pattern())
+            .unwrap();
+        
+        let output_types = hugr.output_types().unwrap().len();
+        assert_eq!(schema.entries.len(), 2);
+        assert_eq!(schema.entries[0].port, 1);
+        assert_eq!(schema.entries[1].port, output_types - 1);
+        let labelled: Vec<(usize, Option<Plane>)> = schema
+            .entries
+            .iter()
+            .map(|entry| (entry.node, entry.basis.map(|(plane, _)| plane)))
+            .collect();
+        assert_eq!(labelled, vec![(0, Some(Plane::XY)), (1, Some(Plane::XY))]);
+        
+        let json: serde_json::Value = serde_json::from_str(&schema.to_json()).unwrap();
+        assert_eq!(json["entries"][1]["node"], 1);
+        assert_eq!(json["entries"][1]["basis"][0], "XY");
+    }
+    
+    #[test]
+    fn test_report_warns_on_unused_measurement() {
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::N { node: 2 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::E { nodes: (1, 2) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::MZ { node: 2 });
+        pattern.add_command(Command::X {
+            node: 1,
+            domain: [0].into_iter().collect(),
+        });
+        // Node 2's outcome is neither a correction input nor returned
+        pattern.classical_output_nodes = vec![0];
+        
+        let (hugr, warnings) = GraphixToHugrConverter::new()
+            .convert_with_report(&pattern)
+            .unwrap();
+        assert!(!hugr.is_empty());
+        assert_eq!(warnings, vec![ConversionWarning::UnusedMeasurement(2)]);
+        
+        let (_, warnings) = GraphixToHugrConverter::new()
+            .convert_with_report(&teleportation_pattern())
+            .unwrap();
+        assert!(warnings.is_empty());
+    }
+    
+    #[test]
+    fn test_open_graph_json_of_linear_cluster() {
+        let mut pattern = Pattern::new(vec![0], vec![4]);
+        for node in 1..5 {
+            pattern.add_command(Command::N { node });
+            pattern.add_command(Command::E {
+                nodes: (node, node - 1),
+            });
+        }
+        for node in 0..4 {
+            pattern.add_command(Command::M {
+                node,
+                plane: Plane::XY,
+                angle: 0.5,
+            });
+        }
+        
+        let json: serde_json::Value = serde_json::from_str(&pattern.to_open_graph_json()).unwrap();
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 5);
+        assert_eq!(json["edges"].as_array().unwrap().len(), 4);
+        assert_eq!(json["edges"][0], serde_json::json!([0, 1]));
+        assert_eq!(json["inputs"], serde_json::json!([0]));
+        assert_eq!(json["outputs"], serde_json::json!([4]));
+        assert_eq!(json["measurements"].as_object().unwrap().len(), 4);
+        assert_eq!(json["measurements"]["3"]["plane"], "XY");
+    }
+    
+    #[test]
+    fn test_open_graph_json_round_trip() {
+        let pattern = teleportation_pattern();
+        let imported = Pattern::from_open_graph_json(&pattern.to_open_graph_json()).unwrap();
+        
+        let edges = |p: &Pattern| -> HashSet<(usize, usize)> {
+            p.entanglements().map(|(a, b)| (a.min(b), a.max(b))).collect()
+        };
+        assert_eq!(edges(&imported), edges(&pattern));
+        assert_eq!(imported.input_nodes, pattern.input_nodes);
+        assert_eq!(imported.output_nodes, pattern.output_nodes);
+        for node in pattern.measured_nodes() {
+            assert_eq!(imported.measurement_of(node), pattern.measurement_of(node));
+        }
+        // Measurements follow the flow: 0 before 1
+        let measured: Vec<usize> = imported
+            .commands_of_kind(CommandKind::M)
+            .map(|cmd| cmd.nodes()[0])
+            .collect();
+        assert_eq!(measured, vec![0, 1]);
+        
+        let dangling = r#"{"nodes": [0], "edges": [[0, 1]], "inputs": [], "outputs": [], "measurements": {}}"#;
+        assert!(matches!(
+            Pattern::from_open_graph_json(dangling),
+            Err(ImportError::UnknownNode(1))
+        ));
+        assert!(matches!(Pattern::from_open_graph_json("[]"), Err(ImportError::Json(_))));
+    }
+    
+    #[test]
+    fn test_classical_input_drives_correction() {
+        let mut pattern = Pattern::new(vec![0], vec![0]);
+        pattern.classical_input_nodes = vec![5];
+        pattern.add_command(Command::X {
+            node: 0,
+            domain: [5].into_iter().collect(),
+        });
+        
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        let input = hugr.input_node().unwrap();
+        assert_eq!(hugr.input_types().unwrap(), &[HugrType::Qubit, HugrType::Bool]);
+        
+        let correction = hugr
+            .nodes
+            .values()
+            .find(|node| matches!(&node.operation, Operation::Custom { name, .. } if name == "ConditionalX"))
+            .unwrap();
+        assert_eq!(correction.inputs, vec![Wire::new(input.id, 1), Wire::new(input.id, 0)]);
+    }
+    
+    #[test]
+    fn test_correction_on_unmeasured_node_errors() {
+        let mut pattern = teleportation_pattern();
+        pattern.add_command(Command::Z {
+            node: 2,
+            domain: [0, 7].into_iter().collect(),
+        });
+        
+        assert!(matches!(
+            convert_graphix_pattern_to_hugr(&pattern),
+            Err(ConversionError::MissingMeasurement(7))
+        ));
+    }
+    
+    #[test]
+    fn test_operation_names() {
+        let signature = FunctionType::new(vec![], vec![]);
+        let ops = [
+            Operation::Input { types: vec![] },
+            Operation::Output { types: vec![] },
+            Operation::from(CliffordGate::H),
+            Operation::Const {
+                value: ConstValue::Bool(true),
+            },
+            Operation::LoadConst { const_node: 0 },
+            Operation::DFG { signature },
+        ];
+        let names: Vec<&str> = ops.iter().map(|op| op.name()).collect();
+        assert_eq!(names, vec!["Input", "Output", "H", "Const", "LoadConst", "DFG"]);
+    }
+    
+    #[test]
+    fn test_and_controlled_correction() {
+        let mut pattern = Pattern::new(vec![0, 1, 2], vec![2]);
+        pattern.add_command(Command::MZ { node: 0 });
+        pattern.add_command(Command::MZ { node: 1 });
+        pattern.add_command(Command::XControlled {
+            node: 2,
+            controls: [0, 1].into_iter().collect(),
+            combiner: ClassicalOp::And,
+        });
+        
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        let correction = hugr
+            .nodes
+            .values()
+            .find(|node| node.operation.name() == "ConditionalX")
+            .unwrap();
+        let condition = hugr.get_node(correction.inputs[0].node_id).unwrap();
+        assert_eq!(condition.operation.name(), "AND");
+        
+        let gates = to_gate_list(&pattern).unwrap();
+        assert_eq!(gates.last().unwrap().to_string(), "if AND {0, 1} X q2");
+    }
+    
+    /// Interpret random steps as a valid pattern. Each step is an opcode and
+    /// two selectors into the live/measured nodes, plus an angle.
+    fn arbitrary_pattern(n_inputs: usize, steps: &[(u8, u8, u8, f64)]) -> Pattern {
+        let mut live: Vec<usize> = (0..n_inputs).collect();
+        let mut measured: Vec<usize> = Vec::new();
+        let mut pattern = Pattern::new(live.clone(), vec![]);
+        let mut next = n_inputs;
+        
+        for &(op, a, b, angle) in steps {
+            let pick = |sel: u8| live[sel as usize % live.len()];
+            match op % 5 {
+                _ if live.is_empty() || op % 5 == 0 => {
+                    pattern.add_command(Command::N { node: next });
+                    live.push(next);
+                    next += 1;
+                }
+                1 if pick(a) != pick(b) => pattern.add_command(Command::E {
+                    nodes: (pick(a), pick(b)),
+                }),
+                2 if live.len() > 1 => {
+                    let node = pick(a);
+                    let plane = [Plane::XY, Plane::YZ, Plane::XZ][b as usize % 3];
+                    pattern.add_command(Command::M { node, plane, angle });
+                    live.retain(|&n| n != node);
+                    measured.push(node);
+                }
+                3 => {
+                    let node = pick(a);
+                    let domain = measured
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| (b >> (i % 8)) & 1 == 1)
+                        .map(|(_, &n)| n)
+                        .collect();
+                    pattern.add_command(if b % 2 == 0 {
+                        Command::X { node, domain }
+                    } else {
+                        Command::Z { node, domain }
+                    });
+                }
+                _ => pattern.add_command(Command::C {
+                    node: pick(a),
+                    clifford: vec![CliffordGate::ALL[b as usize % CliffordGate::ALL.len()]],
+                }),
+            }
+        }
+        pattern.output_nodes = live;
+        pattern
+    }
+    
+    use proptest::prelude::any;
+    
+    proptest::proptest! {
+        #[test]
+        fn prop_conversion_is_deterministic(
+            n_inputs in 0usize..3,
+            steps in proptest::collection::vec(
+                (any::<u8>(), any::<u8>(), any::<u8>(), -3.0f64..3.0),
+                0..24,
+            ),
+        ) {
+            let pattern = arbitrary_pattern(n_inputs, &steps);
+            let first = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+            let second = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+            proptest::prop_assert!(first.is_isomorphic(&second));
+            proptest::prop_assert_eq!(first.to_spec_json(), second.to_spec_json());
+        }
+    }
+    
+    #[test]
+    fn test_is_isomorphic_ignores_renumbering() {
+        let hugr = convert_graphix_pattern_to_hugr(&teleportation_pattern()).unwrap();
+        
+        // Reverse the node ids
+        let last = hugr.next_node_id - 1;
+        let mut renumbered = Hugr::new();
+        for node in hugr.nodes.values() {
+            let mut node = node.clone();
+            node.id = last - node.id;
+            for wire in node.inputs.iter_mut().chain(node.outputs.iter_mut()) {
+                wire.node_id = last - wire.node_id;
+            }
+            if let Operation::LoadConst { const_node } = &mut node.operation {
+                *const_node = last - *const_node;
+            }
+            renumbered.nodes.insert(node.id, node);
+        }
+        
+        assert!(hugr.is_isomorphic(&renumbered));
+        assert!(renumbered.is_isomorphic(&hugr));
+        let ghz = convert_graphix_pattern_to_hugr(&Pattern::ghz(3)).unwrap();
+        assert!(!hugr.is_isomorphic(&ghz));
+    }
+    
+    #[test]
+    fn test_t_count() {
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::measure_pi(0, Plane::XY, PiFraction::new(1, 4)));
+        pattern.add_command(Command::X {
+            node: 1,
+            domain: [0].into_iter().collect(),
+        });
+        assert_eq!(convert_graphix_pattern_to_hugr(&pattern).unwrap().t_count(), 1);
+        
+        // π/2 rotations are Clifford
+        pattern.commands[2] = Command::measure_pi(0, Plane::XY, PiFraction::new(1, 2));
+        assert_eq!(convert_graphix_pattern_to_hugr(&pattern).unwrap().t_count(), 0);
+        
+        let clifford_only = convert_graphix_pattern_to_hugr(&teleportation_pattern()).unwrap();
+        assert_eq!(clifford_only.t_count(), 0);
+    }
+    
+    #[test]
+    fn test_nodes_carry_source_metadata() {
+        let hugr = convert_graphix_pattern_to_hugr(&teleportation_pattern()).unwrap();
+        
+        // Command 5 is C(0, [H])
+        let h = hugr
+            .nodes
+            .values()
+            .find(|node| {
+                node.operation.name() == "H" && node.get_meta("source_command") == Some("5")
+            })
+            .unwrap();
+        assert_eq!(h.get_meta("pattern_node"), Some("0"));
+        
+        let cz = hugr
+            .nodes
+            .values()
+            .find(|node| node.get_meta("source_command") == Some("3"))
+            .unwrap();
+        assert_eq!(cz.operation.name(), "CZ");
+        assert_eq!(cz.get_meta("pattern_node"), Some("1,2"));
+        assert!(hugr.input_node().unwrap().metadata.is_empty());
+    }
+    
+    #[test]
+    fn test_fused_clifford_emits_euler_rotations() {
+        use CliffordGate::*;
+        
+        let config = ConverterConfig {
+            fuse_cliffords: true,
+            ..Default::default()
+        };
+        let check = |clifford: Vec<CliffordGate>| {
+            let mut pattern = Pattern::new(vec![0], vec![0]);
+            pattern.add_command(Command::C { node: 0, clifford });
+            let hugr = GraphixToHugrConverter::with_config(config.clone())
+                .convert(&pattern)
+                .unwrap();
+            
+            let mut ids: Vec<_> = hugr.nodes.keys().cloned().collect();
+            ids.sort();
+            let gates: Vec<sim::Gate2x2> = ids
+                .iter()
+                .filter_map(|id| match &hugr.nodes[id].operation {
+                    Operation::Custom { name, args, .. } if !args.is_empty() => {
+                        sim::rotation_matrix(name, args[0])
+                    }
+                    op @ Operation::Custom { .. } => CliffordGate::try_from(op).ok().map(sim::clifford_matrix),
+                    _ => None,
+                })
+                .collect();
+            assert!(gates.len() <= 3);
+            
+            // Multiply the emitted gates back together
+            let identity = vec![vec![Complex::ONE, Complex::ZERO], vec![Complex::ZERO, Complex::ONE]];
+            let product = gates.iter().fold(identity, |acc: sim::Matrix, g| {
+                (0..2)
+                    .map(|i| (0..2).map(|j| g[i][0] * acc[0][j] + g[i][1] * acc[1][j]).collect())
+                    .collect()
+            });
+            assert!(sim::unitary_close(&product, &sim::pattern_unitary(&pattern).unwrap(), 1e-9));
+        };
+        
+        check(vec![H, S, H, S, Z, X]);
+        for a in CliffordGate::ALL {
+            for b in CliffordGate::ALL {
+                check(vec![a, b]);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_soft_measurement_yields_bool_and_likelihood() {
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::MSoft {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.3,
+        });
+        pattern.add_command(Command::X {
+            node: 1,
+            domain: [0].into_iter().collect(),
+        });
+        
+        let mut converter = GraphixToHugrConverter::new();
+        let hugr = converter.convert(&pattern).unwrap();
+        let soft = hugr
+            .nodes
+            .values()
+            .find(|node| node.operation.name() == "MeasureSoft")
+            .unwrap();
+        let Operation::Custom { signature, .. } = &soft.operation else {
+            unreachable!()
+        };
+        assert_eq!(signature.outputs, vec![HugrType::Bool, HugrType::Float64]);
+        assert_eq!(soft.outputs.len(), 2);
+        assert_eq!(converter.soft_wire(0), Some(soft.out(1)));
+        
+        // The hard bit drives the correction
+        let correction = hugr
+            .nodes
+            .values()
+            .find(|node| node.operation.name() == "ConditionalX")
+            .unwrap();
+        assert_eq!(correction.inputs[0], soft.out(0));
+        assert!(hugr.validate_against_registry(&ExtensionRegistry::standard()).is_ok());
+    }
+    
+    #[test]
+    fn test_is_deterministic() {
+        assert!(teleportation_pattern().is_deterministic());
+        
+        // Node 0 is corrected by node 1's outcome, which is only measured later
+        let mut pattern = Pattern::new(vec![0], vec![2]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::N { node: 2 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::E { nodes: (1, 2) });
+        pattern.add_command(Command::Z {
+            node: 0,
+            domain: [1].into_iter().collect(),
+        });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::M {
+            node: 1,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        assert!(pattern.causal_flow().is_some());
+        assert!(!pattern.is_deterministic());
+        
+        // No causal flow: two measured inputs share their only neighbour
+        let mut no_flow = Pattern::new(vec![0, 1], vec![2]);
+        no_flow.add_command(Command::N { node: 2 });
+        no_flow.add_command(Command::E { nodes: (0, 2) });
+        no_flow.add_command(Command::E { nodes: (1, 2) });
+        assert!(!no_flow.is_deterministic());
+    }
+    
+    #[test]
+    fn test_connectivity_routes_entanglement_with_swaps() {
+        use crate::testutil::{assert_op_count, find_ops};
+        
+        let mut pattern = Pattern::new(vec![], vec![0, 1, 2]);
+        for node in 0..3 {
+            pattern.add_command(Command::N { node });
+        }
+        pattern.add_command(Command::E { nodes: (0, 2) });
+        pattern.add_command(Command::C {
+            node: 2,
+            clifford: vec![CliffordGate::H],
+        });
+        let config = ConverterConfig {
+            connectivity: Some(vec![(0, 1), (1, 2)]),
+            ..Default::default()
+        };
+        let routed = GraphixToHugrConverter::with_config(config.clone())
+            .convert(&pattern)
+            .unwrap();
+        
+        // Swap 0 next to 2, entangle, swap back
+        assert_op_count(&routed, "SWAP", 2);
+        assert_op_count(&routed, "CZ", 1);
+        let cz = find_ops(&routed, "CZ")[0];
+        assert_eq!(routed.nodes[&cz.inputs[0].node_id].operation.name(), "SWAP");
+        assert!(routed
+            .validate_against_registry(&ExtensionRegistry::for_config(&config))
+            .is_ok());
+        
+        // Routing leaves the computation unchanged
+        let direct = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        assert_eq!(
+            sim::hugr_distribution(&routed, &[Complex::ONE]).unwrap(),
+            sim::hugr_distribution(&direct, &[Complex::ONE]).unwrap()
+        );
+        
+        let mut unreachable = pattern.clone();
+        unreachable.commands.insert(3, Command::MZ { node: 1 });
+        assert!(matches!(
+            GraphixToHugrConverter::with_config(config).convert(&unreachable),
+            Err(ConversionError::Unroutable { nodes: (0, 2) })
+        ));
+    }
+    
+    #[test]
+    fn test_per_category_extensions() {
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.5,
+        });
+        pattern.add_command(Command::X {
+            node: 1,
+            domain: [0].into_iter().collect(),
+        });
+        let config = ConverterConfig {
+            rotation_extension: Some("tket.quantum".to_string()),
+            measurement_extension: Some("measure.ext".to_string()),
+            pauli_extension: Some("pauli.ext".to_string()),
+            ..Default::default()
+        };
+        let hugr = GraphixToHugrConverter::with_config(config.clone())
+            .convert(&pattern)
+            .unwrap();
+        
+        let extension_of = |op_name: &str| {
+            hugr.nodes
+                .values()
+                .find_map(|node| match &node.operation {
+                    Operation::Custom { name, extension, .. } if name == op_name => {
+                        Some(extension.clone())
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        };
+        assert_eq!(extension_of("Rz"), "tket.quantum");
+        assert_eq!(extension_of("Measure"), "measure.ext");
+        assert_eq!(extension_of("ConditionalX"), "pauli.ext");
+        assert_eq!(extension_of("CZ"), "quantum.mbqc");
+        assert!(hugr
+            .validate_against_registry(&ExtensionRegistry::for_config(&config))
+            .is_ok());
+        assert!(hugr
+            .validate_against_registry(&ExtensionRegistry::standard())
+            .is_err());
+    }
+    
+    #[test]
+    fn test_bool_to_sum() {
+        let mut dfg = DfgBuilder::new(vec![HugrType::Bool]);
+        let condition = dfg.input_wires[0];
+        let predicate = dfg.bool_to_sum(condition);
+        dfg.set_outputs(vec![predicate]).unwrap();
+        let hugr = dfg.finish();
+        
+        let Operation::Custom { signature, .. } = &hugr.nodes[&predicate.node_id].operation else {
+            unreachable!()
+        };
+        let sum = HugrType::Sum(vec![vec![], vec![]]);
+        assert_eq!(*signature, FunctionType::new(vec![HugrType::Bool], vec![sum.clone()]));
+        assert_eq!(HugrType::unit_sum(2), sum);
+        assert_eq!(hugr.output_types().unwrap(), &[sum]);
+        assert!(hugr.to_spec_json().contains("Sum([], [])"));
+    }
+    
+    #[test]
+    fn test_packed_classical_outputs() {
+        let mut pattern = Pattern::new(vec![0, 1, 2, 3], vec![3]);
+        for node in 0..3 {
+            pattern.add_command(Command::MZ { node });
+        }
+        let config = ConverterConfig {
+            pack_classical_outputs: true,
+            ..Default::default()
+        };
+        let hugr = GraphixToHugrConverter::with_config(config.clone())
+            .convert(&pattern)
+            .unwrap();
+        
+        let pack = hugr
+            .nodes
+            .values()
+            .find(|node| node.operation.name() == "Pack")
+            .unwrap();
+        let Operation::Custom { signature, .. } = &pack.operation else {
+            unreachable!()
+        };
+        assert_eq!(signature.inputs, vec![HugrType::Bool; 3]);
+        assert_eq!(signature.outputs, vec![HugrType::Int(3)]);
+        assert_eq!(pack.inputs.len(), 3);
+        
+        // One qubit output followed by the single packed output
+        let outputs = &hugr.output_node().unwrap().inputs;
+        assert_eq!(outputs[1], pack.out(0));
+        assert_eq!(hugr.output_types().unwrap(), &[HugrType::Qubit, HugrType::Int(3)]);
+        assert!(hugr
+            .validate_against_registry(&ExtensionRegistry::for_config(&config))
+            .is_ok());
+        
+        // The schema points every outcome at a bit of the packed port
+        let (hugr, schema) = GraphixToHugrConverter::with_config(config)
+            .convert_with_schema(&pattern)
+            .unwrap();
+        let output_types = hugr.output_types().unwrap();
+        for (i, entry) in schema.entries.iter().enumerate() {
+            assert_eq!((entry.port, entry.bit, entry.node), (1, Some(i), i));
+            assert_eq!(output_types[entry.port], HugrType::Int(3));
+        }
+        
+        let (hugr, schema) = GraphixToHugrConverter::new().convert_with_schema(&pattern).unwrap();
+        for entry in &schema.entries {
+            assert_eq!(entry.bit, None);
+            assert_eq!(hugr.output_types().unwrap()[entry.port], HugrType::Bool);
+        }
+    }
+    
+    #[test]
+    fn test_purely_classical_pattern_outputs_bools() {
+        let mut pattern = Pattern::new(vec![], vec![]);
+        pattern.add_command(Command::N { node: 0 });
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::MZ { node: 1 });
+        
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        assert_eq!(hugr.input_types().unwrap(), &[]);
+        assert_eq!(hugr.output_types().unwrap(), &[HugrType::Bool, HugrType::Bool]);
+        
+        // Mixed outputs keep their own types
+        let teleport = convert_graphix_pattern_to_hugr(&teleportation_pattern()).unwrap();
+        assert_eq!(
+            teleport.output_types().unwrap(),
+            &[HugrType::Qubit, HugrType::Bool, HugrType::Bool]
+        );
+    }
+    
+    #[test]
+    fn test_unsupported_under_config() {
+        let deferring = || {
+            GraphixToHugrConverter::with_config(ConverterConfig {
+                defer_measurements: true,
+                ..Default::default()
+            })
+        };
+        
+        let mut and_correction = Pattern::new(vec![0, 1, 2], vec![2]);
+        and_correction.add_command(Command::MZ { node: 0 });
+        and_correction.add_command(Command::MZ { node: 1 });
+        and_correction.add_command(Command::XControlled {
+            node: 2,
+            controls: [0, 1].into_iter().collect(),
+            combiner: ClassicalOp::And,
+        });
+        assert!(convert_graphix_pattern_to_hugr(&and_correction).is_ok());
+        assert!(matches!(
+            deferring().convert(&and_correction),
+            Err(ConversionError::UnsupportedUnderConfig {
+                command_kind: CommandKind::XControlled,
+                ..
+            })
+        ));
+        
+        let mut soft = Pattern::new(vec![0], vec![]);
+        soft.add_command(Command::MSoft {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        assert!(matches!(
+            deferring().convert(&soft),
+            Err(ConversionError::UnsupportedUnderConfig {
+                command_kind: CommandKind::MSoft,
+                ..
+            })
+        ));
+    }
+    
+    #[test]
+    fn test_teleportation_correction_schedule() {
+        let schedule = teleportation_pattern().correction_schedule();
+        
+        // Z waits for M0, X for M1
+        assert_eq!(
+            schedule,
+            vec![
+                vec![(2, Pauli::Z, [0].into_iter().collect())],
+                vec![(2, Pauli::X, [1].into_iter().collect())],
+            ]
+        );
+    }
+    
+    #[test]
+    fn test_command_kind_iterators() {
+        let pattern = teleportation_pattern();
+        let measurements: Vec<&Command> = pattern.commands_of_kind(CommandKind::M).collect();
+        assert_eq!(measurements.len(), 2);
+        assert!(measurements.iter().all(|cmd| cmd.kind() == CommandKind::M));
+        
+        let edges: Vec<(usize, usize)> = pattern.entanglements().collect();
+        assert_eq!(edges, vec![(1, 2), (0, 1)]);
+    }
+    
+    #[test]
+    fn test_config_builder_changes_single_field() {
+        let config = ConverterConfig::default().with_measurement_extension("measure.ext");
+        assert_eq!(config.measurement_ext(), "measure.ext");
+        assert_eq!(config.pauli_ext(), "quantum.mbqc");
+        assert!(!config.nondestructive_measure && config.connectivity.is_none());
+        
+        let registry = ExtensionRegistry::for_config(&config);
+        let hugr = GraphixToHugrConverter::with_config(config)
+            .convert(&teleportation_pattern())
+            .unwrap();
+        let extensions: BTreeSet<(&str, &str)> = hugr
+            .nodes
+            .values()
+            .filter_map(|node| match &node.operation {
+                Operation::Custom { name, extension, .. } => Some((name.as_str(), extension.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert!(extensions.contains(&("Measure", "measure.ext")));
+        assert!(extensions.contains(&("ConditionalX", "quantum.mbqc")));
+        assert!(hugr.validate_against_registry(&registry).is_ok());
+    }
+    
+    #[test]
+    fn test_bind_symbolic_measurement_angle() {
+        let mut pattern = teleportation_pattern();
+        pattern.angle_parameters.insert(0, "theta".to_string());
+        let mut hugr = GraphixToHugrConverter::new().convert(&pattern).unwrap();
+        
+        let rotation_args = |hugr: &Hugr| -> Vec<f64> {
+            hugr.nodes
+                .values()
+                .filter_map(|node| match &node.operation {
+                    Operation::Custom { name, args, .. } if name == "Rz" => Some(args[0]),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(rotation_args(&hugr), vec![0.0]);
+        
+        assert_eq!(hugr.bind_parameter("phi", 1.0), 0);
+        assert_eq!(hugr.bind_parameter("theta", std::f64::consts::FRAC_PI_4), 1);
+        assert_eq!(rotation_args(&hugr), vec![-std::f64::consts::FRAC_PI_4]);
+        assert!(hugr.nodes.values().all(|node| node.get_meta("parameter").is_none()));
+    }
+    
+    #[test]
+    fn test_computed_angle_shifts_with_prior_outcome() {
+        use crate::testutil::{assert_op_count, assert_wire_feeds, find_ops};
+        use std::f64::consts::FRAC_PI_2;
+        
+        let mut pattern = Pattern::new(vec![0], vec![]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::MAdaptive {
+            node: 1,
+            plane: Plane::XY,
+            angle: Angle::Computed {
+                base: 0.3,
+                shift_domain: [0].into_iter().collect(),
+                shift: FRAC_PI_2,
+            },
+        });
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        
+        assert_op_count(&hugr, "Rz", 0);
+        assert_op_count(&hugr, "RzWire", 1);
+        let first_measure = find_ops(&hugr, "Measure")[0].id;
+        assert_wire_feeds(&hugr, first_measure, 0, "BoolToFloat");
+        assert_wire_feeds(&hugr, find_ops(&hugr, "BoolToFloat")[0].id, 0, "FMul");
+        assert_wire_feeds(&hugr, find_ops(&hugr, "FMul")[0].id, 0, "FAdd");
+        assert_wire_feeds(&hugr, find_ops(&hugr, "FAdd")[0].id, 0, "RzWire");
+        
+        let mut constants: Vec<f64> = hugr
+            .nodes
+            .values()
+            .filter_map(|node| match node.operation {
+                Operation::Const {
+                    value: ConstValue::Float(value),
+                } => Some(value),
+                _ => None,
+            })
+            .collect();
+        constants.sort_by(f64::total_cmp);
+        assert_eq!(constants, vec![-FRAC_PI_2, -0.3]);
+        assert!(hugr.validate_against_registry(&ExtensionRegistry::standard()).is_ok());
+    }
+    
+    #[test]
+    fn test_lint_reports_identity_clifford() {
+        let mut pattern = Pattern::new(vec![0], vec![0]);
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::I, CliffordGate::I],
+        });
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::H],
+        });
+        
+        let lints = pattern.lint();
+        assert_eq!(lints, vec![Lint::IdentityClifford { command: 0, node: 0 }]);
+        assert_eq!(lints[0].to_string(), "command 0: identity Clifford on node 0 can be removed");
+        
+        let lints = teleportation_pattern().lint();
+        assert_eq!(
+            lints,
+            vec![
+                Lint::XBasisMeasurement { command: 6, node: 0 },
+                Lint::XBasisMeasurement { command: 7, node: 1 },
+            ]
+        );
+    }
+    
+    #[test]
+    fn test_measurement_partial_order() {
+        // Nodes 1 and 2 are measured independently; node 3's angle depends
+        // on node 1
+        let mut pattern = Pattern::new(vec![], vec![]);
+        for node in 1..4 {
+            pattern.add_command(Command::N { node });
+        }
+        for node in [1, 2] {
+            pattern.add_command(Command::M {
+                node,
+                plane: Plane::XY,
+                angle: 0.5,
+            });
+        }
+        pattern.add_command(Command::MAdaptive {
+            node: 3,
+            plane: Plane::XY,
+            angle: Angle::Computed {
+                base: 0.0,
+                shift_domain: [1].into_iter().collect(),
+                shift: std::f64::consts::FRAC_PI_2,
+            },
+        });
+        
+        let order = pattern.measurement_partial_order();
+        assert_eq!(order.nodes().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(!order.are_comparable(1, 2));
+        assert!(!order.are_comparable(2, 3));
+        assert!(order.precedes(1, 3) && !order.precedes(3, 1));
+        assert_eq!(order.topological_order(), vec![1, 2, 3]);
+        
+        // In teleportation neither measurement waits for the other
+        let order = teleportation_pattern().measurement_partial_order();
+        assert!(!order.are_comparable(0, 1));
+    }
+    
+    #[test]
+    fn test_cloned_converter_is_an_independent_checkpoint() {
+        let pattern = teleportation_pattern();
+        let mut converter = GraphixToHugrConverter::new();
+        converter.begin(&pattern);
+        for cmd in &pattern.commands[..6] {
+            converter.push_command(cmd).unwrap();
+        }
+        
+        let mut checkpoint = converter.clone();
+        let n_nodes = checkpoint.in_progress().len();
+        for cmd in &pattern.commands[6..] {
+            converter.push_command(cmd).unwrap();
+        }
+        assert!(converter.in_progress().len() > n_nodes);
+        assert_eq!(checkpoint.in_progress().len(), n_nodes);
+        
+        // The finished original matches a one-shot conversion, while the
+        // checkpoint finishes without the measurements
+        let finished = converter.finish(&pattern).unwrap();
+        let expected = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        assert_eq!(finished.to_spec_json(), expected.to_spec_json());
+        let rolled_back = checkpoint.finish(&pattern).unwrap();
+        assert!(!rolled_back
+            .nodes
+            .values()
+            .any(|node| node.operation.name() == "Measure"));
+    }
+    
+    #[test]
+    fn test_explicit_qubit_scopes_bracket_each_ancilla() {
+        use crate::testutil::{assert_op_count, assert_wire_feeds, find_ops};
+        
+        let config = ConverterConfig::default().with_explicit_qubit_scopes(true);
+        let registry = ExtensionRegistry::for_config(&config);
+        let hugr = GraphixToHugrConverter::with_config(config)
+            .convert(&teleportation_pattern())
+            .unwrap();
+        assert!(hugr.validate_against_registry(&registry).is_ok());
+        
+        // Ancillas 1 and 2 are allocated; the measured qubits 0 and 1 are
+        // freed, while output 2 is returned instead
+        assert_op_count(&hugr, "QAlloc", 2);
+        assert_op_count(&hugr, "QFree", 2);
+        for alloc in find_ops(&hugr, "QAlloc") {
+            assert_wire_feeds(&hugr, alloc.id, 0, "PrepareQubit");
+        }
+        let scope_of = |node: usize| {
+            let alloc = find_ops(&hugr, "QAlloc")
+                .into_iter()
+                .find(|op| op.get_meta("pattern_node") == Some(node.to_string().as_str()))
+                .map(|op| op.id);
+            let free = find_ops(&hugr, "QFree")
+                .into_iter()
+                .find(|op| {
+                    hugr.get_node(op.inputs[0].node_id)
+                        .and_then(|measure| measure.get_meta("pattern_node"))
+                        == Some(node.to_string().as_str())
+                })
+                .map(|op| op.id);
+            (alloc, free)
+        };
+        for measure in find_ops(&hugr, "Measure") {
+            assert_wire_feeds(&hugr, measure.id, 0, "QFree");
+        }
+        let (alloc_1, free_1) = scope_of(1);
+        assert!(alloc_1.unwrap() < free_1.unwrap());
+        assert!(matches!(scope_of(0), (None, Some(_))));
+        assert!(matches!(scope_of(2), (Some(_), None)));
+        
+        // Freed qubits are never used again
+        for free in find_ops(&hugr, "QFree") {
+            let freed = free.inputs[0];
+            let consumers = hugr
+                .nodes
+                .values()
+                .filter(|node| node.inputs.contains(&freed))
+                .count();
+            assert_eq!(consumers, 1);
+        }
+    }
+    
+    #[test]
+    fn test_to_xy_plane_preserves_outcome_statistics() {
+        let mut pattern = Pattern::new(vec![0], vec![]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::YZ,
+            angle: 0.7,
+        });
+        pattern.add_command(Command::M {
+            node: 1,
+            plane: Plane::XZ,
+            angle: 0.4,
+        });
+        
+        let xy = pattern.to_xy_plane();
+        assert_eq!(
+            xy.commands[2..],
+            [
+                Command::C {
+                    node: 0,
+                    clifford: vec![CliffordGate::H],
+                },
+                Command::M {
+                    node: 0,
+                    plane: Plane::XY,
+                    angle: 0.7,
+                },
+                Command::C {
+                    node: 1,
+                    clifford: vec![CliffordGate::SDG, CliffordGate::H],
+                },
+                Command::M {
+                    node: 1,
+                    plane: Plane::XY,
+                    angle: -0.4,
+                },
+            ]
+        );
+        
+        let input = [Complex::new(0.6, 0.0), Complex::new(0.0, 0.8)];
+        let distribution = |pattern: &Pattern| {
+            let hugr = convert_graphix_pattern_to_hugr(pattern).unwrap();
+            sim::hugr_distribution(&hugr, &input).unwrap()
+        };
+        let (before, after) = (distribution(&pattern), distribution(&xy));
+        assert_eq!(before.keys().collect::<Vec<_>>(), after.keys().collect::<Vec<_>>());
+        for (outcome, p) in &before {
+            assert!((p - after[outcome]).abs() < 1e-9, "{:?}: {} vs {}", outcome, p, after[outcome]);
+        }
+    }
+    
+    #[test]
+    fn test_teleportation_output_byproduct() {
+        use crate::testutil::assert_op_count;
+        
+        let config = ConverterConfig::default().with_track_output_byproduct(true);
+        let mut converter = GraphixToHugrConverter::with_config(config);
+        let hugr = converter.convert(&teleportation_pattern()).unwrap();
+        
+        assert_op_count(&hugr, "ConditionalX", 0);
+        assert_op_count(&hugr, "ConditionalZ", 0);
+        let byproduct = converter.output_byproduct();
+        assert_eq!(
+            byproduct.domains,
+            [(2, (BTreeSet::from([1]), BTreeSet::from([0])))].into_iter().collect()
+        );
+        
+        // Deferred outcomes are recorded the same way
+        let config = ConverterConfig::default()
+            .with_track_output_byproduct(true)
+            .with_defer_measurements(true);
+        let mut converter = GraphixToHugrConverter::with_config(config);
+        let hugr = converter.convert(&teleportation_pattern()).unwrap();
+        assert_op_count(&hugr, "CX", 0);
+        assert_op_count(&hugr, "Measure", 2);
+        assert_eq!(converter.output_byproduct(), byproduct);
+        
+        // Without the flag the corrections are emitted and nothing is tracked
+        let mut converter = GraphixToHugrConverter::new();
+        let hugr = converter.convert(&teleportation_pattern()).unwrap();
+        assert_op_count(&hugr, "ConditionalX", 1);
+        assert!(converter.output_byproduct().domains.is_empty());
+    }
+    
+    #[test]
+    fn test_classical_register_circuit() {
+        let mut converter = GraphixToHugrConverter::new();
+        let hugr = converter.convert(&teleportation_pattern()).unwrap();
+        let circuit = to_classical_register_circuit(&hugr).unwrap();
+        
+        assert_eq!(circuit.register, "c");
+        assert_eq!(circuit.bits, vec![Some(0), Some(1)]);
+        let measures: Vec<_> = circuit.instrs.iter().filter(|i| i.name == "Measure").collect();
+        assert_eq!(measures.iter().map(|i| i.bit).collect::<Vec<_>>(), vec![Some(0), Some(1)]);
+        
+        let conditioned: Vec<_> = circuit
+            .instrs
+            .iter()
+            .filter_map(|i| {
+                let c_if = i.c_if.as_ref()?;
+                Some((i.name.as_str(), circuit.qubits[i.qubits[0]], c_if.bits.clone()))
+            })
+            .collect();
+        assert_eq!(conditioned, vec![("X", Some(2), vec![1]), ("Z", Some(2), vec![0])]);
+    }
+    
+    #[test]
+    fn test_missing_output_errors() {
+        // Output node 1 is measured, so its qubit is consumed
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 1,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        assert!(matches!(
+            convert_graphix_pattern_to_hugr(&pattern),
+            Err(ConversionError::OutputNodeMeasured(1))
+        ));
+        
+        // Output node 5 is never prepared at all
+        let pattern = Pattern::new(vec![0], vec![5]);
+        assert!(matches!(
+            convert_graphix_pattern_to_hugr(&pattern),
+            Err(ConversionError::OutputNodeNotFound(5))
+        ));
+    }
+    
+    #[test]
+    fn test_set_outputs_types_from_producers() {
+        let mut converter = GraphixToHugrConverter::new();
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        let hugr = converter.convert(&pattern).unwrap();
+        assert_eq!(hugr.output_types(), Some(&[HugrType::Qubit, HugrType::Bool][..]));
+        
+        // A wire with no known producer is rejected instead of assumed a qubit
+        let mut dfg = DfgBuilder::new(vec![HugrType::Bool]);
+        let input = dfg.input_wires[0];
+        assert!(matches!(
+            dfg.set_outputs(vec![input, Wire::new(99, 0)]),
+            Err(ConversionError::UntypedWire { node: 99, port: 0 })
+        ));
+    }
+    
+    #[test]
+    fn test_dfg_builder_tracks_wire_types() {
+        let mut dfg = DfgBuilder::new(vec![HugrType::Qubit]);
+        let qubit = dfg.input_wires[0];
+        assert_eq!(dfg.wire_type(qubit), Some(HugrType::Qubit));
+        
+        let measure = Operation::Custom {
+            name: "Measure".to_string(),
+            signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Bool]),
+            extension: "quantum.mbqc".to_string(),
+            args: vec![],
+        };
+        let outcome = dfg.add_op_wires(measure, vec![qubit])[0];
+        assert_eq!(dfg.wire_type(outcome), Some(HugrType::Bool));
+        
+        let angle = dfg.add_const(ConstValue::Float(0.5));
+        let angle = dfg.load_const(angle);
+        assert_eq!(dfg.wire_type(angle), Some(HugrType::Float64));
+        assert_eq!(dfg.wire_type(Wire::new(99, 0)), None);
+    }
+    
+    #[test]
+    fn test_validate_reports_every_violation() {
+        assert_eq!(teleportation_pattern().validate(), Ok(()));
+        
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::E { nodes: (0, 4) });
+        pattern.add_command(Command::M {
+            node: 1,
+            plane: Plane::XY,
+            angle: 0.0,
+        });
+        pattern.add_command(Command::X {
+            node: 0,
+            domain: [1, 3].into_iter().collect(),
+        });
+        assert_eq!(
+            pattern.validate(),
+            Err(vec![
+                PatternError::DuplicatePreparation { command: 1, node: 1 },
+                PatternError::UnpreparedNode { command: 2, node: 4 },
+                PatternError::MeasuredOutput { command: 3, node: 1 },
+                PatternError::UnmeasuredDomainNode { command: 4, node: 3 },
+            ])
+        );
+        
+        let config = ConverterConfig::default().with_validate_patterns(true);
+        assert!(matches!(
+            GraphixToHugrConverter::with_config(config).convert(&pattern),
+            Err(ConversionError::InvalidPattern(errors)) if errors.len() == 4
+        ));
+    }
+    
+    #[test]
+    fn test_validate_tracks_liveness() {
+        let mut pattern = Pattern::new(vec![0], vec![]);
+        pattern.add_command(Command::MZ { node: 0 });
+        pattern.add_command(Command::N { node: 0 });
+        pattern.add_command(Command::MZ { node: 0 });
+        assert_eq!(pattern.validate(), Ok(()));
+        
+        let mut pattern = Pattern::new(vec![0], vec![1]);
+        pattern.add_command(Command::N { node: 1 });
+        pattern.add_command(Command::MZ { node: 0 });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::E { nodes: (1, 1) });
+        assert_eq!(
+            pattern.validate(),
+            Err(vec![
+                PatternError::EntangledMeasuredNode { command: 2, node: 0 },
+                PatternError::SelfEntanglement { command: 3, node: 1 },
+            ])
+        );
+    }
+    
+    #[test]
+    fn test_remeasure_after_reprepare_returns_both_outcomes() {
+        use crate::testutil::find_ops;
+        
+        let mut pattern = Pattern::new(vec![0], vec![]);
+        pattern.add_command(Command::M {
+            node: 0,
+            plane: Plane::XY,
+            angle: 0.5,
+        });
+        pattern.add_command(Command::N { node: 0 });
+        pattern.add_command(Command::MZ { node: 0 });
+        
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        let measures = find_ops(&hugr, "Measure");
+        assert_eq!(measures.len(), 2);
+        assert_eq!(hugr.output_types(), Some(&[HugrType::Bool, HugrType::Bool][..]));
+        assert_eq!(
+            hugr.output_node().unwrap().inputs,
+            vec![measures[0].out(0), measures[1].out(0)]
+        );
+        
+        // Each schema entry describes its own measurement's basis
+        let bases: Vec<_> = ResultSchema::for_pattern(&pattern)
+            .entries
+            .iter()
+            .map(|entry| (entry.port, entry.basis))
+            .collect();
+        assert_eq!(bases, [(0, Some((Plane::XY, 0.5))), (1, Some((Plane::YZ, 0.0)))]);
+    }
+    
+    #[test]
+    fn test_t_gates_in_clifford_command() {
+        let mut pattern = Pattern::new(vec![0], vec![0]);
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::H, CliffordGate::T, CliffordGate::H, CliffordGate::TDG],
+        });
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        
+        // The gates form a chain from the input, in command order
+        let mut names = Vec::new();
+        let mut wire = hugr.input_node().unwrap().out(0);
+        while let Some(node) = hugr.nodes.values().find(|n| n.inputs == [wire] && !n.outputs.is_empty()) {
+            names.push(node.operation.name().to_string());
+            wire = node.out(0);
+        }
+        assert_eq!(names, ["H", "T", "H", "Tdg"]);
+        assert_eq!(hugr.t_count(), 2);
+        assert!(hugr.validate_against_registry(&ExtensionRegistry::standard()).is_ok());
+    }
+    
+    #[test]
+    fn test_entangling_gate_cz_and_cx() {
+        use crate::testutil::assert_op_count;
+        
+        // H on both, CZ, then H on the second qubit prepares a Bell state
+        let mut pattern = Pattern::new(vec![0, 1], vec![0, 1]);
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::H],
+        });
+        pattern.add_command(Command::C {
+            node: 1,
+            clifford: vec![CliffordGate::H],
+        });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        pattern.add_command(Command::C {
+            node: 1,
+            clifford: vec![CliffordGate::H],
+        });
+        let zero: Vec<Complex> = (0..4).map(|i| if i == 0 { Complex::ONE } else { Complex::ZERO }).collect();
+        let bell = [(vec![false, false], 0.5), (vec![true, true], 0.5)];
+        
+        for edge in [EdgeKind::CZ, EdgeKind::CX] {
+            let config = ConverterConfig::default().with_entangling_gate(edge);
+            let hugr = GraphixToHugrConverter::with_config(config.clone()).convert(&pattern).unwrap();
+            assert_op_count(&hugr, "CZ", (edge == EdgeKind::CZ) as usize);
+            assert_op_count(&hugr, "CX", (edge == EdgeKind::CX) as usize);
+            assert!(hugr.validate_against_registry(&ExtensionRegistry::for_config(&config)).is_ok());
+            
+            let distribution = sim::hugr_distribution(&hugr, &zero).unwrap();
+            assert_eq!(distribution.len(), bell.len());
+            for (outcome, p) in &bell {
+                assert!((distribution[outcome] - p).abs() < 1e-9);
+            }
+        }
+        assert_eq!(ConverterConfig::default().entangling_gate, EdgeKind::CZ);
+    }
+    
+    #[test]
+    fn test_hugr_json_round_trip() {
+        let hugr = convert_graphix_pattern_to_hugr(&teleportation_pattern()).unwrap();
+        let json = hugr.to_json_string().unwrap();
+        
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        for key in ["nodes", "next_node_id", "root"] {
+            assert!(value.get(key).is_some(), "missing {}", key);
+        }
+        
+        // Nodes come out in ascending id order, so the text is stable
+        let positions: Vec<usize> = (0..hugr.next_node_id)
+            .map(|id| json.find(&format!("{{\"id\":{},", id)).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        let again = convert_graphix_pattern_to_hugr(&teleportation_pattern()).unwrap();
+        assert_eq!(again.to_json_string().unwrap(), json);
+        
+        let back = Hugr::from_json_string(&hugr.to_json_string_pretty().unwrap()).unwrap();
+        assert_eq!(back.to_json_string().unwrap(), json);
+        assert_eq!(back.structural_hash(), hugr.structural_hash());
+    }
+    
+    #[test]
+    fn test_hugr_from_json_validates_structure() {
+        let hugr = convert_graphix_pattern_to_hugr(&teleportation_pattern()).unwrap();
+        let json = hugr.to_json_string().unwrap();
+        assert_eq!(Hugr::from_json_string(&json).unwrap().to_json_string().unwrap(), json);
+        
+        assert!(matches!(Hugr::from_json_string("{\"nodes\": "), Err(HugrParseError::Json(_))));
+        
+        let mut broken = hugr.clone();
+        broken.root = 99;
+        assert!(matches!(
+            Hugr::from_json_string(&broken.to_json_string().unwrap()),
+            Err(HugrParseError::MissingRoot(99))
+        ));
+        
+        let mut broken = hugr.clone();
+        let consumer = *broken.nodes.keys().max().unwrap();
+        broken.nodes.get_mut(&consumer).unwrap().inputs.push(Wire::new(77, 0));
+        assert!(matches!(
+            Hugr::from_json_string(&broken.to_json_string().unwrap()),
+            Err(HugrParseError::DanglingWire { source_node: 77, .. })
+        ));
+        
+        let mut broken = hugr.clone();
+        broken.next_node_id -= 1;
+        assert!(matches!(
+            Hugr::from_json_string(&broken.to_json_string().unwrap()),
+            Err(HugrParseError::NodeIdOutOfRange { .. })
+        ));
+    }
+    
+    #[test]
+    fn test_pattern_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("graphix_to_hugr_io_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("teleportation.json");
+        
+        let pattern = teleportation_pattern();
+        pattern.save_to_path(&path).unwrap();
+        assert_eq!(Pattern::load_from_path(&path).unwrap(), pattern);
+        
+        // Negative node indices are rejected by the parser
+        let garbage = std::fs::read_to_string(&path).unwrap().replacen("\"node\": 2", "\"node\": -2", 1);
+        std::fs::write(&path, garbage).unwrap();
+        assert!(matches!(Pattern::load_from_path(&path), Err(PatternIoError::Json(_))));
+        
+        assert!(matches!(
+            Pattern::load_from_path(dir.join("missing.json")),
+            Err(PatternIoError::Io(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn test_hugr_mermaid_flowchart() {
+        let mut pattern = Pattern::new(vec![0, 1], vec![0, 1]);
+        pattern.add_command(Command::C {
+            node: 0,
+            clifford: vec![CliffordGate::H],
+        });
+        pattern.add_command(Command::E { nodes: (0, 1) });
+        let hugr = convert_graphix_pattern_to_hugr(&pattern).unwrap();
+        let mermaid = hugr.to_mermaid();
+        
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert_eq!(mermaid.lines().filter(|l| l.contains("[\"")).count(), hugr.len());
+        // Input -> H, Input -> CZ, H -> CZ and both CZ outputs -> Output
+        let edges: Vec<&str> = mermaid.lines().filter(|l| l.contains("-->")).collect();
+        assert_eq!(edges.len(), 5);
+        assert!(edges.contains(&"    n0 -->|1| n2"));
+        assert_eq!(mermaid, convert_graphix_pattern_to_hugr(&pattern).unwrap().to_mermaid());
+    }
+    
+    #[test]
+    fn test_parse_graphix_text_pattern() {
+        let text = "
+            # Teleportation
+            inputs: [0]
+            outputs: [2]
+            N(1)
+            N(2)
+            C(1,[H])
+            E(1,2)
+            E(0,1)
+            C(0,[H])
+            M(0,XY,0)
+            M(1,XY,0.0)
+            X(2,{1})
+            Z(2,{0})
+        ";
+        assert_eq!(parse_pattern(text).unwrap(), teleportation_pattern());
+        
+        let pattern = parse_pattern("N(0)\nM(0,YZ,-3pi/4)\nC(0,[H,SDG,T])\nZ(0,{0, 1})").unwrap();
+        let Command::M { plane, angle, .. } = pattern.commands[1] else {
+            panic!("expected a measurement");
+        };
+        assert_eq!(plane, Plane::YZ);
+        assert!((angle + 3.0 * std::f64::consts::FRAC_PI_4).abs() < 1e-12);
+        assert_eq!(
+            pattern.commands[2],
+            Command::C {
+                node: 0,
+                clifford: vec![CliffordGate::H, CliffordGate::SDG, CliffordGate::T],
+            }
+        );
+        assert_eq!(pattern.commands[3].domain(), Some(&[0, 1].into_iter().collect()));
+        
+        let error = parse_pattern("N(0)\n\nM(0,AB,0)").unwrap_err();
+        assert_eq!(error.line, 3);
+        assert!(error.message.contains("plane"));
+        assert_eq!(parse_pattern("E(0)").unwrap_err().line, 1);
+        assert!(parse_pattern("M(0,XY,pi/0)").is_err());
+    }
+    
+    #[test]
+    fn test_teleportation_to_qasm2() {
+        let qasm = teleportation_pattern().to_qasm2().unwrap();
+        assert!(qasm.starts_with("OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[3];\n"));
+        for line in [
+            "creg m0[1];",
+            "creg m1[1];",
+            "reset q[1];",
+            "cz q[0],q[1];",
+            "measure q[0] -> m0[0];",
+            "if(m1==1) x q[2];",
+            "if(m0==1) z q[2];",
+        ] {
+            assert!(qasm.lines().any(|l| l == line), "missing {:?} in\n{}", line, qasm);
+        }
+        
+        // A parity over two outcomes becomes two conditioned gates
+        let mut pattern = teleportation_pattern();
+        pattern.replace_command(8, Command::X {
+            node: 2,
+            domain: [0, 1].into_iter().collect(),
+        });
+        let qasm = pattern.to_qasm2().unwrap();
+        let xs: Vec<&str> = qasm.lines().filter(|l| l.ends_with(" x q[2];")).collect();
+        assert_eq!(xs, ["if(m0==1) x q[2];", "if(m1==1) x q[2];"]);
+        
+        let mut pattern = Pattern::new(vec![0], vec![0]);
+        pattern.add_command(Command::Gate {
+            node: 0,
+            name: "Foo".to_string(),
+            params: vec![],
+            matrix: None,
+        });
+        assert!(matches!(pattern.to_qasm2(), Err(ExportError::Unsupported(name)) if name == "Foo"));
+    }
+    
+    #[test]
+    fn test_qasm2_prepares_internal_inputs() {
+        let mut pattern = teleportation_pattern();
+        pattern.input_states.insert(0, InitState::Plus);
+        let qasm = pattern.to_qasm2().unwrap();
+        let body: Vec<&str> = qasm.lines().skip_while(|l| !l.starts_with("creg")).collect();
+        let first = body.iter().position(|l| !l.starts_with("creg")).unwrap();
+        assert_eq!(body[first..first + 2], ["reset q[0];", "h q[0];"]);
+        
+        pattern.input_states.insert(0, InitState::Zero);
+        let qasm = pattern.to_qasm2().unwrap();
+        assert!(qasm.contains("reset q[0];\nh q[0];\nh q[0];\n"));
+    }
+}
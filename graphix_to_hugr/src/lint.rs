@@ -0,0 +1,56 @@
+use crate::types::{CliffordGate, Command, CommandKind, Pattern, Plane};
+use thiserror::Error;
+
+/// Angles closer to zero than this are treated as zero, as in the converter
+const ANGLE_TOLERANCE: f64 = 1e-10;
+
+/// Non-fatal style and efficiency hint reported by `Pattern::lint`
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Lint {
+    #[error("command {command}: {kind:?} correction on node {node} could be merged with the adjacent correction")]
+    MergeableCorrections {
+        command: usize,
+        node: usize,
+        kind: CommandKind,
+    },
+    #[error("command {command}: measurement of node {node} at angle 0 in the XY plane is an X-basis measurement (consider MZ after H)")]
+    XBasisMeasurement { command: usize, node: usize },
+    #[error("command {command}: identity Clifford on node {node} can be removed")]
+    IdentityClifford { command: usize, node: usize },
+}
+
+impl Pattern {
+    /// Style and efficiency hints for the top-level commands, in command
+    /// order. Two `X` (or two `Z`) corrections in a row on the same node are
+    /// reported at the second one, since one correction on the symmetric
+    /// difference of their domains does the same.
+    pub fn lint(&self) -> Vec<Lint> {
+        let mut lints = Vec::new();
+        for (command, cmd) in self.iter().enumerate() {
+            match cmd {
+                Command::X { node, .. } | Command::Z { node, .. } => {
+                    let previous = command.checked_sub(1).map(|i| &self.commands[i]);
+                    if previous.is_some_and(|prev| prev.kind() == cmd.kind() && prev.nodes() == [*node]) {
+                        lints.push(Lint::MergeableCorrections {
+                            command,
+                            node: *node,
+                            kind: cmd.kind(),
+                        });
+                    }
+                }
+                Command::M {
+                    node,
+                    plane: Plane::XY,
+                    angle,
+                } if angle.abs() <= ANGLE_TOLERANCE => {
+                    lints.push(Lint::XBasisMeasurement { command, node: *node });
+                }
+                Command::C { node, clifford } if clifford.iter().all(|&g| g == CliffordGate::I) => {
+                    lints.push(Lint::IdentityClifford { command, node: *node });
+                }
+                _ => {}
+            }
+        }
+        lints
+    }
+}
@@ -0,0 +1,71 @@
+use crate::hugr::{Hugr, Operation};
+use crate::types::{Command, Pattern};
+use std::fmt::Write;
+
+/// Render the pattern's open graph as a Mermaid `graph LR`. Nodes are styled
+/// by role (input, output, measured, ancilla, in that priority), `E` commands
+/// become solid edges and each correction gets a dotted edge from every
+/// outcome it depends on, labelled with the correction.
+pub fn to_mermaid(pattern: &Pattern) -> String {
+    let measured = pattern.measured_nodes();
+    let mut mermaid = String::from("graph LR\n");
+    for &node in pattern.open_graph().keys() {
+        let role = if pattern.input_nodes.contains(&node) {
+            "input"
+        } else if pattern.output_nodes.contains(&node) {
+            "output"
+        } else if measured.contains(&node) {
+            "measured"
+        } else {
+            "ancilla"
+        };
+        let _ = writeln!(mermaid, "    n{}[\"{}\"]:::{}", node, node, role);
+    }
+    for (a, b) in pattern.entanglements() {
+        let _ = writeln!(mermaid, "    n{} --- n{}", a, b);
+    }
+    for cmd in pattern.iter() {
+        let (node, label) = match cmd {
+            Command::X { node, .. } | Command::XControlled { node, .. } => (node, "X"),
+            Command::Z { node, .. } => (node, "Z"),
+            _ => continue,
+        };
+        let mut domain: Vec<_> = cmd.domain().into_iter().flatten().collect();
+        domain.sort();
+        for source in domain {
+            let _ = writeln!(mermaid, "    n{} -.->|{}| n{}", source, label, node);
+        }
+    }
+    mermaid.push_str("    classDef input fill:#cfe8ff\n");
+    mermaid.push_str("    classDef output fill:#d5f5d5\n");
+    mermaid.push_str("    classDef measured fill:#ffe0b3\n");
+    mermaid.push_str("    classDef ancilla fill:#eeeeee\n");
+    mermaid
+}
+
+impl Hugr {
+    /// Render the dataflow as a Mermaid `flowchart TD`: one node `n<id>` per
+    /// HUGR node (in id order) labelled with its operation, and an edge
+    /// `n<src> -->|<port>| n<dst>` for every input wire, labelled with the
+    /// producer's output port
+    pub fn to_mermaid(&self) -> String {
+        let mut ids: Vec<_> = self.nodes.keys().cloned().collect();
+        ids.sort();
+        
+        let mut mermaid = String::from("flowchart TD\n");
+        for id in &ids {
+            let label = match &self.nodes[id].operation {
+                Operation::Custom { name, .. } => name.clone(),
+                Operation::Const { value } => format!("Const {:?}", value),
+                other => other.name().to_string(),
+            };
+            let _ = writeln!(mermaid, "    n{}[\"{}\"]", id, label);
+        }
+        for id in &ids {
+            for wire in &self.nodes[id].inputs {
+                let _ = writeln!(mermaid, "    n{} -->|{}| n{}", wire.node_id, wire.port, id);
+            }
+        }
+        mermaid
+    }
+}
@@ -0,0 +1,120 @@
+use crate::types::{Command, Pattern};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Dependencies between a pattern's measurements, as a DAG over the measured
+/// nodes. Measurements with no path between them are incomparable and may be
+/// made in either order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartialOrder {
+    /// Direct predecessors of each measured node: the measurements whose
+    /// outcomes it waits for. Every measured node has an entry.
+    pub predecessors: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+impl PartialOrder {
+    /// Measured nodes, in ascending order
+    pub fn nodes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.predecessors.keys().cloned()
+    }
+    
+    /// Whether `a` must be measured before `b`, directly or through other
+    /// measurements
+    pub fn precedes(&self, a: usize, b: usize) -> bool {
+        let mut stack = vec![b];
+        let mut seen = BTreeSet::new();
+        while let Some(node) = stack.pop() {
+            for &pred in self.predecessors.get(&node).into_iter().flatten() {
+                if pred == a {
+                    return true;
+                }
+                if seen.insert(pred) {
+                    stack.push(pred);
+                }
+            }
+        }
+        false
+    }
+    
+    /// Whether the order between `a` and `b` is fixed
+    pub fn are_comparable(&self, a: usize, b: usize) -> bool {
+        a == b || self.precedes(a, b) || self.precedes(b, a)
+    }
+    
+    /// One valid measurement order: repeatedly the smallest node whose
+    /// predecessors are all measured. Any other topological sort of the DAG
+    /// is valid too.
+    pub fn topological_order(&self) -> Vec<usize> {
+        let mut waiting: BTreeMap<usize, usize> = self
+            .predecessors
+            .iter()
+            .map(|(&node, preds)| (node, preds.len()))
+            .collect();
+        let mut ready: BTreeSet<usize> = waiting
+            .iter()
+            .filter(|(_, &count)| count == 0)
+            .map(|(&node, _)| node)
+            .collect();
+        
+        let mut order = Vec::with_capacity(waiting.len());
+        while let Some(node) = ready.pop_first() {
+            order.push(node);
+            for (&later, preds) in &self.predecessors {
+                if preds.contains(&node) {
+                    let count = waiting.get_mut(&later).expect("every measured node is keyed");
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.insert(later);
+                    }
+                }
+            }
+        }
+        order
+    }
+}
+
+impl Pattern {
+    /// Which measurements must precede which. Measuring `b` waits for `a`
+    /// when `a`'s outcome is in the domain of a command acting on `b`'s
+    /// qubit before `b` is measured: a correction, a computed angle, or a
+    /// branch whose cases touch `b`. Outcomes supplied as classical inputs
+    /// are not measurements and are left out.
+    pub fn measurement_partial_order(&self) -> PartialOrder {
+        let measured = self.measured_nodes();
+        let mut order = PartialOrder::default();
+        let mut pending: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+        visit(&self.commands, &BTreeSet::new(), &measured, &mut pending, &mut order);
+        order
+    }
+}
+
+/// Record the dependencies of `commands`, each also conditioned on the
+/// outcomes in `outer` (the domains of the enclosing branches)
+fn visit(
+    commands: &[Command],
+    outer: &BTreeSet<usize>,
+    measured: &BTreeSet<usize>,
+    pending: &mut BTreeMap<usize, BTreeSet<usize>>,
+    order: &mut PartialOrder,
+) {
+    for cmd in commands {
+        let mut domain = outer.clone();
+        domain.extend(cmd.domain().into_iter().flatten().filter(|d| measured.contains(d)));
+        if let Command::Branch {
+            if_true, if_false, ..
+        } = cmd
+        {
+            visit(if_true, &domain, measured, pending, order);
+            visit(if_false, &domain, measured, pending, order);
+            continue;
+        }
+        
+        for node in cmd.nodes() {
+            pending.entry(node).or_default().extend(domain.iter().cloned());
+        }
+        if let Some(node) = cmd.measured_node() {
+            let mut preds = pending.remove(&node).unwrap_or_default();
+            preds.remove(&node);
+            order.predecessors.entry(node).or_default().extend(preds);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::angle::PiFraction;
+use crate::types::{CliffordGate, Command, Pattern, Plane};
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Why `parse_pattern` rejected its input, with the 1-based line it failed on
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("line {line}: {message}")]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Parse a pattern in the Graphix command syntax, one item per line:
+///
+/// ```text
+/// inputs: [0]
+/// outputs: [2]
+/// N(1)
+/// E(0,1)
+/// M(1,XY,pi/4)
+/// X(2,{1})
+/// Z(2,{0,1})
+/// C(0,[H,S])
+/// ```
+///
+/// The `inputs`/`outputs` header lines are optional (empty by default) and
+/// may appear anywhere. `MZ(n)` measures in the Z basis. Angles are in
+/// radians, either a plain float or a multiple of π such as `pi`, `-pi/2` or
+/// `3pi/4`. Blank lines and `#` comments are skipped.
+pub fn parse_pattern(input: &str) -> Result<Pattern, ParseError> {
+    let mut pattern = Pattern::new(vec![], vec![]);
+    for (index, raw) in input.lines().enumerate() {
+        let error = |message: String| ParseError {
+            line: index + 1,
+            message,
+        };
+        let line = raw.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        
+        if let Some((key, value)) = line.split_once(':') {
+            let nodes = parse_list(value.trim(), '[', ']').map_err(error)?;
+            match key.trim() {
+                "inputs" => pattern.input_nodes = nodes,
+                "outputs" => pattern.output_nodes = nodes,
+                other => return Err(error(format!("unknown header {:?}", other))),
+            }
+        } else {
+            pattern.add_command(parse_command(line).map_err(error)?);
+        }
+    }
+    Ok(pattern)
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let (name, rest) = line
+        .split_once('(')
+        .ok_or_else(|| format!("expected a command like N(0), got {:?}", line))?;
+    let args = rest
+        .strip_suffix(')')
+        .ok_or_else(|| format!("missing closing parenthesis in {:?}", line))?;
+    let args = split_args(args);
+    let expect = |n: usize| {
+        if args.len() == n {
+            Ok(())
+        } else {
+            Err(format!("{} takes {} arguments, got {}", name.trim(), n, args.len()))
+        }
+    };
+    
+    Ok(match name.trim() {
+        "N" => {
+            expect(1)?;
+            Command::N {
+                node: parse_node(args[0])?,
+            }
+        }
+        "E" => {
+            expect(2)?;
+            Command::E {
+                nodes: (parse_node(args[0])?, parse_node(args[1])?),
+            }
+        }
+        "M" => {
+            expect(3)?;
+            Command::M {
+                node: parse_node(args[0])?,
+                plane: parse_plane(args[1])?,
+                angle: parse_angle(args[2])?,
+            }
+        }
+        "MZ" => {
+            expect(1)?;
+            Command::MZ {
+                node: parse_node(args[0])?,
+            }
+        }
+        "X" | "Z" => {
+            expect(2)?;
+            let node = parse_node(args[0])?;
+            let domain: HashSet<usize> = parse_list(args[1], '{', '}')?.into_iter().collect();
+            if name.trim() == "X" {
+                Command::X { node, domain }
+            } else {
+                Command::Z { node, domain }
+            }
+        }
+        "C" => {
+            expect(2)?;
+            let gates = args[1]
+                .strip_prefix('[')
+                .and_then(|s| s.strip_suffix(']'))
+                .ok_or_else(|| format!("expected a gate list like [H,S], got {:?}", args[1]))?;
+            Command::C {
+                node: parse_node(args[0])?,
+                clifford: split_args(gates)
+                    .into_iter()
+                    .filter(|gate| !gate.is_empty())
+                    .map(parse_gate)
+                    .collect::<Result<_, _>>()?,
+            }
+        }
+        other => return Err(format!("unknown command {:?}", other)),
+    })
+}
+
+/// Split on commas that are not inside `{}` or `[]`, trimming each part
+fn split_args(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0i32, 0);
+    for (i, c) in args.char_indices() {
+        match c {
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(args[start..].trim());
+    parts
+}
+
+fn parse_node(arg: &str) -> Result<usize, String> {
+    arg.parse().map_err(|_| format!("expected a node index, got {:?}", arg))
+}
+
+/// Node list such as `[0, 1]` or `{}` between `open` and `close`
+fn parse_list(arg: &str, open: char, close: char) -> Result<Vec<usize>, String> {
+    let inner = arg
+        .strip_prefix(open)
+        .and_then(|s| s.strip_suffix(close))
+        .ok_or_else(|| format!("expected a node list in {}{}, got {:?}", open, close, arg))?;
+    inner
+        .split(',')
+        .map(str::trim)
+        .filter(|node| !node.is_empty())
+        .map(parse_node)
+        .collect()
+}
+
+fn parse_plane(arg: &str) -> Result<Plane, String> {
+    match arg {
+        "XY" => Ok(Plane::XY),
+        "YZ" => Ok(Plane::YZ),
+        "XZ" => Ok(Plane::XZ),
+        other => Err(format!("unknown plane {:?}", other)),
+    }
+}
+
+fn parse_gate(arg: &str) -> Result<CliffordGate, String> {
+    CliffordGate::ALL
+        .into_iter()
+        .find(|gate| gate.op_name() == arg || format!("{:?}", gate) == arg)
+        .ok_or_else(|| format!("unknown gate {:?}", arg))
+}
+
+/// Angle in radians: a float, or `[-][k][*]pi[/d]` with integer `k` and `d`
+fn parse_angle(arg: &str) -> Result<f64, String> {
+    let bad = || format!("expected an angle like 0.5 or pi/4, got {:?}", arg);
+    let Some((coefficient, divisor)) = arg.split_once("pi") else {
+        return arg.parse().map_err(|_| bad());
+    };
+    
+    let coefficient = coefficient.trim_end_matches('*');
+    let numerator = match coefficient {
+        "" => 1,
+        "-" => -1,
+        k => k.parse().map_err(|_| bad())?,
+    };
+    let denominator = match divisor {
+        "" => 1,
+        d => d
+            .strip_prefix('/')
+            .and_then(|d| d.parse().ok())
+            .filter(|&d| d != 0)
+            .ok_or_else(bad)?,
+    };
+    Ok(PiFraction::new(numerator, denominator).to_radians())
+}
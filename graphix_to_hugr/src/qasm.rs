@@ -0,0 +1,81 @@
+use crate::circuit::to_gate_list;
+use crate::converter::{ClassicalOp, ConversionError};
+use crate::types::Pattern;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error(transparent)]
+    Conversion(#[from] ConversionError),
+    
+    #[error("{0} has no OpenQASM 2.0 form")]
+    Unsupported(String),
+}
+
+impl Pattern {
+    /// Lower the pattern to an OpenQASM 2.0 program over `qreg q`, with one
+    /// qubit per node index, using the same decompositions as `to_gate_list`.
+    /// `N` becomes `reset` then `h`, `E` a `cz` and `M` its basis change then
+    /// `measure`. QASM 2.0 can only condition on a whole register, so each
+    /// node whose outcome is measured or read gets its own one-bit register
+    /// `m<node>`, and a correction on the parity of its domain becomes one
+    /// `if(m<d>==1)` gate per domain node.
+    ///
+    /// Approximations: boundary input qubits start in |0⟩ rather than an
+    /// arbitrary input state (inputs listed in `input_states` are prepared
+    /// like the converter does), classical inputs read as 0, and
+    /// `rx`/`ry`/`rz` match the HUGR rotations only up to global phase.
+    /// `MSoft`, custom gates and `AND`/`OR`-combined controls are rejected.
+    pub fn to_qasm2(&self) -> Result<String, ExportError> {
+        let instrs = to_gate_list(self)?;
+        let mut bits: BTreeSet<usize> = self.measured_nodes();
+        bits.extend(instrs.iter().filter_map(|i| i.condition.as_ref()).flatten());
+        
+        let mut qasm = String::from("OPENQASM 2.0;\ninclude \"qelib1.inc\";\n");
+        if let Some(max) = self.max_node() {
+            let _ = writeln!(qasm, "qreg q[{}];", max + 1);
+        }
+        for bit in &bits {
+            let _ = writeln!(qasm, "creg m{}[1];", bit);
+        }
+        
+        for instr in &instrs {
+            let q = instr.qubits.iter().map(|q| format!("q[{}]", q)).collect::<Vec<_>>().join(",");
+            let gate = match instr.name.as_str() {
+                "PrepareQubit" => {
+                    let _ = writeln!(qasm, "reset {};\nh {};", q, q);
+                    continue;
+                }
+                "Measure" => {
+                    let _ = writeln!(qasm, "measure {} -> m{}[0];", q, instr.qubits[0]);
+                    continue;
+                }
+                "Rx" | "Ry" | "Rz" => format!("{}({})", instr.name.to_lowercase(), instr.params[0]),
+                "I" => "id".to_string(),
+                "X" | "Y" | "Z" | "H" | "S" | "Sdg" | "T" | "Tdg" | "CZ" => instr.name.to_lowercase(),
+                other => return Err(ExportError::Unsupported(other.to_string())),
+            };
+            
+            match &instr.condition {
+                None => {
+                    let _ = writeln!(qasm, "{} {};", gate, q);
+                }
+                Some(domain) if instr.combiner == ClassicalOp::Xor => {
+                    for bit in domain {
+                        let _ = writeln!(qasm, "if(m{}==1) {} {};", bit, gate, q);
+                    }
+                }
+                Some(_) => {
+                    return Err(ExportError::Unsupported(format!(
+                        "{} condition on {}",
+                        instr.combiner.op_name(),
+                        instr.name
+                    )))
+                }
+            }
+        }
+        Ok(qasm)
+    }
+}
@@ -0,0 +1,139 @@
+use crate::config::{ConverterConfig, EdgeKind};
+use crate::converter::{measure_signature, prepare_signature, FLOAT_EXTENSION, LOGIC_EXTENSION, QUANTUM_EXTENSION};
+use crate::hugr::{FunctionType, HugrType};
+use crate::types::CliffordGate;
+use std::collections::{HashMap, HashSet};
+
+/// Registry of extension operations and their signatures
+#[derive(Debug, Clone, Default)]
+pub struct ExtensionRegistry {
+    ops: HashMap<(String, String), FunctionType>,
+    variadic: HashSet<(String, String)>,
+}
+
+impl ExtensionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Registry containing every operation emitted by the converter with the
+    /// default configuration
+    pub fn standard() -> Self {
+        Self::for_config(&ConverterConfig::default())
+    }
+    
+    /// Registry containing every operation emitted by a converter using `config`
+    pub fn for_config(config: &ConverterConfig) -> Self {
+        let mut reg = Self::new();
+        let q = || vec![HugrType::Qubit];
+        let b = || vec![HugrType::Bool];
+        
+        reg.register(QUANTUM_EXTENSION, "PrepareQubit", prepare_signature(config));
+        if config.explicit_qubit_scopes {
+            reg.register(QUANTUM_EXTENSION, "QAlloc", FunctionType::new(vec![], q()));
+            reg.register(QUANTUM_EXTENSION, "QFree", FunctionType::new(q(), vec![]));
+        }
+        reg.register(
+            QUANTUM_EXTENSION,
+            "CZ",
+            FunctionType::new(vec![HugrType::Qubit; 2], vec![HugrType::Qubit; 2]),
+        );
+        for gate in CliffordGate::ALL {
+            reg.register(QUANTUM_EXTENSION, gate.op_name(), FunctionType::new(q(), q()));
+        }
+        for rotation in ["Rx", "Ry", "Rz"] {
+            reg.register(config.rotation_ext(), rotation, FunctionType::new(q(), q()));
+            reg.register(
+                config.rotation_ext(),
+                &format!("{}Wire", rotation),
+                FunctionType::new(vec![HugrType::Qubit, HugrType::Float64], q()),
+            );
+        }
+        if config.defer_measurements || config.entangling_gate == EdgeKind::CX {
+            reg.register(
+                QUANTUM_EXTENSION,
+                "CX",
+                FunctionType::new(vec![HugrType::Qubit; 2], vec![HugrType::Qubit; 2]),
+            );
+        }
+        if config.connectivity.is_some() {
+            reg.register(
+                QUANTUM_EXTENSION,
+                "SWAP",
+                FunctionType::new(vec![HugrType::Qubit; 2], vec![HugrType::Qubit; 2]),
+            );
+        }
+        reg.register(config.measurement_ext(), "Measure", measure_signature(config));
+        reg.register(
+            config.measurement_ext(),
+            "MeasureSoft",
+            FunctionType::new(q(), vec![HugrType::Bool, HugrType::Float64]),
+        );
+        reg.register_variadic(QUANTUM_EXTENSION, "Barrier");
+        if config.pack_classical_outputs {
+            reg.register_variadic(LOGIC_EXTENSION, "Pack");
+        }
+        for gate in ["X", "Z"] {
+            reg.register(
+                config.pauli_ext(),
+                &format!("Conditional{}", gate),
+                FunctionType::new(vec![HugrType::Bool, HugrType::Qubit], q()),
+            );
+        }
+        reg.register(
+            LOGIC_EXTENSION,
+            "BoolToSum",
+            FunctionType::new(b(), vec![HugrType::unit_sum(2)]),
+        );
+        reg.register(
+            FLOAT_EXTENSION,
+            "BoolToFloat",
+            FunctionType::new(b(), vec![HugrType::Float64]),
+        );
+        for arithmetic in ["FAdd", "FMul"] {
+            reg.register(
+                FLOAT_EXTENSION,
+                arithmetic,
+                FunctionType::new(vec![HugrType::Float64; 2], vec![HugrType::Float64]),
+            );
+        }
+        for logic in ["XOR", "AND", "OR"] {
+            reg.register(
+                LOGIC_EXTENSION,
+                logic,
+                FunctionType::new(vec![HugrType::Bool; 2], b()),
+            );
+        }
+        
+        reg
+    }
+    
+    pub fn register(&mut self, extension: &str, name: &str, signature: FunctionType) {
+        self.ops
+            .insert((extension.to_string(), name.to_string()), signature);
+    }
+    
+    /// Register an operation whose arity depends on its use site; its
+    /// signature is not checked during validation
+    pub fn register_variadic(&mut self, extension: &str, name: &str) {
+        self.variadic
+            .insert((extension.to_string(), name.to_string()));
+    }
+    
+    pub fn is_variadic(&self, extension: &str, name: &str) -> bool {
+        self.variadic
+            .contains(&(extension.to_string(), name.to_string()))
+    }
+    
+    pub fn get(&self, extension: &str, name: &str) -> Option<&FunctionType> {
+        self.ops.get(&(extension.to_string(), name.to_string()))
+    }
+    
+    pub fn len(&self) -> usize {
+        self.ops.len() + self.variadic.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty() && self.variadic.is_empty()
+    }
+}
@@ -0,0 +1,69 @@
+use crate::config::ConverterConfig;
+use crate::converter::GraphixToHugrConverter;
+use crate::types::{Pattern, Plane};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Source of one classical outcome of a converted HUGR
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultEntry {
+    /// Output port index, counted across all outputs (qubits come first)
+    pub port: usize,
+    /// Bit of the port's `Int(n)` value holding the outcome when
+    /// `pack_classical_outputs` is set, `None` for a `Bool` port
+    #[serde(default)]
+    pub bit: Option<usize>,
+    /// Pattern node whose outcome is returned
+    pub node: usize,
+    /// Measurement plane and angle, or `None` if the node is never measured
+    /// (its port then carries a constant `false`)
+    pub basis: Option<(Plane, f64)>,
+}
+
+/// Labels for the classical outputs of a converted HUGR, in port order
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResultSchema {
+    pub entries: Vec<ResultEntry>,
+}
+
+impl ResultSchema {
+    /// Schema of the HUGR produced by converting `pattern` with the default
+    /// configuration. A node measured more than once has one entry per
+    /// measurement, in command order.
+    pub fn for_pattern(pattern: &Pattern) -> Self {
+        Self::for_config(pattern, &ConverterConfig::default())
+    }
+    
+    /// Schema of the HUGR produced by converting `pattern` with `config`.
+    /// With `pack_classical_outputs` every outcome shares the one packed
+    /// port after the qubits, at its own bit.
+    pub fn for_config(pattern: &Pattern, config: &ConverterConfig) -> Self {
+        let n_qubits = pattern.output_nodes.len();
+        let mut seen: HashMap<usize, usize> = HashMap::new();
+        let entries = GraphixToHugrConverter::get_measured_nodes(pattern)
+            .into_iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let k = seen.entry(node).or_default();
+                let basis = pattern.measurements_of(node).nth(*k);
+                *k += 1;
+                let (port, bit) = if config.pack_classical_outputs {
+                    (n_qubits, Some(i))
+                } else {
+                    (n_qubits + i, None)
+                };
+                ResultEntry {
+                    port,
+                    bit,
+                    node,
+                    basis,
+                }
+            })
+            .collect();
+        Self { entries }
+    }
+    
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("result schema is always serializable")
+    }
+}
@@ -0,0 +1,536 @@
+use crate::hugr::{ConstValue, Hugr, HugrType, Operation, Wire};
+use crate::types::{CliffordGate, Command, CommandKind, Complex, InitState, Pattern};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::f64::consts::FRAC_1_SQRT_2;
+use thiserror::Error;
+
+/// Largest number of simultaneously live qubits the dense simulator accepts
+pub const MAX_QUBITS: usize = 16;
+
+/// Largest pattern `Pattern::is_identity` checks by building its unitary
+const IDENTITY_CHECK_MAX_QUBITS: usize = 8;
+
+/// Dense single-qubit gate matrix
+pub type Gate2x2 = [[Complex; 2]; 2];
+
+/// Dense matrix, indexed `[row][column]`
+pub type Matrix = Vec<Vec<Complex>>;
+
+#[derive(Error, Debug)]
+pub enum SimError {
+    #[error("Command kind {0:?} cannot be simulated as a pure state")]
+    UnsupportedCommand(CommandKind),
+    
+    #[error("Pattern uses {0} qubits, more than the simulator limit")]
+    TooManyQubits(usize),
+    
+    #[error("Custom gate {0} has no matrix")]
+    MissingMatrix(String),
+    
+    #[error("Node {0} is not live")]
+    NodeNotFound(usize),
+    
+    #[error("Input state has {got} amplitudes, expected {expected}")]
+    InputSizeMismatch { expected: usize, got: usize },
+    
+    #[error("Operation {0} cannot be simulated")]
+    UnsupportedOperation(String),
+    
+    #[error("HUGR has no input or output node")]
+    MissingBoundary,
+}
+
+/// Matrix of a `CliffordGate` element (including `T`/`TDG`)
+pub fn clifford_matrix(gate: CliffordGate) -> Gate2x2 {
+    let (o, z, i) = (Complex::ONE, Complex::ZERO, Complex::I);
+    let h = Complex::new(FRAC_1_SQRT_2, 0.0);
+    match gate {
+        CliffordGate::I => [[o, z], [z, o]],
+        CliffordGate::X => [[z, o], [o, z]],
+        CliffordGate::Y => [[z, i * -1.0], [i, z]],
+        CliffordGate::Z => [[o, z], [z, o * -1.0]],
+        CliffordGate::S => [[o, z], [z, i]],
+        CliffordGate::SDG => [[o, z], [z, i * -1.0]],
+        CliffordGate::H => [[h, h], [h, h * -1.0]],
+        CliffordGate::T => [[o, z], [z, Complex::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2)]],
+        CliffordGate::TDG => [[o, z], [z, Complex::new(FRAC_1_SQRT_2, -FRAC_1_SQRT_2)]],
+    }
+}
+
+/// Pure state over the live pattern nodes. `qubits[0]` is the most
+/// significant bit of the amplitude index.
+struct StateVector {
+    qubits: Vec<usize>,
+    amps: Vec<Complex>,
+}
+
+impl StateVector {
+    fn position(&self, node: usize) -> Result<usize, SimError> {
+        self.qubits
+            .iter()
+            .position(|&q| q == node)
+            .ok_or(SimError::NodeNotFound(node))
+    }
+    
+    fn mask(&self, pos: usize) -> usize {
+        1 << (self.qubits.len() - 1 - pos)
+    }
+    
+    fn add_plus(&mut self, node: usize) -> Result<(), SimError> {
+        if self.qubits.len() >= MAX_QUBITS {
+            return Err(SimError::TooManyQubits(self.qubits.len() + 1));
+        }
+        let h = FRAC_1_SQRT_2;
+        self.amps = self.amps.iter().flat_map(|&a| [a * h, a * h]).collect();
+        self.qubits.push(node);
+        Ok(())
+    }
+    
+    fn apply_1q(&mut self, node: usize, u: &Gate2x2) -> Result<(), SimError> {
+        let mask = self.mask(self.position(node)?);
+        for idx in 0..self.amps.len() {
+            if idx & mask == 0 {
+                let (a0, a1) = (self.amps[idx], self.amps[idx | mask]);
+                self.amps[idx] = u[0][0] * a0 + u[0][1] * a1;
+                self.amps[idx | mask] = u[1][0] * a0 + u[1][1] * a1;
+            }
+        }
+        Ok(())
+    }
+    
+    fn apply_cz(&mut self, a: usize, b: usize) -> Result<(), SimError> {
+        let both = self.mask(self.position(a)?) | self.mask(self.position(b)?);
+        for (idx, amp) in self.amps.iter_mut().enumerate() {
+            if idx & both == both {
+                *amp = *amp * -1.0;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Project onto Z-outcome `outcome` of `node`, returning the outcome's
+    /// probability and the renormalized state
+    fn project(&self, node: usize, outcome: bool) -> Result<(f64, StateVector), SimError> {
+        let mask = self.mask(self.position(node)?);
+        let amps: Vec<Complex> = self
+            .amps
+            .iter()
+            .enumerate()
+            .map(|(idx, &a)| if (idx & mask != 0) == outcome { a } else { Complex::ZERO })
+            .collect();
+        let prob: f64 = amps.iter().map(|a| a.norm_sqr()).sum();
+        let scale = if prob > 0.0 { 1.0 / prob.sqrt() } else { 0.0 };
+        let projected = StateVector {
+            qubits: self.qubits.clone(),
+            amps: amps.into_iter().map(|a| a * scale).collect(),
+        };
+        Ok((prob, projected))
+    }
+    
+    /// Amplitudes reordered so that `order[0]` is the most significant bit
+    fn reordered(&self, order: &[usize]) -> Result<Vec<Complex>, SimError> {
+        let masks = order
+            .iter()
+            .map(|&node| Ok(self.mask(self.position(node)?)))
+            .collect::<Result<Vec<_>, SimError>>()?;
+        let n = order.len();
+        let mut out = vec![Complex::ZERO; self.amps.len()];
+        for (idx, &amp) in self.amps.iter().enumerate() {
+            let target = masks
+                .iter()
+                .enumerate()
+                .filter(|(_, &m)| idx & m != 0)
+                .fold(0, |acc, (k, _)| acc | 1 << (n - 1 - k));
+            out[target] = amp;
+        }
+        Ok(out)
+    }
+}
+
+/// Simulate a measurement-free pattern on `input_state` (amplitudes over the
+/// external input nodes, first input most significant). Returns the output state with
+/// the first output node most significant.
+pub fn simulate(pattern: &Pattern, input_state: &[Complex]) -> Result<Vec<Complex>, SimError> {
+    let external = pattern.external_inputs();
+    if external.len() > MAX_QUBITS {
+        return Err(SimError::TooManyQubits(external.len()));
+    }
+    let expected = 1 << external.len();
+    if input_state.len() != expected {
+        return Err(SimError::InputSizeMismatch {
+            expected,
+            got: input_state.len(),
+        });
+    }
+    
+    let mut state = StateVector {
+        qubits: external,
+        amps: input_state.to_vec(),
+    };
+    for &node in &pattern.input_nodes {
+        match pattern.input_states.get(&node) {
+            Some(InitState::Plus) => state.add_plus(node)?,
+            Some(InitState::Zero) => {
+                state.add_plus(node)?;
+                state.apply_1q(node, &clifford_matrix(CliffordGate::H))?;
+            }
+            None => {}
+        }
+    }
+    
+    for cmd in pattern.iter() {
+        match cmd {
+            Command::N { node } => state.add_plus(*node)?,
+            Command::E { nodes: (a, b) } => state.apply_cz(*a, *b)?,
+            Command::C { node, clifford } => {
+                for &gate in clifford {
+                    state.apply_1q(*node, &clifford_matrix(gate))?;
+                }
+            }
+            Command::Gate {
+                node, name, matrix, ..
+            } => {
+                let u = matrix.ok_or_else(|| SimError::MissingMatrix(name.clone()))?;
+                state.apply_1q(*node, &u)?;
+            }
+            other => return Err(SimError::UnsupportedCommand(other.kind())),
+        }
+    }
+    
+    state.reordered(&pattern.output_nodes)
+}
+
+/// Dense unitary of a measurement-free pattern, mapping input basis states
+/// (first input most significant) to output states
+pub fn pattern_unitary(pattern: &Pattern) -> Result<Matrix, SimError> {
+    let n_inputs = pattern.external_inputs().len();
+    if n_inputs > MAX_QUBITS {
+        return Err(SimError::TooManyQubits(n_inputs));
+    }
+    let dim_in = 1 << n_inputs;
+    let dim_out = 1 << pattern.output_nodes.len();
+    
+    let mut matrix = vec![vec![Complex::ZERO; dim_in]; dim_out];
+    for col in 0..dim_in {
+        let mut basis = vec![Complex::ZERO; dim_in];
+        basis[col] = Complex::ONE;
+        for (row, amp) in simulate(pattern, &basis)?.into_iter().enumerate() {
+            matrix[row][col] = amp;
+        }
+    }
+    Ok(matrix)
+}
+
+impl Pattern {
+    /// Whether the pattern routes its inputs unchanged to its outputs: the
+    /// outputs are the inputs in the same order, every command acts only on
+    /// those nodes, there are no measurements, preparations or classical
+    /// data, and the commands multiply to the identity up to a global phase.
+    /// Entanglement-only patterns are checked structurally (every edge must
+    /// occur an even number of times); others are simulated, and never
+    /// recognized beyond a few qubits.
+    pub fn is_identity(&self) -> bool {
+        if self.output_nodes != self.input_nodes
+            || !self.input_states.is_empty()
+            || !self.classical_input_nodes.is_empty()
+            || !self.classical_output_nodes.is_empty()
+            || self
+                .iter()
+                .flat_map(|cmd| cmd.nodes())
+                .any(|node| !self.input_nodes.contains(&node))
+        {
+            return false;
+        }
+        
+        if self.iter().all(|cmd| cmd.kind() == CommandKind::E) {
+            let mut odd_edges = HashSet::new();
+            for (a, b) in self.entanglements() {
+                let edge = (a.min(b), a.max(b));
+                if !odd_edges.remove(&edge) {
+                    odd_edges.insert(edge);
+                }
+            }
+            return odd_edges.is_empty();
+        }
+        
+        if self.input_nodes.len() > IDENTITY_CHECK_MAX_QUBITS {
+            return false;
+        }
+        let dim = 1 << self.input_nodes.len();
+        let identity: Matrix = (0..dim)
+            .map(|row| {
+                (0..dim)
+                    .map(|col| if row == col { Complex::ONE } else { Complex::ZERO })
+                    .collect()
+            })
+            .collect();
+        pattern_unitary(self).is_ok_and(|unitary| unitary_close(&unitary, &identity, 1e-9))
+    }
+}
+
+/// Whether `a` and `b` are equal up to a global phase, entrywise within `tol`
+pub fn unitary_close(a: &Matrix, b: &Matrix, tol: f64) -> bool {
+    if a.len() != b.len() || a.iter().zip(b).any(|(ra, rb)| ra.len() != rb.len()) {
+        return false;
+    }
+    
+    // Fix the phase on the largest entry of `b`
+    let pivot = b
+        .iter()
+        .enumerate()
+        .flat_map(|(i, row)| row.iter().enumerate().map(move |(j, v)| (i, j, v.norm_sqr())))
+        .max_by(|x, y| x.2.total_cmp(&y.2));
+    let phase = match pivot {
+        Some((i, j, norm)) if norm > 0.0 => {
+            let ratio = a[i][j] * b[i][j].conj() * (1.0 / norm);
+            let magnitude = ratio.norm_sqr().sqrt();
+            if magnitude == 0.0 {
+                return false;
+            }
+            ratio * (1.0 / magnitude)
+        }
+        _ => Complex::ONE,
+    };
+    
+    a.iter().zip(b).all(|(ra, rb)| {
+        ra.iter()
+            .zip(rb)
+            .all(|(&x, &y)| (x - phase * y).norm_sqr().sqrt() <= tol)
+    })
+}
+
+/// Angles `(beta, gamma, delta)` with `u = e^{iα} Rz(beta) Ry(gamma) Rz(delta)`
+/// for some global phase `α`
+pub(crate) fn zyz_angles(u: &Gate2x2) -> (f64, f64, f64) {
+    let arg = |c: Complex| c.im.atan2(c.re);
+    let det = u[0][0] * u[1][1] - u[0][1] * u[1][0];
+    let half_phase = arg(det) / 2.0;
+    let unphase = Complex::new(half_phase.cos(), -half_phase.sin());
+    let (v00, v10, v11) = (u[0][0] * unphase, u[1][0] * unphase, u[1][1] * unphase);
+    
+    let (cos, sin) = (v00.norm_sqr().sqrt(), v10.norm_sqr().sqrt());
+    let gamma = 2.0 * sin.atan2(cos);
+    let sum = if cos > 1e-12 { 2.0 * arg(v11) } else { 0.0 };
+    let diff = if sin > 1e-12 { 2.0 * arg(v10) } else { 0.0 };
+    ((sum + diff) / 2.0, gamma, (sum - diff) / 2.0)
+}
+
+/// Matrix of a single-qubit rotation op (`Rx`, `Ry`, `Rz`) by `theta`
+pub(crate) fn rotation_matrix(name: &str, theta: f64) -> Option<Gate2x2> {
+    let (c, s) = ((theta / 2.0).cos(), (theta / 2.0).sin());
+    let (cos, z) = (Complex::new(c, 0.0), Complex::ZERO);
+    match name {
+        "Rx" => Some([[cos, Complex::new(0.0, -s)], [Complex::new(0.0, -s), cos]]),
+        "Ry" => Some([[cos, Complex::new(-s, 0.0)], [Complex::new(s, 0.0), cos]]),
+        "Rz" => Some([[Complex::new(c, -s), z], [z, Complex::new(c, s)]]),
+        _ => None,
+    }
+}
+
+/// One measurement branch of a HUGR simulation
+struct Branch {
+    prob: f64,
+    state: StateVector,
+    bits: HashMap<Wire, bool>,
+}
+
+/// Distribution over the outputs of a converted HUGR run on `input_state`
+/// (amplitudes over the input qubits, first input most significant). Each
+/// key lists the output values in port order, qubit outputs read out in the
+/// Z basis; outcomes with zero probability are omitted.
+pub fn hugr_distribution(hugr: &Hugr, input_state: &[Complex]) -> Result<BTreeMap<Vec<bool>, f64>, SimError> {
+    let hugr = &hugr.flatten_regions();
+    let input = hugr.input_node().ok_or(SimError::MissingBoundary)?;
+    let output = hugr.output_node().ok_or(SimError::MissingBoundary)?;
+    // Classical inputs follow the qubits and are read as `false`
+    let n_inputs = hugr
+        .input_types()
+        .map_or(0, |types| types.iter().filter(|&ty| *ty == HugrType::Qubit).count());
+    if n_inputs > MAX_QUBITS {
+        return Err(SimError::TooManyQubits(n_inputs));
+    }
+    let expected = 1 << n_inputs;
+    if input_state.len() != expected {
+        return Err(SimError::InputSizeMismatch {
+            expected,
+            got: input_state.len(),
+        });
+    }
+    
+    // Physical qubit carried by each qubit wire; the same in every branch
+    let mut qubits: HashMap<Wire, usize> = (0..n_inputs)
+        .map(|port| (Wire::new(input.id, port), port))
+        .collect();
+    let mut next_qubit = n_inputs;
+    let mut branches = vec![Branch {
+        prob: 1.0,
+        state: StateVector {
+            qubits: (0..n_inputs).collect(),
+            amps: input_state.to_vec(),
+        },
+        bits: HashMap::new(),
+    }];
+    let qubit_of = |qubits: &HashMap<Wire, usize>, wire: &Wire| {
+        qubits.get(wire).copied().ok_or(SimError::NodeNotFound(wire.node_id))
+    };
+    
+    let mut ids: Vec<_> = hugr.nodes.keys().cloned().collect();
+    ids.sort();
+    for id in ids {
+        let node = &hugr.nodes[&id];
+        match &node.operation {
+            Operation::Input { .. }
+            | Operation::Output { .. }
+            | Operation::Const { .. }
+            | Operation::DFG { .. } => {}
+            // Case nodes precede their contents, so this fires before any
+            // op inside a branch is reached
+            Operation::Conditional { .. } | Operation::Case { .. } => {
+                return Err(SimError::UnsupportedOperation(node.operation.name().to_string()))
+            }
+            Operation::LoadConst { const_node } => {
+                let value = match hugr.get_node(*const_node).map(|n| &n.operation) {
+                    Some(Operation::Const {
+                        value: ConstValue::Bool(b),
+                    }) => *b,
+                    _ => return Err(SimError::UnsupportedOperation("LoadConst".to_string())),
+                };
+                for branch in &mut branches {
+                    branch.bits.insert(node.out(0), value);
+                }
+            }
+            Operation::Custom { name, args, .. } => match name.as_str() {
+                "PrepareQubit" => {
+                    for branch in &mut branches {
+                        branch.state.add_plus(next_qubit)?;
+                    }
+                    qubits.insert(node.out(0), next_qubit);
+                    next_qubit += 1;
+                }
+                // Scopes only bracket a qubit's lifetime: `PrepareQubit`
+                // allocates the simulated qubit and measured qubits are
+                // never reused
+                "QAlloc" | "QFree" => {}
+                "SWAP" => {
+                    let (a, b) = (qubit_of(&qubits, &node.inputs[0])?, qubit_of(&qubits, &node.inputs[1])?);
+                    qubits.insert(node.out(0), b);
+                    qubits.insert(node.out(1), a);
+                }
+                "CZ" | "CX" => {
+                    let (c, t) = (qubit_of(&qubits, &node.inputs[0])?, qubit_of(&qubits, &node.inputs[1])?);
+                    let h = clifford_matrix(CliffordGate::H);
+                    for branch in &mut branches {
+                        if name == "CX" {
+                            branch.state.apply_1q(t, &h)?;
+                        }
+                        branch.state.apply_cz(c, t)?;
+                        if name == "CX" {
+                            branch.state.apply_1q(t, &h)?;
+                        }
+                    }
+                    qubits.insert(node.out(0), c);
+                    qubits.insert(node.out(1), t);
+                }
+                "ConditionalX" | "ConditionalZ" => {
+                    let q = qubit_of(&qubits, &node.inputs[1])?;
+                    let gate = clifford_matrix(if name == "ConditionalX" {
+                        CliffordGate::X
+                    } else {
+                        CliffordGate::Z
+                    });
+                    for branch in &mut branches {
+                        let condition = branch.bits.get(&node.inputs[0]).copied().unwrap_or(false);
+                        if condition {
+                            branch.state.apply_1q(q, &gate)?;
+                        }
+                    }
+                    qubits.insert(node.out(0), q);
+                }
+                "XOR" | "AND" | "OR" => {
+                    for branch in &mut branches {
+                        let a = branch.bits.get(&node.inputs[0]).copied().unwrap_or(false);
+                        let b = branch.bits.get(&node.inputs[1]).copied().unwrap_or(false);
+                        let value = match name.as_str() {
+                            "XOR" => a ^ b,
+                            "AND" => a && b,
+                            _ => a || b,
+                        };
+                        branch.bits.insert(node.out(0), value);
+                    }
+                }
+                "Measure" => {
+                    let q = qubit_of(&qubits, &node.inputs[0])?;
+                    // A non-destructive measure keeps the qubit on port 0
+                    let bit_port = node.outputs.len() - 1;
+                    if bit_port == 1 {
+                        qubits.insert(node.out(0), q);
+                    }
+                    let mut split = Vec::with_capacity(2 * branches.len());
+                    for branch in branches {
+                        for outcome in [false, true] {
+                            let (p, state) = branch.state.project(q, outcome)?;
+                            if branch.prob * p > 1e-12 {
+                                let mut bits = branch.bits.clone();
+                                bits.insert(node.out(bit_port), outcome);
+                                split.push(Branch {
+                                    prob: branch.prob * p,
+                                    state,
+                                    bits,
+                                });
+                            }
+                        }
+                    }
+                    branches = split;
+                }
+                "Barrier" => {
+                    for (port, wire) in node.inputs.iter().enumerate() {
+                        qubits.insert(node.out(port), qubit_of(&qubits, wire)?);
+                    }
+                }
+                other => {
+                    let gate = CliffordGate::try_from(&node.operation)
+                        .ok()
+                        .map(clifford_matrix)
+                        .or_else(|| rotation_matrix(other, args.first().copied().unwrap_or(0.0)))
+                        .ok_or_else(|| SimError::UnsupportedOperation(other.to_string()))?;
+                    let q = qubit_of(&qubits, &node.inputs[0])?;
+                    for branch in &mut branches {
+                        branch.state.apply_1q(q, &gate)?;
+                    }
+                    qubits.insert(node.out(0), q);
+                }
+            },
+        }
+    }
+    
+    // Read out every output, measuring qubit outputs in the Z basis
+    let mut distribution = BTreeMap::new();
+    for branch in branches {
+        let mut readouts = vec![(branch.prob, branch.state, Vec::new())];
+        for wire in &output.inputs {
+            let mut next = Vec::new();
+            for (prob, state, values) in readouts {
+                if let Some(&bit) = branch.bits.get(wire) {
+                    let mut values = values;
+                    values.push(bit);
+                    next.push((prob, state, values));
+                    continue;
+                }
+                let q = qubit_of(&qubits, wire)?;
+                for outcome in [false, true] {
+                    let (p, projected) = state.project(q, outcome)?;
+                    if prob * p > 1e-12 {
+                        let mut values = values.clone();
+                        values.push(outcome);
+                        next.push((prob * p, projected, values));
+                    }
+                }
+            }
+            readouts = next;
+        }
+        for (prob, _, values) in readouts {
+            *distribution.entry(values).or_insert(0.0) += prob;
+        }
+    }
+    Ok(distribution)
+}
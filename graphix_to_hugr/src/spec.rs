@@ -0,0 +1,99 @@
+use crate::hugr::{ConstValue, FunctionType, Hugr, HugrType, Operation};
+use serde_json::{json, Value};
+
+impl Hugr {
+    /// Export in the external HUGR spec layout: a sorted `nodes` array whose
+    /// entries carry an `op` discriminator, and explicit
+    /// `[src_node, src_port, dst_node, dst_port]` edges. Unlike the serde
+    /// form this is stable across runs, so it can be diffed.
+    pub fn to_spec_json(&self) -> String {
+        let mut ids: Vec<_> = self.nodes.keys().cloned().collect();
+        ids.sort();
+        
+        let nodes: Vec<Value> = ids
+            .iter()
+            .map(|id| {
+                let mut entry = spec_op(&self.nodes[id].operation);
+                entry["id"] = json!(id);
+                entry
+            })
+            .collect();
+        let edges: Vec<Value> = ids
+            .iter()
+            .flat_map(|id| {
+                self.nodes[id]
+                    .inputs
+                    .iter()
+                    .enumerate()
+                    .map(move |(dst_port, wire)| json!([wire.node_id, wire.port, id, dst_port]))
+            })
+            .collect();
+        
+        let spec = json!({
+            "root": self.root,
+            "nodes": nodes,
+            "edges": edges,
+        });
+        serde_json::to_string_pretty(&spec).expect("spec JSON is always serializable")
+    }
+}
+
+fn spec_op(op: &Operation) -> Value {
+    match op {
+        Operation::Input { types } => json!({ "op": "Input", "types": spec_types(types) }),
+        Operation::Output { types } => json!({ "op": "Output", "types": spec_types(types) }),
+        Operation::Custom {
+            name,
+            signature,
+            extension,
+            args,
+        } => json!({
+            "op": "Custom",
+            "extension": extension,
+            "name": name,
+            "signature": spec_signature(signature),
+            "args": args,
+        }),
+        Operation::Const { value } => json!({ "op": "Const", "value": spec_const(value) }),
+        Operation::LoadConst { const_node } => json!({ "op": "LoadConst", "const_node": const_node }),
+        Operation::DFG { signature } => json!({ "op": "DFG", "signature": spec_signature(signature) }),
+        Operation::Conditional { signature } => {
+            json!({ "op": "Conditional", "signature": spec_signature(signature) })
+        }
+        Operation::Case { signature } => json!({ "op": "Case", "signature": spec_signature(signature) }),
+    }
+}
+
+fn spec_signature(signature: &FunctionType) -> Value {
+    json!({
+        "input": spec_types(&signature.inputs),
+        "output": spec_types(&signature.outputs),
+    })
+}
+
+fn spec_types(types: &[HugrType]) -> Vec<String> {
+    types.iter().map(spec_type).collect()
+}
+
+fn spec_type(ty: &HugrType) -> String {
+    match ty {
+        HugrType::Qubit => "Qubit".to_string(),
+        HugrType::Bool => "Bool".to_string(),
+        HugrType::Float64 => "Float64".to_string(),
+        HugrType::Int(width) => format!("Int({})", width),
+        HugrType::Sum(variants) => {
+            let variants: Vec<String> = variants
+                .iter()
+                .map(|row| format!("[{}]", spec_types(row).join(", ")))
+                .collect();
+            format!("Sum({})", variants.join(", "))
+        }
+    }
+}
+
+fn spec_const(value: &ConstValue) -> Value {
+    match value {
+        ConstValue::Bool(b) => json!({ "type": "Bool", "value": b }),
+        ConstValue::Float(f) => json!({ "type": "Float64", "value": f }),
+    }
+}
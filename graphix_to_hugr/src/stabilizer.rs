@@ -0,0 +1,267 @@
+use crate::types::{Angle, CliffordGate, Command, InitState, Pattern, Pauli, PauliString, Plane};
+use std::collections::BTreeMap;
+use std::f64::consts::FRAC_PI_2;
+
+/// Tolerance when deciding whether a measurement angle is a multiple of π/2
+const ANGLE_TOLERANCE: f64 = 1e-9;
+
+/// How a measurement's outcome is distributed before it is made
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Determinism {
+    /// Fixed by the state prepared so far
+    Deterministic,
+    /// Uniformly random
+    Random,
+    /// Fixed or random depending on the state fed to the inputs
+    InputDependent,
+}
+
+impl Determinism {
+    /// Value stored in the `outcome` metadata of annotated `Measure` nodes
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Determinism::Deterministic => "deterministic",
+            Determinism::Random => "random",
+            Determinism::InputDependent => "input_dependent",
+        }
+    }
+}
+
+impl Pauli {
+    fn has_x(self) -> bool {
+        matches!(self, Pauli::X | Pauli::Y)
+    }
+    
+    fn has_z(self) -> bool {
+        matches!(self, Pauli::Y | Pauli::Z)
+    }
+    
+    fn from_parts(x: bool, z: bool) -> Pauli {
+        match (x, z) {
+            (false, false) => Pauli::I,
+            (true, false) => Pauli::X,
+            (true, true) => Pauli::Y,
+            (false, true) => Pauli::Z,
+        }
+    }
+    
+    /// Product with `other`, up to phase
+    fn times(self, other: Pauli) -> Pauli {
+        Pauli::from_parts(self.has_x() != other.has_x(), self.has_z() != other.has_z())
+    }
+}
+
+/// Factor of `row` on `node`
+fn factor(row: &PauliString, node: usize) -> Pauli {
+    row.get(&node).copied().unwrap_or(Pauli::I)
+}
+
+/// Set the factor of `row` on `node`, keeping identities out of the map
+fn set_factor(row: &mut PauliString, node: usize, pauli: Pauli) {
+    if pauli == Pauli::I {
+        row.remove(&node);
+    } else {
+        row.insert(node, pauli);
+    }
+}
+
+fn toggle_z(row: &mut PauliString, node: usize) {
+    set_factor(row, node, factor(row, node).times(Pauli::Z));
+}
+
+fn commutes(a: &PauliString, b: &PauliString) -> bool {
+    a.iter()
+        .filter(|&(node, &pauli)| {
+            let other = factor(b, *node);
+            other != Pauli::I && other != pauli
+        })
+        .count()
+        .is_multiple_of(2)
+}
+
+/// Multiply `row` by `other` in place, up to sign
+fn multiply(row: &mut PauliString, other: &PauliString) {
+    for (&node, &pauli) in other {
+        set_factor(row, node, factor(row, node).times(pauli));
+    }
+}
+
+/// Smallest factor, ordering the X part before the Z part on each node
+fn pivot(row: &PauliString) -> Option<(usize, bool)> {
+    row.iter().next().map(|(&node, pauli)| (node, !pauli.has_x()))
+}
+
+fn has(row: &PauliString, (node, is_z): (usize, bool)) -> bool {
+    let pauli = factor(row, node);
+    if is_z {
+        pauli.has_z()
+    } else {
+        pauli.has_x()
+    }
+}
+
+/// Single-qubit Pauli measured by `plane`/`angle`, or `None` if the angle is
+/// not a multiple of π/2
+fn measured_pauli(node: usize, plane: Plane, angle: f64) -> Option<PauliString> {
+    let quarter_turns = angle / FRAC_PI_2;
+    let rounded = quarter_turns.round();
+    if (quarter_turns - rounded).abs() > ANGLE_TOLERANCE {
+        return None;
+    }
+    let odd = rounded.rem_euclid(2.0) == 1.0;
+    let pauli = match (plane, odd) {
+        (Plane::XY, false) | (Plane::XZ, true) => Pauli::X,
+        (Plane::XY, true) | (Plane::YZ, true) => Pauli::Y,
+        (Plane::YZ, false) | (Plane::XZ, false) => Pauli::Z,
+    };
+    Some([(node, pauli)].into_iter().collect())
+}
+
+/// Stabilizer generators (signs ignored) of the state prepared so far.
+/// Input qubits fed from outside contribute no generator.
+#[derive(Debug, Default)]
+struct Stabilizers {
+    generators: Vec<PauliString>,
+}
+
+impl Stabilizers {
+    /// Generators of the graph state built by the leading `N` and `E`
+    /// commands of `pattern`, taken from `Pattern::stabilizers`. Returns them
+    /// with the number of commands consumed.
+    fn of_graph_state(pattern: &Pattern) -> (Stabilizers, usize) {
+        let prepared = pattern
+            .commands
+            .iter()
+            .position(|cmd| !matches!(cmd, Command::N { .. } | Command::E { .. }))
+            .unwrap_or(pattern.commands.len());
+        let mut graph = pattern.clone();
+        graph.commands.truncate(prepared);
+        
+        // Each generator is X on its own node and Z on the neighbours. An input
+        // in |0> keeps just its Z, and one fed from outside has no generator.
+        let generators = graph
+            .stabilizers()
+            .into_iter()
+            .filter_map(|generator| {
+                let (&node, _) = generator.iter().find(|(_, &pauli)| pauli == Pauli::X)?;
+                if !pattern.input_nodes.contains(&node) {
+                    return Some(generator);
+                }
+                match pattern.input_states.get(&node)? {
+                    InitState::Plus => Some(generator),
+                    InitState::Zero => Some([(node, Pauli::Z)].into_iter().collect()),
+                }
+            })
+            .collect();
+        (Stabilizers { generators }, prepared)
+    }
+    
+    fn conjugate(&mut self, f: impl Fn(&mut PauliString)) {
+        self.generators.iter_mut().for_each(f);
+    }
+    
+    /// Whether `pauli` is, up to sign, a product of the generators
+    fn contains(&self, pauli: &PauliString) -> bool {
+        let mut pivots: Vec<((usize, bool), PauliString)> = Vec::new();
+        for generator in &self.generators {
+            let mut row = generator.clone();
+            for (pivot, pivot_row) in &pivots {
+                if has(&row, *pivot) {
+                    multiply(&mut row, pivot_row);
+                }
+            }
+            if let Some(pivot) = pivot(&row) {
+                pivots.push((pivot, row));
+            }
+        }
+        let mut target = pauli.clone();
+        for (pivot, pivot_row) in &pivots {
+            if has(&target, *pivot) {
+                multiply(&mut target, pivot_row);
+            }
+        }
+        target.is_empty()
+    }
+    
+    /// Classify a measurement of `pauli`, then project onto its eigenspace
+    fn measure(&mut self, pauli: PauliString) -> Determinism {
+        let anticommuting: Vec<usize> = (0..self.generators.len())
+            .filter(|&i| !commutes(&self.generators[i], &pauli))
+            .collect();
+        if let Some((&first, rest)) = anticommuting.split_first() {
+            let pivot = self.generators[first].clone();
+            for &i in rest {
+                multiply(&mut self.generators[i], &pivot);
+            }
+            self.generators[first] = pauli;
+            Determinism::Random
+        } else if self.contains(&pauli) {
+            Determinism::Deterministic
+        } else {
+            self.generators.push(pauli);
+            Determinism::InputDependent
+        }
+    }
+}
+
+/// Outcome distribution of each measurement in a Clifford pattern, by
+/// stabilizer simulation of the graph state and measurement bases. Pauli
+/// corrections only flip signs, so they never change the classification.
+/// The analysis stops at the first command it cannot track (a non-Pauli
+/// measurement, a computed angle, a `T` correction, a custom gate or a
+/// branch); later measurements are left out.
+pub fn compute_measurement_determinism(pattern: &Pattern) -> BTreeMap<usize, Determinism> {
+    let (mut state, prepared) = Stabilizers::of_graph_state(pattern);
+    let mut outcomes = BTreeMap::new();
+    for cmd in &pattern.commands[prepared..] {
+        match cmd {
+            Command::N { node } => state.generators.push([(*node, Pauli::X)].into_iter().collect()),
+            Command::E { nodes: (a, b) } => state.conjugate(|row| {
+                let (x_on_a, x_on_b) = (factor(row, *a).has_x(), factor(row, *b).has_x());
+                if x_on_a {
+                    toggle_z(row, *b);
+                }
+                if x_on_b {
+                    toggle_z(row, *a);
+                }
+            }),
+            Command::C { clifford, .. } if !clifford.iter().all(CliffordGate::is_clifford) => break,
+            Command::C { node, clifford } => {
+                for gate in clifford {
+                    match gate {
+                        CliffordGate::H => state.conjugate(|row| {
+                            let pauli = factor(row, *node);
+                            set_factor(row, *node, Pauli::from_parts(pauli.has_z(), pauli.has_x()));
+                        }),
+                        CliffordGate::S | CliffordGate::SDG => state.conjugate(|row| {
+                            if factor(row, *node).has_x() {
+                                toggle_z(row, *node);
+                            }
+                        }),
+                        CliffordGate::I | CliffordGate::X | CliffordGate::Y | CliffordGate::Z => {}
+                        CliffordGate::T | CliffordGate::TDG => unreachable!("checked above"),
+                    }
+                }
+            }
+            Command::X { .. } | Command::Z { .. } | Command::XControlled { .. } => {}
+            Command::M { node, plane, angle }
+            | Command::MSoft { node, plane, angle }
+            | Command::MAdaptive {
+                node,
+                plane,
+                angle: Angle::Fixed(angle),
+            } => {
+                let Some(pauli) = measured_pauli(*node, *plane, *angle) else {
+                    break;
+                };
+                outcomes.insert(*node, state.measure(pauli));
+            }
+            Command::MZ { node } => {
+                let pauli = [(*node, Pauli::Z)].into_iter().collect();
+                outcomes.insert(*node, state.measure(pauli));
+            }
+            Command::MAdaptive { .. } | Command::Gate { .. } | Command::Branch { .. } => break,
+        }
+    }
+    outcomes
+}
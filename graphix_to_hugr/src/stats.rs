@@ -0,0 +1,81 @@
+use crate::hugr::{Hugr, Operation};
+use crate::types::{CommandKind, Pattern};
+use std::f64::consts::FRAC_PI_4;
+use std::fmt;
+
+/// Tolerance when deciding whether a rotation angle is a multiple of π/4
+const ANGLE_TOLERANCE: f64 = 1e-9;
+
+impl Hugr {
+    /// Number of T-type gates: explicit `T`/`Tdg` ops plus `Rx`/`Ry`/`Rz`
+    /// rotations by an odd multiple of π/4. Clifford rotations (multiples of
+    /// π/2) and other angles are not counted.
+    pub fn t_count(&self) -> usize {
+        self.nodes
+            .values()
+            .filter(|node| match &node.operation {
+                Operation::Custom { name, args, .. } => match name.as_str() {
+                    "T" | "Tdg" => true,
+                    "Rx" | "Ry" | "Rz" => args.first().is_some_and(|&angle| is_odd_pi_over_4(angle)),
+                    _ => false,
+                },
+                _ => false,
+            })
+            .count()
+    }
+}
+
+fn is_odd_pi_over_4(angle: f64) -> bool {
+    let multiple = angle / FRAC_PI_4;
+    let rounded = multiple.round();
+    (multiple - rounded).abs() < ANGLE_TOLERANCE && rounded.rem_euclid(2.0) == 1.0
+}
+
+/// Size and shape of a pattern, for logging
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternSummary {
+    pub n_inputs: usize,
+    pub n_outputs: usize,
+    pub n_commands: usize,
+    pub n_prepared: usize,
+    pub n_measured: usize,
+    pub n_entanglements: usize,
+    /// Largest number of outcomes any single command is conditioned on
+    pub max_correction_domain_size: usize,
+}
+
+impl fmt::Display for PatternSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "in={} out={} cmds={} N={} M={} E={} max_domain={}",
+            self.n_inputs,
+            self.n_outputs,
+            self.n_commands,
+            self.n_prepared,
+            self.n_measured,
+            self.n_entanglements,
+            self.max_correction_domain_size
+        )
+    }
+}
+
+impl Pattern {
+    /// Counts describing the pattern's size and shape
+    pub fn summary(&self) -> PatternSummary {
+        PatternSummary {
+            n_inputs: self.input_nodes.len(),
+            n_outputs: self.output_nodes.len(),
+            n_commands: self.commands.len(),
+            n_prepared: self.commands_of_kind(CommandKind::N).count(),
+            n_measured: self.measured_nodes().len(),
+            n_entanglements: self.entanglements().count(),
+            max_correction_domain_size: self
+                .iter()
+                .filter_map(|cmd| cmd.domain())
+                .map(|domain| domain.len())
+                .max()
+                .unwrap_or(0),
+        }
+    }
+}
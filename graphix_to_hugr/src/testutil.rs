@@ -0,0 +1,41 @@
+use crate::hugr::{Hugr, Node, Wire};
+
+/// Nodes whose operation is named `op_name`, in id order
+pub fn find_ops<'a>(hugr: &'a Hugr, op_name: &str) -> Vec<&'a Node> {
+    let mut nodes: Vec<&Node> = hugr
+        .nodes
+        .values()
+        .filter(|node| node.operation.name() == op_name)
+        .collect();
+    nodes.sort_by_key(|node| node.id);
+    nodes
+}
+
+/// Assert that exactly `expected` nodes are named `op_name`
+#[track_caller]
+pub fn assert_op_count(hugr: &Hugr, op_name: &str, expected: usize) {
+    let found = find_ops(hugr, op_name).len();
+    assert_eq!(
+        found, expected,
+        "expected {} {} node(s), found {}",
+        expected, op_name, found
+    );
+}
+
+/// Assert that output `port` of node `producer` is consumed by a node named
+/// `consumer_op_name`
+#[track_caller]
+pub fn assert_wire_feeds(hugr: &Hugr, producer: usize, port: usize, consumer_op_name: &str) {
+    let wire = Wire::new(producer, port);
+    let consumers: Vec<&str> = hugr
+        .nodes
+        .values()
+        .filter(|node| node.inputs.contains(&wire))
+        .map(|node| node.operation.name())
+        .collect();
+    assert!(
+        consumers.contains(&consumer_op_name),
+        "wire {}:{} feeds {:?}, not {}",
+        producer, port, consumers, consumer_op_name
+    );
+}
@@ -1,16 +1,34 @@
-use serde::{Deserialize, Serialize};
-use std::collections::{HashSet};
+use crate::angle::PiFraction;
+use crate::converter::{ClassicalOp, ConversionError, QUANTUM_EXTENSION};
+use crate::hugr::{FunctionType, HugrType, Operation};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::{Add, Mul, Range, Sub};
+use thiserror::Error;
 
 /// Measurement plane in MBQC
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Plane {
     XY,
     YZ,
     XZ,
 }
 
+/// Measurement angle in radians, fixed or depending on earlier outcomes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Angle {
+    Fixed(f64),
+    /// `base + parity(shift_domain) * shift`, computed at run time
+    Computed {
+        base: f64,
+        #[serde(serialize_with = "serialize_sorted")]
+        shift_domain: HashSet<usize>,
+        shift: f64,
+    },
+}
+
 /// Command kind enumeration
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CommandKind {
     /// Prepare node (N command)
     N,
@@ -18,16 +36,29 @@ pub enum CommandKind {
     E,
     /// Measure node (M command)
     M,
+    /// Measure node in the Z basis (MZ command)
+    MZ,
+    /// Measure node with a soft outcome (MSoft command)
+    MSoft,
+    /// Measure node at an angle computed from earlier outcomes
+    MAdaptive,
     /// Pauli X correction (X command)
     X,
     /// Pauli Z correction (Z command)
     Z,
+    /// X correction on a combination of outcomes
+    XControlled,
     /// Clifford correction (C command)
     C,
+    /// User-defined single-qubit gate
+    Gate,
+    /// Classical branch on a combination of outcomes
+    Branch,
 }
 
-/// Clifford gate elements
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Clifford gate elements, plus the non-Clifford `T`/`TDG` that compiled
+/// patterns apply as byproduct corrections
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CliffordGate {
     I,    // Identity
     X,    // Pauli X
@@ -36,8 +67,152 @@ pub enum CliffordGate {
     S,    // S gate
     SDG,  // S dagger
     H,    // Hadamard
+    T,    // T gate (not Clifford)
+    TDG,  // T dagger (not Clifford)
+}
+
+impl CliffordGate {
+    pub const ALL: [CliffordGate; 9] = [
+        CliffordGate::I,
+        CliffordGate::X,
+        CliffordGate::Y,
+        CliffordGate::Z,
+        CliffordGate::S,
+        CliffordGate::SDG,
+        CliffordGate::H,
+        CliffordGate::T,
+        CliffordGate::TDG,
+    ];
+    
+    /// Whether the gate is in the Clifford group (everything but `T`/`TDG`)
+    pub fn is_clifford(&self) -> bool {
+        !matches!(self, CliffordGate::T | CliffordGate::TDG)
+    }
+    
+    /// Name of the gate in the quantum extension
+    pub fn op_name(&self) -> &'static str {
+        match self {
+            CliffordGate::I => "I",
+            CliffordGate::X => "X",
+            CliffordGate::Y => "Y",
+            CliffordGate::Z => "Z",
+            CliffordGate::S => "S",
+            CliffordGate::SDG => "Sdg",
+            CliffordGate::H => "H",
+            CliffordGate::T => "T",
+            CliffordGate::TDG => "Tdg",
+        }
+    }
+}
+
+impl From<CliffordGate> for Operation {
+    fn from(gate: CliffordGate) -> Self {
+        Operation::Custom {
+            name: gate.op_name().to_string(),
+            signature: FunctionType::new(vec![HugrType::Qubit], vec![HugrType::Qubit]),
+            extension: QUANTUM_EXTENSION.to_string(),
+            args: vec![],
+        }
+    }
+}
+
+impl TryFrom<&Operation> for CliffordGate {
+    type Error = ConversionError;
+    
+    fn try_from(op: &Operation) -> Result<Self, Self::Error> {
+        match op {
+            Operation::Custom {
+                name,
+                signature,
+                extension,
+                args,
+            } if extension == QUANTUM_EXTENSION
+                && args.is_empty()
+                && signature.inputs == [HugrType::Qubit]
+                && signature.outputs == [HugrType::Qubit] =>
+            {
+                CliffordGate::ALL
+                    .into_iter()
+                    .find(|gate| gate.op_name() == name)
+                    .ok_or_else(|| ConversionError::NotACliffordGate(name.clone()))
+            }
+            other => Err(ConversionError::NotACliffordGate(format!("{:?}", other))),
+        }
+    }
+}
+
+/// Complex number, used for user-supplied gate matrices
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const ZERO: Complex = Complex { re: 0.0, im: 0.0 };
+    pub const ONE: Complex = Complex { re: 1.0, im: 0.0 };
+    pub const I: Complex = Complex { re: 0.0, im: 1.0 };
+    
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+    
+    pub fn conj(&self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+    
+    pub fn norm_sqr(&self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Mul<f64> for Complex {
+    type Output = Complex;
+    
+    fn mul(self, rhs: f64) -> Complex {
+        Complex::new(self.re * rhs, self.im * rhs)
+    }
+}
+
+/// Single-qubit Pauli operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Pauli {
+    I,
+    X,
+    Y,
+    Z,
 }
 
+/// Tensor product of Paulis, keyed by node (identity on absent nodes)
+pub type PauliString = BTreeMap<usize, Pauli>;
+
 /// Represents a Graphix command
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Command {
@@ -54,44 +229,291 @@ pub enum Command {
         angle: f64,
     },
     
+    /// Measure a node directly in the computational (Z) basis
+    MZ { node: usize },
+    
+    /// Measure a node like `M`, also producing a `Float64` likelihood of the
+    /// outcome for soft-decision decoders
+    MSoft {
+        node: usize,
+        plane: Plane,
+        angle: f64,
+    },
+    
+    /// Measure a node like `M`, at an angle that may depend on earlier
+    /// outcomes
+    MAdaptive {
+        node: usize,
+        plane: Plane,
+        angle: Angle,
+    },
+    
     /// Apply Pauli X correction based on measurement outcomes
     X {
         node: usize,
+        #[serde(serialize_with = "serialize_sorted")]
         domain: HashSet<usize>,
     },
     
     /// Apply Pauli Z correction based on measurement outcomes
     Z {
         node: usize,
+        #[serde(serialize_with = "serialize_sorted")]
         domain: HashSet<usize>,
     },
     
+    /// Apply X when the outcomes of `controls`, folded with `combiner`,
+    /// give 1 (e.g. `And` for a Toffoli-style correction)
+    XControlled {
+        node: usize,
+        #[serde(serialize_with = "serialize_sorted")]
+        controls: HashSet<usize>,
+        combiner: ClassicalOp,
+    },
+    
     /// Apply Clifford correction
     C {
         node: usize,
         clifford: Vec<CliffordGate>,
     },
+    
+    /// Apply a user-defined single-qubit gate
+    Gate {
+        node: usize,
+        name: String,
+        matrix: Option<[[Complex; 2]; 2]>,
+        params: Vec<f64>,
+    },
+    
+    /// Run `if_true` when the parity of the `domain` outcomes is 1 and
+    /// `if_false` otherwise. Both lists must leave the same qubits live.
+    Branch {
+        #[serde(serialize_with = "serialize_sorted")]
+        domain: HashSet<usize>,
+        if_true: Vec<Command>,
+        if_false: Vec<Command>,
+    },
+}
+
+/// Serialize a domain in ascending order so output is deterministic
+fn serialize_sorted<S: Serializer>(domain: &HashSet<usize>, serializer: S) -> Result<S::Ok, S::Error> {
+    let sorted: BTreeSet<&usize> = domain.iter().collect();
+    sorted.serialize(serializer)
+}
+
+/// `commands` with their YZ/XZ measurements rewritten as in
+/// `Pattern::to_xy_plane`
+fn measurements_to_xy(commands: &[Command], symbolic: &HashMap<usize, String>) -> Vec<Command> {
+    let mut rewritten = Vec::with_capacity(commands.len());
+    for cmd in commands {
+        let (node, plane) = match cmd {
+            Command::M { node, plane, .. }
+            | Command::MSoft { node, plane, .. }
+            | Command::MAdaptive { node, plane, .. } => (*node, *plane),
+            Command::Branch {
+                domain,
+                if_true,
+                if_false,
+            } => {
+                rewritten.push(Command::Branch {
+                    domain: domain.clone(),
+                    if_true: measurements_to_xy(if_true, symbolic),
+                    if_false: measurements_to_xy(if_false, symbolic),
+                });
+                continue;
+            }
+            _ => {
+                rewritten.push(cmd.clone());
+                continue;
+            }
+        };
+        let (clifford, sign) = match plane {
+            Plane::YZ => (vec![CliffordGate::H], 1.0),
+            Plane::XZ if !symbolic.contains_key(&node) => (vec![CliffordGate::SDG, CliffordGate::H], -1.0),
+            _ => {
+                rewritten.push(cmd.clone());
+                continue;
+            }
+        };
+        
+        rewritten.push(Command::C { node, clifford });
+        rewritten.push(match cmd {
+            Command::M { angle, .. } => Command::M {
+                node,
+                plane: Plane::XY,
+                angle: sign * angle,
+            },
+            Command::MSoft { angle, .. } => Command::MSoft {
+                node,
+                plane: Plane::XY,
+                angle: sign * angle,
+            },
+            Command::MAdaptive { angle, .. } => Command::MAdaptive {
+                node,
+                plane: Plane::XY,
+                angle: match angle {
+                    Angle::Fixed(angle) => Angle::Fixed(sign * angle),
+                    Angle::Computed {
+                        base,
+                        shift_domain,
+                        shift,
+                    } => Angle::Computed {
+                        base: sign * base,
+                        shift_domain: shift_domain.clone(),
+                        shift: sign * shift,
+                    },
+                },
+            },
+            _ => unreachable!("only measurements are rewritten"),
+        });
+    }
+    rewritten
 }
 
 impl Command {
+    /// Measurement command with an exact angle given as a fraction of π
+    pub fn measure_pi(node: usize, plane: Plane, angle: PiFraction) -> Self {
+        Command::M {
+            node,
+            plane,
+            angle: angle.to_radians(),
+        }
+    }
+    
+    /// Measurement command with the angle given in degrees
+    pub fn measure_deg(node: usize, plane: Plane, degrees: f64) -> Self {
+        Command::M {
+            node,
+            plane,
+            angle: degrees.to_radians(),
+        }
+    }
+    
+    /// Nodes whose qubit the command acts on (domains are not included); for
+    /// `Branch`, the nodes of both cases in ascending order
+    pub fn nodes(&self) -> Vec<usize> {
+        match self {
+            Command::E { nodes: (a, b) } => vec![*a, *b],
+            Command::Branch {
+                if_true, if_false, ..
+            } => if_true
+                .iter()
+                .chain(if_false)
+                .flat_map(|cmd| cmd.nodes())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect(),
+            Command::N { node }
+            | Command::M { node, .. }
+            | Command::MZ { node }
+            | Command::MSoft { node, .. }
+            | Command::MAdaptive { node, .. }
+            | Command::X { node, .. }
+            | Command::Z { node, .. }
+            | Command::XControlled { node, .. }
+            | Command::C { node, .. }
+            | Command::Gate { node, .. } => vec![*node],
+        }
+    }
+    
+    /// Node measured by the command, for the measurement commands
+    pub fn measured_node(&self) -> Option<usize> {
+        match self {
+            Command::M { node, .. }
+            | Command::MZ { node }
+            | Command::MSoft { node, .. }
+            | Command::MAdaptive { node, .. } => Some(*node),
+            _ => None,
+        }
+    }
+    
+    /// Measurement outcomes the command is conditioned on
+    pub fn domain(&self) -> Option<&HashSet<usize>> {
+        match self {
+            Command::X { domain, .. } | Command::Z { domain, .. } => Some(domain),
+            Command::XControlled { controls, .. } => Some(controls),
+            Command::Branch { domain, .. } => Some(domain),
+            Command::MAdaptive {
+                angle: Angle::Computed { shift_domain, .. },
+                ..
+            } => Some(shift_domain),
+            _ => None,
+        }
+    }
+    
     pub fn kind(&self) -> CommandKind {
         match self {
             Command::N { .. } => CommandKind::N,
             Command::E { .. } => CommandKind::E,
             Command::M { .. } => CommandKind::M,
+            Command::MZ { .. } => CommandKind::MZ,
+            Command::MSoft { .. } => CommandKind::MSoft,
+            Command::MAdaptive { .. } => CommandKind::MAdaptive,
             Command::X { .. } => CommandKind::X,
             Command::Z { .. } => CommandKind::Z,
+            Command::XControlled { .. } => CommandKind::XControlled,
             Command::C { .. } => CommandKind::C,
+            Command::Gate { .. } => CommandKind::Gate,
+            Command::Branch { .. } => CommandKind::Branch,
         }
     }
 }
 
+/// State of an input node that is prepared inside the pattern rather than
+/// wired from the function boundary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InitState {
+    /// |+⟩, as prepared by an `N` command
+    Plus,
+    /// |0⟩
+    Zero,
+}
+
+/// Structural mistake found by `Pattern::validate`, pointing at the
+/// top-level command (by position) that makes it
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    #[error("command {command}: node {node} is entangled before it is input or prepared")]
+    UnpreparedNode { command: usize, node: usize },
+    
+    #[error("command {command}: domain refers to node {node}, which is never measured")]
+    UnmeasuredDomainNode { command: usize, node: usize },
+    
+    #[error("command {command}: output node {node} is measured")]
+    MeasuredOutput { command: usize, node: usize },
+    
+    #[error("command {command}: node {node} is prepared while its qubit is still live")]
+    DuplicatePreparation { command: usize, node: usize },
+    
+    #[error("command {command}: node {node} is entangled after it is measured")]
+    EntangledMeasuredNode { command: usize, node: usize },
+    
+    #[error("command {command}: node {node} is entangled with itself")]
+    SelfEntanglement { command: usize, node: usize },
+}
+
 /// Represents a Graphix MBQC pattern
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Pattern {
     pub input_nodes: Vec<usize>,
     pub output_nodes: Vec<usize>,
     pub commands: Vec<Command>,
+    /// Inputs prepared internally instead of taken from the function boundary
+    #[serde(default)]
+    pub input_states: HashMap<usize, InitState>,
+    /// Measured nodes returned as classical outputs, in port order. When
+    /// empty, all measured non-output nodes are returned in ascending order.
+    #[serde(default)]
+    pub classical_output_nodes: Vec<usize>,
+    /// Nodes whose outcome is supplied as a `Bool` input, in port order after
+    /// the qubit inputs. They may appear in correction domains.
+    #[serde(default)]
+    pub classical_input_nodes: Vec<usize>,
+    /// Measured nodes whose angle is a named symbolic parameter, bound after
+    /// conversion with `Hugr::bind_parameter`. The angle in the command is
+    /// only a placeholder.
+    #[serde(default)]
+    pub angle_parameters: HashMap<usize, String>,
 }
 
 impl Pattern {
@@ -100,14 +522,475 @@ impl Pattern {
             input_nodes,
             output_nodes,
             commands: Vec::new(),
+            input_states: HashMap::new(),
+            classical_output_nodes: Vec::new(),
+            classical_input_nodes: Vec::new(),
+            angle_parameters: HashMap::new(),
+        }
+    }
+    
+    /// Check the top-level commands for structural mistakes, reporting every
+    /// one found in command order: `E` on a node not yet input or prepared,
+    /// on an already measured node or on a single node, a domain node that
+    /// is never measured (nor a classical input), a measured output node and
+    /// an `N` for a node whose qubit is still live. As in the converter, a
+    /// measured node may be prepared again.
+    pub fn validate(&self) -> Result<(), Vec<PatternError>> {
+        let mut live: HashSet<usize> = self.input_nodes.iter().cloned().collect();
+        let mut measured = HashSet::new();
+        let outputs: HashSet<usize> = self.output_nodes.iter().cloned().collect();
+        let mut outcomes: HashSet<usize> = self.classical_input_nodes.iter().cloned().collect();
+        outcomes.extend(self.iter().filter_map(Command::measured_node));
+        
+        let mut errors = Vec::new();
+        for (command, cmd) in self.iter().enumerate() {
+            match cmd {
+                Command::N { node } if !live.insert(*node) => {
+                    errors.push(PatternError::DuplicatePreparation { command, node: *node });
+                }
+                Command::E { nodes: (a, b) } if a == b => {
+                    errors.push(PatternError::SelfEntanglement { command, node: *a });
+                }
+                Command::E { nodes: (a, b) } => {
+                    for node in [*a, *b].into_iter().filter(|node| !live.contains(node)) {
+                        errors.push(if measured.contains(&node) {
+                            PatternError::EntangledMeasuredNode { command, node }
+                        } else {
+                            PatternError::UnpreparedNode { command, node }
+                        });
+                    }
+                }
+                _ => {}
+            }
+            if let Some(node) = cmd.measured_node() {
+                live.remove(&node);
+                measured.insert(node);
+                if outputs.contains(&node) {
+                    errors.push(PatternError::MeasuredOutput { command, node });
+                }
+            }
+            let mut missing: Vec<usize> = cmd
+                .domain()
+                .into_iter()
+                .flatten()
+                .filter(|node| !outcomes.contains(node))
+                .cloned()
+                .collect();
+            missing.sort();
+            errors.extend(
+                missing
+                    .into_iter()
+                    .map(|node| PatternError::UnmeasuredDomainNode { command, node }),
+            );
+        }
+        
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
     }
     
+    /// Largest node index referenced by the pattern, if any
+    pub fn max_node(&self) -> Option<usize> {
+        let command_nodes = self.iter().flat_map(|cmd| {
+            let mut nodes = cmd.nodes();
+            nodes.extend(cmd.domain().into_iter().flatten());
+            nodes
+        });
+        self.input_nodes
+            .iter()
+            .chain(&self.output_nodes)
+            .cloned()
+            .chain(command_nodes)
+            .max()
+    }
+    
+    /// Input nodes wired from the function boundary, in declaration order
+    pub fn external_inputs(&self) -> Vec<usize> {
+        self.input_nodes
+            .iter()
+            .cloned()
+            .filter(|node| !self.input_states.contains_key(node))
+            .collect()
+    }
+    
     pub fn add_command(&mut self, command: Command) {
         self.commands.push(command);
     }
     
+    /// Insert `command` before position `index`, shifting later commands.
+    /// Panics if `index > self.commands.len()`.
+    pub fn insert_command(&mut self, index: usize, command: Command) {
+        self.commands.insert(index, command);
+    }
+    
+    /// Remove and return the command at `index`. Panics if out of bounds.
+    pub fn remove_command(&mut self, index: usize) -> Command {
+        self.commands.remove(index)
+    }
+    
+    /// Replace the command at `index`, returning the old one. Panics if out
+    /// of bounds.
+    pub fn replace_command(&mut self, index: usize, command: Command) -> Command {
+        std::mem::replace(&mut self.commands[index], command)
+    }
+    
     pub fn iter(&self) -> impl Iterator<Item = &Command> {
         self.commands.iter()
     }
-}
\ No newline at end of file
+    
+    /// Commands of the given kind, in order
+    pub fn commands_of_kind(&self, kind: CommandKind) -> impl Iterator<Item = &Command> {
+        self.iter().filter(move |cmd| cmd.kind() == kind)
+    }
+    
+    /// Node pairs of the `E` commands, in order
+    pub fn entanglements(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.iter().filter_map(|cmd| match cmd {
+            Command::E { nodes } => Some(*nodes),
+            _ => None,
+        })
+    }
+    
+    /// Preparation of an `n`-qubit GHZ state `(|0…0⟩ + |1…1⟩)/√2` on nodes
+    /// `0..n`, all of which are outputs.
+    ///
+    /// Node 0 is prepared in |+⟩ and each further node is attached with a CX
+    /// realised as `N`, `E` and a Hadamard on the target.
+    pub fn ghz(n: usize) -> Pattern {
+        let mut pattern = Pattern::new(vec![], (0..n).collect());
+        if n == 0 {
+            return pattern;
+        }
+        
+        pattern.add_command(Command::N { node: 0 });
+        for node in 1..n {
+            pattern.add_command(Command::N { node });
+            pattern.add_command(Command::E {
+                nodes: (node - 1, node),
+            });
+            pattern.add_command(Command::C {
+                node,
+                clifford: vec![CliffordGate::H],
+            });
+        }
+        pattern
+    }
+    
+    /// Copy of the pattern with pairs of `E` commands on the same edge removed
+    /// when nothing but other entanglements acts on either endpoint between
+    /// them (CZs commute with each other and square to the identity)
+    pub fn cancel_redundant_edges(&self) -> Pattern {
+        let mut pending: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut removed = vec![false; self.commands.len()];
+        
+        for (idx, cmd) in self.commands.iter().enumerate() {
+            match cmd {
+                Command::E { nodes: (a, b) } => {
+                    let edge = ((*a).min(*b), (*a).max(*b));
+                    if let Some(first) = pending.remove(&edge) {
+                        removed[first] = true;
+                        removed[idx] = true;
+                    } else {
+                        pending.insert(edge, idx);
+                    }
+                }
+                other => {
+                    let touched = other.nodes();
+                    pending.retain(|(a, b), _| !touched.contains(a) && !touched.contains(b));
+                }
+            }
+        }
+        
+        let mut pattern = self.clone();
+        pattern.commands = self
+            .commands
+            .iter()
+            .zip(removed)
+            .filter(|(_, removed)| !removed)
+            .map(|(cmd, _)| cmd.clone())
+            .collect();
+        pattern
+    }
+    
+    /// Copy of the pattern without measured ancillas that cannot affect the
+    /// outputs, together with every command acting on them.
+    ///
+    /// An ancilla is pruned when it is prepared by the pattern (not an input),
+    /// never entangled, not an output or explicit classical output, and its
+    /// outcome appears in no correction domain. Removing corrections on a
+    /// pruned node may free further ancillas, so this repeats to a fixpoint.
+    pub fn prune_unused_measurements(&self) -> Pattern {
+        let mut pattern = self.clone();
+        
+        loop {
+            let referenced: HashSet<usize> = pattern
+                .iter()
+                .filter_map(|cmd| cmd.domain())
+                .flatten()
+                .cloned()
+                .collect();
+            let entangled: HashSet<usize> = pattern
+                .entanglements()
+                .flat_map(|(a, b)| [a, b])
+                .collect();
+            
+            let prunable = pattern.measured_nodes().into_iter().find(|node| {
+                !referenced.contains(node)
+                    && !entangled.contains(node)
+                    && !pattern.input_nodes.contains(node)
+                    && !pattern.output_nodes.contains(node)
+                    && !pattern.classical_output_nodes.contains(node)
+            });
+            
+            match prunable {
+                Some(node) => pattern.commands.retain(|cmd| !cmd.nodes().contains(&node)),
+                None => return pattern,
+            }
+        }
+    }
+    
+    /// Copy of the pattern measuring only in the XY plane, for backends
+    /// without YZ/XZ measurements. Each YZ/XZ measurement (soft, adaptive
+    /// and inside branches included) gets a basis-change Clifford on its
+    /// node first: `YZ(α)` becomes `H` then `XY(α)`, and `XZ(α)` becomes
+    /// `SDG`, `H` then `XY(-α)`, with the same outcome statistics. `MZ` is
+    /// kept, as are XZ measurements of nodes with a symbolic angle, since a
+    /// parameter cannot be negated.
+    pub fn to_xy_plane(&self) -> Pattern {
+        let mut pattern = self.clone();
+        pattern.commands = measurements_to_xy(&self.commands, &self.angle_parameters);
+        pattern
+    }
+    
+    /// Indices of the later commands that directly depend on command
+    /// `command_index`: those acting on a qubit it acts on, and, for a
+    /// measurement, those whose domain references the measured node.
+    ///
+    /// Panics if `command_index` is out of bounds.
+    pub fn dependents_of(&self, command_index: usize) -> Vec<usize> {
+        let cmd = &self.commands[command_index];
+        let touched = cmd.nodes();
+        let measured = cmd.measured_node();
+        
+        self.commands
+            .iter()
+            .enumerate()
+            .skip(command_index + 1)
+            .filter(|(_, later)| {
+                later.nodes().iter().any(|n| touched.contains(n))
+                    || measured.is_some_and(|m| later.domain().is_some_and(|d| d.contains(&m)))
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+    
+    /// Plane and angle of the measurement of `node`, if it is measured. A
+    /// Z-basis `MZ` is reported as the YZ plane at angle 0, and an adaptive
+    /// measurement with a computed angle by its base angle.
+    pub fn measurement_of(&self, node: usize) -> Option<(Plane, f64)> {
+        self.measurements_of(node).next()
+    }
+    
+    /// Plane and angle of every measurement of `node` in command order, as
+    /// reported by `measurement_of`; a node prepared again after being
+    /// measured has one per preparation
+    pub fn measurements_of(&self, node: usize) -> impl Iterator<Item = (Plane, f64)> + '_ {
+        self.iter().filter_map(move |cmd| match cmd {
+            Command::M {
+                node: n,
+                plane,
+                angle,
+            }
+            | Command::MSoft {
+                node: n,
+                plane,
+                angle,
+            } if *n == node => Some((*plane, *angle)),
+            Command::MAdaptive {
+                node: n,
+                plane,
+                angle: Angle::Fixed(angle) | Angle::Computed { base: angle, .. },
+            } if *n == node => Some((*plane, *angle)),
+            Command::MZ { node: n } if *n == node => Some((Plane::YZ, 0.0)),
+            _ => None,
+        })
+    }
+    
+    /// Every node with a measurement command (`M`, `MZ`, `MSoft` or
+    /// `MAdaptive`), including output nodes
+    pub fn measured_nodes(&self) -> BTreeSet<usize> {
+        self.iter()
+            .filter_map(|cmd| cmd.measured_node())
+            .collect()
+    }
+    
+    /// Nodes prepared by `N` that are neither inputs nor outputs
+    pub fn ancilla_nodes(&self) -> BTreeSet<usize> {
+        let data = self.data_nodes();
+        self.commands_of_kind(CommandKind::N)
+            .map(|cmd| cmd.nodes()[0])
+            .filter(|node| !data.contains(node))
+            .collect()
+    }
+    
+    /// Input and output nodes
+    pub fn data_nodes(&self) -> BTreeSet<usize> {
+        self.input_nodes
+            .iter()
+            .chain(&self.output_nodes)
+            .cloned()
+            .collect()
+    }
+    
+    /// Pauli corrections (`X`/`Z` commands) grouped by the measurement round
+    /// in which they can run. Round `k` starts once the `k`-th measurement
+    /// (in command order) has completed, so each correction lands in the
+    /// round of the last measurement in its domain; corrections on
+    /// classical inputs or with empty domains run in round 0. Trailing empty
+    /// rounds are dropped.
+    pub fn correction_schedule(&self) -> Vec<Vec<(usize, Pauli, HashSet<usize>)>> {
+        let mut round_of: HashMap<usize, usize> = HashMap::new();
+        for node in self.iter().filter_map(|cmd| cmd.measured_node()) {
+            let round = round_of.len();
+            round_of.entry(node).or_insert(round);
+        }
+        
+        let mut schedule: Vec<Vec<(usize, Pauli, HashSet<usize>)>> = Vec::new();
+        for cmd in self.iter() {
+            let (node, pauli, domain) = match cmd {
+                Command::X { node, domain } => (*node, Pauli::X, domain),
+                Command::Z { node, domain } => (*node, Pauli::Z, domain),
+                _ => continue,
+            };
+            let round = domain
+                .iter()
+                .filter_map(|d| round_of.get(d))
+                .max()
+                .copied()
+                .unwrap_or(0);
+            if schedule.len() <= round {
+                schedule.resize_with(round + 1, Vec::new);
+            }
+            schedule[round].push((node, pauli, domain.clone()));
+        }
+        schedule
+    }
+    
+    /// Extract the sub-pattern made of the commands in `range`.
+    ///
+    /// Inputs are the nodes live when the slice starts (the original inputs
+    /// for a slice starting at 0), and outputs are the nodes still live when
+    /// it ends. Earlier outcomes used by the slice's corrections become
+    /// classical inputs. Panics if `range` is out of bounds, like slice
+    /// indexing.
+    pub fn slice(&self, range: Range<usize>) -> Pattern {
+        let mut live = self.input_nodes.clone();
+        let update = |live: &mut Vec<usize>, cmd: &Command| match cmd {
+            Command::N { node } if !live.contains(node) => live.push(*node),
+            Command::M { node, .. }
+            | Command::MZ { node }
+            | Command::MSoft { node, .. }
+            | Command::MAdaptive { node, .. } => live.retain(|n| n != node),
+            _ => {}
+        };
+        
+        for cmd in &self.commands[..range.start] {
+            update(&mut live, cmd);
+        }
+        let input_nodes = live.clone();
+        
+        let commands = self.commands[range.clone()].to_vec();
+        for cmd in &commands {
+            update(&mut live, cmd);
+        }
+        
+        // Internal preparations only apply while the original inputs are kept
+        let input_states = if range.start == 0 {
+            self.input_states.clone()
+        } else {
+            HashMap::new()
+        };
+        
+        // Keep only the classical outputs measured within the slice
+        let classical_output_nodes = self
+            .classical_output_nodes
+            .iter()
+            .cloned()
+            .filter(|node| {
+                commands
+                    .iter()
+                    .any(|cmd| cmd.measured_node() == Some(*node))
+            })
+            .collect();
+        
+        // Outcomes measured before the slice but used by its corrections
+        // become classical inputs
+        let measured_before: HashSet<usize> = self.commands[..range.start]
+            .iter()
+            .filter_map(|cmd| cmd.measured_node())
+            .collect();
+        let mut classical_input_nodes = self.classical_input_nodes.clone();
+        let carried: BTreeSet<usize> = commands
+            .iter()
+            .filter_map(|cmd| cmd.domain())
+            .flatten()
+            .cloned()
+            .filter(|node| measured_before.contains(node) && !classical_input_nodes.contains(node))
+            .collect();
+        classical_input_nodes.extend(carried);
+        
+        Pattern {
+            input_nodes,
+            output_nodes: live,
+            commands,
+            input_states,
+            classical_output_nodes,
+            classical_input_nodes,
+            angle_parameters: self.angle_parameters.clone(),
+        }
+    }
+    
+    /// Canonical stabilizer generators `X_i ∏_{j∈N(i)} Z_j` of the graph state
+    /// built by the pattern's preparations and entanglements, one per node in
+    /// ascending node order. Self-loops `E(i, i)` are not edges of a graph
+    /// state and are skipped (`validate` reports them).
+    pub fn stabilizers(&self) -> Vec<PauliString> {
+        let mut nodes: BTreeSet<usize> = self.input_nodes.iter().cloned().collect();
+        let mut edges: BTreeSet<(usize, usize)> = BTreeSet::new();
+        
+        for cmd in self.iter() {
+            match cmd {
+                Command::N { node } => {
+                    nodes.insert(*node);
+                }
+                Command::E { nodes: (a, b) } if a == b => {}
+                Command::E { nodes: (a, b) } => {
+                    // Applying CZ twice on the same edge cancels out
+                    let edge = ((*a).min(*b), (*a).max(*b));
+                    if !edges.remove(&edge) {
+                        edges.insert(edge);
+                    }
+                }
+                _ => {}
+            }
+        }
+        
+        nodes
+            .iter()
+            .map(|&i| {
+                let mut stabilizer = PauliString::new();
+                stabilizer.insert(i, Pauli::X);
+                for &(a, b) in &edges {
+                    if a == i {
+                        stabilizer.insert(b, Pauli::Z);
+                    } else if b == i {
+                        stabilizer.insert(a, Pauli::Z);
+                    }
+                }
+                stabilizer
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,17 @@
+use crate::types::Pattern;
+use serde_yaml::with::singleton_map_recursive;
+
+impl Pattern {
+    /// Parse a pattern from YAML. Commands use the same `{N: {node: 0}}`
+    /// map shape as the JSON representation rather than YAML tags.
+    pub fn from_yaml(yaml: &str) -> Result<Pattern, serde_yaml::Error> {
+        singleton_map_recursive::deserialize(serde_yaml::Deserializer::from_str(yaml))
+    }
+    
+    pub fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
+        let mut out = Vec::new();
+        let mut serializer = serde_yaml::Serializer::new(&mut out);
+        singleton_map_recursive::serialize(self, &mut serializer)?;
+        Ok(String::from_utf8(out).expect("serde_yaml emits UTF-8"))
+    }
+}